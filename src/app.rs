@@ -39,7 +39,7 @@ impl App {
         (feed_ids, Result<Vec<crate::rss::FeedId>>),
         (feed_subscription_input, String),
         (force_redraw, Result<()>),
-        (http_client, ureq::Agent),
+        (http_client, crate::rss::HttpClient),
         (mode, Mode),
         (selected, Selected),
         (selected_feed_id, crate::rss::FeedId),
@@ -61,6 +61,7 @@ impl App {
         (reset_feed_subscription_input, ()),
         (select_feeds, ()),
         (delete_feed, Result<()>),
+        (mark_feed_read, Result<()>),
         (toggle_help, Result<()>),
         (toggle_read, Result<()>),
         (toggle_read_mode, Result<()>),
@@ -123,6 +124,7 @@ impl App {
             (KeyCode::Enter, _) => self.on_enter(),
             (KeyCode::Char('?'), _) => self.toggle_help(),
             (KeyCode::Char('a'), _) => self.toggle_read_mode(),
+            (KeyCode::Char('m'), _) => self.mark_feed_read(),
             (KeyCode::Char('e'), _) | (KeyCode::Char('i'), _) => {
                 let mut inner = self.inner.lock().unwrap();
                 inner.mode = Mode::Editing;
@@ -166,7 +168,7 @@ pub struct AppImpl {
     // database stuff
     pub conn: rusqlite::Connection,
     // network stuff
-    pub http_client: ureq::Agent,
+    pub http_client: crate::rss::HttpClient,
     // feed stuff
     pub current_feed: Option<crate::rss::Feed>,
     pub feeds: util::StatefulList<crate::rss::Feed>,
@@ -180,7 +182,6 @@ pub struct AppImpl {
     pub entry_lines_rendered_len: u16,
     pub entry_column_width: u16,
     // modes
-    pub should_quit: bool,
     pub selected: Selected,
     pub mode: Mode,
     pub read_mode: ReadMode,
@@ -200,9 +201,11 @@ impl AppImpl {
     ) -> Result<AppImpl> {
         let mut conn = rusqlite::Connection::open(&options.database_path)?;
 
-        let http_client = ureq::AgentBuilder::new()
-            .timeout_read(options.network_timeout)
-            .build();
+        let http_client = crate::rss::FetchOptions {
+            timeout: options.network_timeout,
+            ..Default::default()
+        }
+        .http_client();
 
         crate::rss::initialize_db(&mut conn)?;
         let feeds: util::StatefulList<crate::rss::Feed> = vec![].into();
@@ -218,7 +221,6 @@ impl AppImpl {
         let mut app = AppImpl {
             conn,
             http_client,
-            should_quit: false,
             error_flash: vec![],
             feeds,
             entries,
@@ -255,7 +257,7 @@ impl AppImpl {
     pub fn delete_feed(&mut self) -> Result<()> {
         if matches!(self.selected, Selected::Feeds) && matches!(self.mode(), Mode::Editing) {
             let feed_id = self.selected_feed_id();
-            crate::rss::delete_feed(&mut self.conn, feed_id)?;
+            crate::rss::unsubscribe_feed(&mut self.conn, feed_id)?;
 
             // Remove the feed in app state
             let feeds_len = self.feeds.items.len();
@@ -376,14 +378,9 @@ impl AppImpl {
 
     fn page_up(&mut self) {
         if matches!(self.selected, Selected::Entry(_)) {
-            self.entry_scroll_position = if let Some(position) = self
+            self.entry_scroll_position = self
                 .entry_scroll_position
-                .checked_sub(self.entry_lines_rendered_len)
-            {
-                position
-            } else {
-                0
-            };
+                .saturating_sub(self.entry_lines_rendered_len);
         };
     }
 
@@ -521,11 +518,21 @@ impl AppImpl {
         Ok(())
     }
 
-    pub fn http_client(&self) -> ureq::Agent {
+    pub fn http_client(&self) -> crate::rss::HttpClient {
         // this is cheap because it only clones a struct containing two Arcs
         self.http_client.clone()
     }
 
+    pub fn mark_feed_read(&mut self) -> Result<()> {
+        if matches!(self.selected, Selected::Feeds) {
+            let feed_id = self.selected_feed_id();
+            crate::rss::mark_feed_read(&self.conn, feed_id)?;
+            self.update_current_entries()?;
+        }
+
+        Ok(())
+    }
+
     pub fn toggle_read_mode(&mut self) -> Result<()> {
         match (&self.read_mode, &self.selected) {
             (ReadMode::ShowRead, Selected::Feeds) | (ReadMode::ShowRead, Selected::Entries) => {
@@ -0,0 +1,155 @@
+//! One value to hold on to when using russ as a library,
+//! rather than passing a connection and an http client to every function in `rss`.
+
+use crate::rss::{
+    Entry, EntryId, EntryQuery, Feed, FeedId, FetchOptions, HttpClient, RefreshSummary,
+};
+use anyhow::Result;
+use std::path::Path;
+
+/// an open feed database and the http client to fetch feeds with.
+/// the methods are the functions in `rss` of the same purpose,
+/// see those for the details.
+pub struct RussClient {
+    conn: rusqlite::Connection,
+    http_client: HttpClient,
+}
+
+impl RussClient {
+    /// opens the database at `database_path`, creating it if it does not exist,
+    /// and brings its schema up to date
+    pub fn open(database_path: impl AsRef<Path>, fetch_options: &FetchOptions) -> Result<Self> {
+        let conn = rusqlite::Connection::open(database_path)?;
+        Self::new(conn, fetch_options.http_client())
+    }
+
+    /// like `open`, for a database that only lives as long as the client
+    pub fn open_in_memory(fetch_options: &FetchOptions) -> Result<Self> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        Self::new(conn, fetch_options.http_client())
+    }
+
+    /// a client over a connection that is already open,
+    /// bringing its schema up to date
+    pub fn new(mut conn: rusqlite::Connection, http_client: HttpClient) -> Result<Self> {
+        crate::rss::initialize_db(&mut conn)?;
+        Ok(Self { conn, http_client })
+    }
+
+    /// for the functions in `rss` that the client does not have a method for
+    pub fn connection(&mut self) -> &mut rusqlite::Connection {
+        &mut self.conn
+    }
+
+    /// the http client the feeds are fetched with, see `rss::HttpClient`
+    pub fn http_client(&self) -> &HttpClient {
+        &self.http_client
+    }
+
+    /// see `rss::subscribe_to_feed`
+    pub fn subscribe(&mut self, url: &str) -> Result<FeedId> {
+        crate::rss::subscribe_to_feed(&self.http_client, &mut self.conn, url)
+    }
+
+    /// see `rss::unsubscribe_feed`
+    pub fn unsubscribe(&mut self, feed_id: FeedId) -> Result<usize> {
+        crate::rss::unsubscribe_feed(&mut self.conn, feed_id)
+    }
+
+    /// see `rss::refresh_feed`
+    pub fn refresh(&mut self, feed_id: FeedId) -> Result<RefreshSummary> {
+        crate::rss::refresh_feed(&self.http_client, &mut self.conn, feed_id)
+    }
+
+    /// see `rss::refresh_feed_and_notify`
+    pub fn refresh_and_notify(
+        &mut self,
+        feed_id: FeedId,
+        on_new_entry: impl FnMut(&Entry),
+    ) -> Result<RefreshSummary> {
+        crate::rss::refresh_feed_and_notify(
+            &self.http_client,
+            &mut self.conn,
+            feed_id,
+            on_new_entry,
+        )
+    }
+
+    /// see `rss::refresh_all_feeds`
+    pub fn refresh_all(
+        &mut self,
+        concurrency: usize,
+    ) -> Result<Vec<(FeedId, Result<RefreshSummary>)>> {
+        crate::rss::refresh_all_feeds(&self.http_client, &mut self.conn, concurrency)
+    }
+
+    /// see `rss::get_feeds`
+    pub fn feeds(&self) -> Result<Vec<Feed>> {
+        crate::rss::get_feeds(&self.conn)
+    }
+
+    /// see `rss::get_feed`
+    pub fn feed(&self, feed_id: FeedId) -> Result<Feed> {
+        crate::rss::get_feed(&self.conn, feed_id)
+    }
+
+    /// the feed's entries, newest first
+    pub fn entries(&self, feed_id: FeedId) -> Result<Vec<Entry>> {
+        self.query_entries(feed_id, &EntryQuery::default())
+    }
+
+    /// see `rss::query_entries`
+    pub fn query_entries(&self, feed_id: FeedId, query: &EntryQuery) -> Result<Vec<Entry>> {
+        crate::rss::query_entries(&self.conn, feed_id, query)
+    }
+
+    /// see `rss::get_entry`
+    pub fn entry(&self, entry_id: EntryId) -> Result<Entry> {
+        crate::rss::get_entry(&self.conn, entry_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve_feed;
+
+    #[test]
+    fn it_subscribes_refreshes_and_lists_over_an_in_memory_database() {
+        let base_url = serve_feed(&["/feed"]);
+
+        let mut client = RussClient::open_in_memory(&FetchOptions::default()).unwrap();
+        assert!(client.feeds().unwrap().is_empty());
+
+        let feed_id = client.subscribe(&format!("{base_url}/feed")).unwrap();
+
+        let feeds = client.feeds().unwrap();
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].id, feed_id);
+        assert_eq!(
+            client.feed(feed_id).unwrap().title.as_deref(),
+            Some("Example")
+        );
+
+        let entries = client.entries(feed_id).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(client.entry(entries[0].id).unwrap().link, entries[0].link);
+
+        let refresh_summary = client.refresh(feed_id).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+
+        assert_eq!(client.unsubscribe(feed_id).unwrap(), 2);
+        assert!(client.feeds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_brings_an_open_connection_up_to_date() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let mut client = RussClient::new(conn, FetchOptions::default().http_client()).unwrap();
+
+        assert_eq!(
+            crate::rss::schema_version(client.connection()).unwrap(),
+            crate::rss::LATEST_SCHEMA_VERSION
+        );
+    }
+}
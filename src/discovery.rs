@@ -0,0 +1,221 @@
+//! Finding the feeds a website advertises, so people can subscribe
+//! with the address of the site rather than the address of its feed.
+
+use crate::rss::{CacheValidators, Credentials, Fetched, HttpClient};
+use anyhow::Result;
+
+/// the feed urls that the page at `url` links to with
+/// `<link rel="alternate" type="application/rss+xml" href="...">`
+/// (or `application/atom+xml` or `application/feed+json`), in the order the page lists them.
+/// relative hrefs are resolved against the page's url, after redirects.
+/// if `url` is itself a feed, the result is just `url`.
+/// `credentials` are for pages behind HTTP basic auth.
+pub fn discover_feeds(
+    http_client: &HttpClient,
+    url: &str,
+    credentials: Option<&Credentials>,
+) -> Result<Vec<String>> {
+    let Fetched {
+        resp,
+        url: page_url,
+        ..
+    } = crate::rss::get(
+        http_client,
+        url,
+        &CacheValidators::default(),
+        credentials,
+        None,
+    )?;
+
+    let body = crate::rss::read_body(resp)?;
+
+    if crate::rss::is_feed(&body) {
+        return Ok(vec![url.to_owned()]);
+    }
+
+    let page_url = url::Url::parse(&page_url)?;
+
+    let mut feed_urls: Vec<String> = vec![];
+
+    for attributes in link_tags(&body) {
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+
+        let is_alternate = attribute("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("alternate"))
+        });
+
+        let is_feed_type = attribute("type").is_some_and(|type_| {
+            let type_ = type_.trim();
+            type_.eq_ignore_ascii_case("application/rss+xml")
+                || type_.eq_ignore_ascii_case("application/atom+xml")
+                || type_.eq_ignore_ascii_case("application/feed+json")
+        });
+
+        let href = match attribute("href").map(|href| href.trim()) {
+            Some(href) if is_alternate && is_feed_type && !href.is_empty() => href,
+            _ => continue,
+        };
+
+        let Ok(feed_url) = page_url.join(href) else {
+            continue;
+        };
+
+        let feed_url = feed_url.to_string();
+        if !feed_urls.contains(&feed_url) {
+            feed_urls.push(feed_url);
+        }
+    }
+
+    Ok(feed_urls)
+}
+
+/// the attributes of every `<link>` tag in `html`, with entities in their values unescaped.
+/// HTML is too loose to parse as XML, so this only looks for tags and does not
+/// care about the structure around them.
+fn link_tags(html: &str) -> Vec<Vec<(String, String)>> {
+    let mut tags = vec![];
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        let is_link = rest
+            .get(..4)
+            .is_some_and(|name| name.eq_ignore_ascii_case("link"))
+            && rest[4..].starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>');
+
+        if !is_link {
+            continue;
+        }
+
+        let (attributes, after_tag) = parse_attributes(&rest[4..]);
+        tags.push(attributes);
+        rest = after_tag;
+    }
+
+    tags
+}
+
+/// parses `name="value"`, `name='value'`, `name=value` and bare `name` attributes
+/// up to the end of the tag, returning them with the rest of the input after the tag.
+fn parse_attributes(mut s: &str) -> (Vec<(String, String)>, &str) {
+    let mut attributes = vec![];
+
+    loop {
+        s = s.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+
+        if s.is_empty() {
+            return (attributes, s);
+        }
+
+        if let Some(after_tag) = s.strip_prefix('>') {
+            return (attributes, after_tag);
+        }
+
+        let name_end = s
+            .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/'))
+            .unwrap_or(s.len());
+        let name = s[..name_end].to_owned();
+        s = s[name_end..].trim_start();
+
+        let value = match s.strip_prefix('=') {
+            Some(after_equals) => {
+                let after_equals = after_equals.trim_start();
+                let (value, after_value) = match after_equals.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let quoted = &after_equals[1..];
+                        match quoted.find(quote) {
+                            Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                            None => (quoted, ""),
+                        }
+                    }
+                    _ => {
+                        let end = after_equals
+                            .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                            .unwrap_or(after_equals.len());
+                        (&after_equals[..end], &after_equals[end..])
+                    }
+                };
+                s = after_value;
+                value
+            }
+            None => "",
+        };
+
+        let value = quick_xml::escape::unescape(value)
+            .map(|value| value.into_owned())
+            .unwrap_or_else(|_| value.to_owned());
+
+        attributes.push((name, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{serve, serve_feed, TestResponse};
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+    <html>
+      <head>
+        <meta charset=utf-8>
+        <link rel="stylesheet" href="/style.css">
+        <LINK REL="alternate" TYPE="application/rss+xml" HREF="/feed.xml">
+        <link rel='alternate' type='application/atom+xml' href='https://example.com/atom?a=1&amp;b=2' />
+        <link rel="alternate" type="text/html" hreflang="fr" href="/fr">
+        <link rel="alternate" type="application/feed+json" href="feed.json">
+        <link rel=alternate type=application/rss+xml href=/feed.xml>
+      </head>
+      <body><p>not a feed</p></body>
+    </html>"#;
+
+    fn test_http_client() -> HttpClient {
+        crate::rss::FetchOptions::default().http_client()
+    }
+
+    #[test]
+    fn it_finds_relative_and_absolute_feed_links() {
+        let base_url = serve(|request| match request.path.as_str() {
+            "/" => TestResponse::status(301).with_header("Location", "/blog/"),
+            "/blog/" => TestResponse::ok(PAGE),
+            _ => TestResponse::status(404),
+        });
+
+        let feed_urls = discover_feeds(&test_http_client(), &format!("{base_url}/"), None).unwrap();
+
+        assert_eq!(
+            feed_urls,
+            vec![
+                format!("{base_url}/feed.xml"),
+                "https://example.com/atom?a=1&b=2".to_owned(),
+                format!("{base_url}/blog/feed.json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_returns_the_url_of_a_feed() {
+        let base_url = serve_feed(&["/feed.xml"]);
+        let url = format!("{base_url}/feed.xml");
+
+        assert_eq!(
+            discover_feeds(&test_http_client(), &url, None).unwrap(),
+            vec![url]
+        );
+    }
+
+    #[test]
+    fn it_finds_nothing_on_a_page_without_feed_links() {
+        let base_url = serve(|_request| TestResponse::ok("<html><body>hi</body></html>"));
+
+        assert!(discover_feeds(&test_http_client(), &base_url, None)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Errors that callers may want to tell apart,
+//! for example to show a more helpful message.
+//! They are returned wrapped in `anyhow::Error`,
+//! so match on them with `anyhow::Error::downcast_ref`,
+//! or use `is_retryable` to decide whether to try again later.
+
+use crate::rss::{EntryId, FeedId};
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum Error {
+    /// the server responded with a status other than 2xx
+    HttpStatus(u16),
+    /// the response could not be parsed as RSS, Atom, or JSON Feed
+    NotAFeed,
+    /// the server took longer than `FetchOptions::timeout` to respond
+    Timeout,
+    /// the connection to the server could not be made or was dropped,
+    /// like when the server is down or the network is
+    Network,
+    /// there is already a feed with this url, see `normalize_feed_link`.
+    /// `subscribe_to_feed` refreshes that feed rather than returning this.
+    AlreadySubscribed(FeedId),
+    /// the url to subscribe to does not parse, or is not http, https, or file
+    InvalidUrl(String),
+    /// there is no feed with this id, like one that was unsubscribed from
+    FeedNotFound(FeedId),
+    /// there is no entry with this id, like one that was purged
+    EntryNotFound(EntryId),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::HttpStatus(status) => write!(f, "the server responded with HTTP {status}"),
+            Error::NotAFeed => write!(f, "the response is not an RSS, Atom, or JSON Feed"),
+            Error::Timeout => write!(f, "the server took too long to respond"),
+            Error::Network => write!(f, "could not connect to the server"),
+            Error::AlreadySubscribed(_) => write!(f, "already subscribed to this feed"),
+            Error::InvalidUrl(url) => write!(f, "{url} is not an http, https, or file url"),
+            Error::FeedNotFound(feed_id) => write!(f, "there is no feed with id {feed_id}"),
+            Error::EntryNotFound(entry_id) => write!(f, "there is no entry with id {entry_id}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// whether the same request may succeed if it is tried again later,
+    /// like after a timeout or a `503 Service Unavailable`,
+    /// rather than fail the same way until something changes,
+    /// like a feed that is gone or a url that is not a feed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::HttpStatus(status) => crate::rss::is_retryable_status(*status),
+            Error::Timeout | Error::Network => true,
+            Error::NotAFeed
+            | Error::AlreadySubscribed(_)
+            | Error::InvalidUrl(_)
+            | Error::FeedNotFound(_)
+            | Error::EntryNotFound(_) => false,
+        }
+    }
+}
+
+/// like `Error::is_retryable`, for any error russ returns.
+/// the database being busy with another connection is retryable too.
+/// other errors, like a database that can't be opened, are not.
+pub fn is_retryable(e: &anyhow::Error) -> bool {
+    if let Some(e) = e.downcast_ref::<Error>() {
+        return e.is_retryable();
+    }
+
+    match e.downcast_ref::<rusqlite::Error>() {
+        Some(rusqlite::Error::SqliteFailure(e, _)) => matches!(
+            e.code,
+            rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tells_errors_worth_retrying_apart() {
+        for (e, retryable) in [
+            (Error::HttpStatus(404), false),
+            (Error::HttpStatus(410), false),
+            (Error::HttpStatus(429), true),
+            (Error::HttpStatus(503), true),
+            (Error::NotAFeed, false),
+            (Error::Timeout, true),
+            (Error::Network, true),
+            (Error::AlreadySubscribed(1), false),
+            (Error::InvalidUrl("not a url".to_owned()), false),
+            (Error::FeedNotFound(1), false),
+            (Error::EntryNotFound(1), false),
+        ] {
+            assert_eq!(e.is_retryable(), retryable, "{e}");
+        }
+    }
+
+    #[test]
+    fn it_finds_the_reason_an_error_is_retryable_in_its_context() {
+        let timeout = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::TimedOut))
+            .context(Error::Timeout)
+            .context("Failed to fetch feed https://example.com/feed");
+        assert!(is_retryable(&timeout));
+
+        let not_a_feed = anyhow::Error::new(Error::NotAFeed).context("while refreshing");
+        assert!(!is_retryable(&not_a_feed));
+
+        let busy = anyhow::Error::new(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        ));
+        assert!(is_retryable(&busy));
+
+        let no_rows = anyhow::Error::new(rusqlite::Error::QueryReturnedNoRows);
+        assert!(!is_retryable(&no_rows));
+    }
+}
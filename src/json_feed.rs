@@ -0,0 +1,84 @@
+//! The parts of JSON Feed (<https://www.jsonfeed.org/version/1.1/>) that russ uses.
+//! Both version 1 and version 1.1 feeds parse,
+//! and fields russ does not use are ignored.
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct JsonFeed {
+    pub version: String,
+    pub title: Option<String>,
+    pub home_page_url: Option<String>,
+    pub feed_url: Option<String>,
+    /// a large square image for the feed
+    pub icon: Option<String>,
+    /// a small square image for the feed, used if there is no `icon`
+    pub favicon: Option<String>,
+    /// version 1.1, like `en-US`
+    pub language: Option<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+impl JsonFeed {
+    /// JSON that parses as a JSON Feed, but is not one, since all of its fields are optional
+    pub fn is_json_feed(&self) -> bool {
+        self.version.starts_with("https://jsonfeed.org/version/")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Item {
+    /// a string, but some feeds use numbers anyway
+    pub id: Option<serde_json::Value>,
+    pub url: Option<String>,
+    pub external_url: Option<String>,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    /// the main image of the item
+    pub image: Option<String>,
+    pub date_published: Option<String>,
+    /// version 1.1, for items not in the feed's `language`
+    pub language: Option<String>,
+    /// version 1.1
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    /// version 1, replaced by `authors`
+    pub author: Option<Author>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Item {
+    pub fn id(&self) -> Option<String> {
+        match &self.id {
+            Some(serde_json::Value::String(id)) => Some(id.to_owned()),
+            Some(serde_json::Value::Number(id)) => Some(id.to_string()),
+            _ => None,
+        }
+        .filter(|id| !id.is_empty())
+    }
+
+    pub fn author_name(&self) -> Option<&str> {
+        self.authors
+            .iter()
+            .chain(self.author.as_ref())
+            .find_map(|author| author.name.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Author {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Attachment {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub size_in_bytes: Option<u64>,
+}
@@ -0,0 +1,155 @@
+//! Importing subscriptions exported as JSON, for readers that export JSON rather than OPML.
+
+use crate::opml::ImportResult;
+use crate::rss::HttpClient;
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// one subscription in an export. other fields are ignored.
+#[derive(Debug, Deserialize)]
+struct JsonSubscription {
+    title: Option<String>,
+    feed_url: Option<String>,
+}
+
+/// subscribes to every feed in `json`, an array of subscriptions like
+/// `[{ "title": "Example", "feed_url": "https://example.com/feed" }]`,
+/// returning the result for each feed url, like `opml::import_opml`.
+/// subscriptions without a `feed_url` are skipped.
+/// feeds are fetched `concurrency` at a time, see `rss::subscribe_many`.
+/// new feeds whose title in the export is not the one they have for themselves
+/// are renamed to it, as it is likely one the user chose, see `rss::rename_feed`.
+pub fn import_json_subscriptions(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    json: &str,
+    concurrency: usize,
+) -> Result<Vec<(String, ImportResult)>> {
+    let subscriptions = serde_json::from_str::<Vec<JsonSubscription>>(json)?
+        .into_iter()
+        .filter_map(|subscription| {
+            let feed_url = subscription.feed_url?.trim().to_owned();
+            let title = subscription
+                .title
+                .map(|title| title.trim().to_owned())
+                .filter(|title| !title.is_empty());
+            Some((feed_url, title)).filter(|(feed_url, _)| !feed_url.is_empty())
+        })
+        .collect::<Vec<_>>();
+
+    let feed_urls = subscriptions
+        .iter()
+        .map(|(feed_url, _)| feed_url.to_owned())
+        .collect::<Vec<_>>();
+
+    let mut existing_feed_ids = crate::rss::get_feed_ids(conn)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let results = crate::rss::subscribe_many(http_client, conn, &feed_urls, concurrency, |_, _| ());
+
+    let mut import_results = Vec::with_capacity(results.len());
+
+    for ((feed_url, result), (_, title)) in results.into_iter().zip(subscriptions) {
+        let import_result = match result {
+            Ok(feed_id) if existing_feed_ids.contains(&feed_id) => {
+                ImportResult::AlreadySubscribed(feed_id)
+            }
+            Ok(feed_id) => {
+                existing_feed_ids.insert(feed_id);
+                match rename_to_exported_title(conn, feed_id, title.as_deref()) {
+                    Ok(()) => ImportResult::Subscribed(feed_id),
+                    Err(e) => ImportResult::Failed(e),
+                }
+            }
+            Err(e) => ImportResult::Failed(e),
+        };
+
+        import_results.push((feed_url, import_result));
+    }
+
+    Ok(import_results)
+}
+
+fn rename_to_exported_title(
+    conn: &rusqlite::Connection,
+    feed_id: crate::rss::FeedId,
+    title: Option<&str>,
+) -> Result<()> {
+    if let Some(title) = title {
+        if crate::rss::get_feed(conn, feed_id)?.title.as_deref() != Some(title) {
+            crate::rss::rename_feed(conn, feed_id, title)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve_feed;
+
+    #[test]
+    fn it_imports_json_subscriptions() {
+        let base_url = serve_feed(&["/a", "/b"]);
+
+        let http_client = crate::rss::FetchOptions::default().http_client();
+        let mut conn = crate::rss::open_in_memory_database().unwrap();
+
+        let json = format!(
+            r#"[
+              {{ "title": "Example", "feed_url": "{base_url}/a", "category": "Dev" }},
+              {{ "title": "My B", "feed_url": "{base_url}/b" }},
+              {{ "title": "No url" }},
+              {{ "title": "Blank url", "feed_url": " " }},
+              {{ "feed_url": "{base_url}/dead" }},
+              {{ "title": "A again", "feed_url": "{base_url}/a" }}
+            ]"#
+        );
+
+        let results = import_json_subscriptions(&http_client, &mut conn, &json, 2).unwrap();
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(feed_url, _)| feed_url.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                format!("{base_url}/a"),
+                format!("{base_url}/b"),
+                format!("{base_url}/dead"),
+                format!("{base_url}/a"),
+            ]
+        );
+
+        let (ImportResult::Subscribed(a), ImportResult::Subscribed(b)) =
+            (&results[0].1, &results[1].1)
+        else {
+            panic!("expected new subscriptions, got {results:?}");
+        };
+        assert!(matches!(results[2].1, ImportResult::Failed(_)));
+        assert!(matches!(results[3].1, ImportResult::AlreadySubscribed(a_again) if a_again == *a));
+
+        assert_eq!(
+            crate::rss::get_feed(&conn, *a).unwrap().title.as_deref(),
+            Some("Example")
+        );
+        assert_eq!(
+            crate::rss::get_feed(&conn, *b).unwrap().title.as_deref(),
+            Some("My B")
+        );
+        assert_eq!(crate::rss::get_feeds(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_does_not_import_json_that_is_not_a_list_of_subscriptions() {
+        let http_client = crate::rss::FetchOptions::default().http_client();
+        let mut conn = crate::rss::open_in_memory_database().unwrap();
+
+        assert!(
+            import_json_subscriptions(&http_client, &mut conn, r#"{"feed_url": "x"}"#, 1).is_err()
+        );
+    }
+}
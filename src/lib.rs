@@ -0,0 +1,37 @@
+#![forbid(unsafe_code)]
+
+use std::path::PathBuf;
+use std::time;
+
+pub mod app;
+pub mod client;
+pub mod discovery;
+pub mod error;
+mod json_feed;
+pub mod json_subscriptions;
+pub mod modes;
+pub mod opml;
+mod readability;
+pub mod rss;
+#[cfg(test)]
+mod test_server;
+mod ui;
+mod util;
+
+pub enum Event<I> {
+    Input(I),
+    Tick,
+}
+
+/// internal, validated options
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// feed database path
+    pub database_path: PathBuf,
+    /// time in ms between two ticks
+    pub tick_rate: u64,
+    /// number of seconds to show the flash message before clearing it
+    pub flash_display_duration_seconds: time::Duration,
+    /// RSS/Atom network request timeout in seconds
+    pub network_timeout: time::Duration,
+}
@@ -1,8 +1,6 @@
 #![forbid(unsafe_code)]
 
-use crate::modes::{Mode, Selected};
 use anyhow::Result;
-use app::App;
 use clap::Parser;
 use crossterm::event;
 use crossterm::event::{Event as CEvent, KeyCode, KeyModifiers, KeyEventKind};
@@ -12,22 +10,14 @@ use crossterm::terminal::{
 };
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
+use russ::app::App;
+use russ::modes::{Mode, Selected};
+use russ::{Event, Options};
 use std::io::stdout;
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::{thread, time};
 
-mod app;
-mod modes;
-mod rss;
-mod ui;
-mod util;
-
-pub enum Event<I> {
-    Input(I),
-    Tick,
-}
-
 // Only used to take input at the boundary.
 // Turned into `Options` with `to_options()`.
 /// A TUI RSS reader with vim-like controls and a local-first, offline-first focus
@@ -69,19 +59,6 @@ fn parse_seconds(s: &str) -> Result<time::Duration, std::num::ParseIntError> {
     Ok(time::Duration::from_secs(as_u64))
 }
 
-/// internal, validated options
-#[derive(Clone, Debug)]
-pub struct Options {
-    /// feed database path
-    database_path: PathBuf,
-    /// time in ms between two ticks
-    tick_rate: u64,
-    /// number of seconds to show the flash message before clearing it
-    flash_display_duration_seconds: time::Duration,
-    /// RSS/Atom network request timeout in seconds
-    network_timeout: time::Duration,
-}
-
 fn get_database_path(cli_options: &CliOptions) -> std::io::Result<PathBuf> {
     let database_path = if let Some(database_path) = cli_options.database_path.as_ref() {
         database_path.to_owned()
@@ -103,8 +80,8 @@ fn get_database_path(cli_options: &CliOptions) -> std::io::Result<PathBuf> {
 
 enum IoCommand {
     Break,
-    RefreshFeed(crate::rss::FeedId),
-    RefreshFeeds(Vec<crate::rss::FeedId>),
+    RefreshFeed(russ::rss::FeedId),
+    RefreshFeeds(Vec<russ::rss::FeedId>),
     SubscribeToFeed(String),
     ClearFlash,
 }
@@ -117,8 +94,7 @@ fn io_loop(
 ) -> Result<()> {
     use IoCommand::*;
 
-    let manager = r2d2_sqlite::SqliteConnectionManager::file(&options.database_path);
-    let connection_pool = r2d2::Pool::new(manager)?;
+    let connection_pool = russ::rss::connection_pool(&options.database_path)?;
 
     while let Ok(event) = rx.recv() {
         match event {
@@ -176,7 +152,7 @@ fn io_loop(
                 app.force_redraw()?;
 
                 let mut conn = connection_pool.get()?;
-                let r = crate::rss::subscribe_to_feed(
+                let r = russ::rss::subscribe_to_feed(
                     &app.http_client(),
                     &mut conn,
                     &feed_subscription_input,
@@ -187,7 +163,7 @@ fn io_loop(
                     continue;
                 }
 
-                match crate::rss::get_feeds(&conn) {
+                match russ::rss::get_feeds(&conn) {
                     Ok(feeds) => {
                         {
                             app.reset_feed_subscription_input();
@@ -219,46 +195,22 @@ fn io_loop(
 
 fn refresh_feeds<F>(
     app: &App,
-    connection_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
-    feed_ids: &[crate::rss::FeedId],
+    connection_pool: &russ::rss::ConnectionPool,
+    feed_ids: &[russ::rss::FeedId],
     mut refresh_result_handler: F,
 ) -> Result<()>
 where
     F: FnMut(&App, anyhow::Result<()>),
 {
-    let min_number_of_threads = num_cpus::get() * 2;
-    let chunk_size = feed_ids.len() / min_number_of_threads;
-    // due to usize floor division, it's possible chunk_size would be 0,
-    // so ensure it is at least 1
-    let chunk_size = chunk_size.max(1);
-    let chunks = feed_ids.chunks(chunk_size);
-
-    let join_handles: Vec<_> = chunks
-        .map(|chunk_feed_ids| {
-            let pool_get_result = connection_pool.get();
-            let http = app.http_client();
-            let chunk_feed_ids = chunk_feed_ids.to_owned();
-
-            thread::spawn(move || -> Result<Vec<Result<(), anyhow::Error>>> {
-                let mut results = vec![];
-                let mut conn = pool_get_result?;
-
-                for feed_id in chunk_feed_ids.into_iter() {
-                    results.push(crate::rss::refresh_feed(&http, &mut conn, feed_id))
-                }
+    // refreshing is mostly waiting on the network,
+    // so fetch more feeds at once than there are cores
+    let concurrency = num_cpus::get() * 2;
+    let mut conn = connection_pool.get()?;
 
-                Ok::<Vec<Result<(), anyhow::Error>>, anyhow::Error>(results)
-            })
-        })
-        .collect();
-
-    for join_handle in join_handles {
-        let chunk_results = join_handle
-            .join()
-            .expect("unable to join worker thread to io thread");
-        for chunk_result in chunk_results? {
-            refresh_result_handler(app, chunk_result)
-        }
+    let results = russ::rss::refresh_feeds(&app.http_client(), &mut conn, feed_ids, concurrency);
+
+    for (_feed_id, result) in results {
+        refresh_result_handler(app, result.map(|_refresh_summary| ()))
     }
 
     Ok(())
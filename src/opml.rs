@@ -0,0 +1,343 @@
+//! OPML import and export, for moving subscriptions between readers.
+
+use crate::rss::{FeedId, FeedKind, HttpClient};
+use anyhow::Result;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::collections::HashSet;
+
+#[derive(Debug)]
+pub enum ImportResult {
+    Subscribed(FeedId),
+    AlreadySubscribed(FeedId),
+    Failed(anyhow::Error),
+}
+
+/// subscribes to every feed in `opml`, returning the result for each feed url.
+/// feeds that are already subscribed to are refreshed, see `rss::subscribe_to_feed`,
+/// and a feed that fails to subscribe does not stop the rest of the import.
+/// feeds are fetched `concurrency` at a time, see `rss::subscribe_many`.
+/// new feeds are put in the category of the outline they are nested in.
+pub fn import_opml(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    opml: &str,
+    concurrency: usize,
+) -> Result<Vec<(String, ImportResult)>> {
+    let feed_outlines = parse_feed_outlines(opml)?;
+
+    let feed_urls = feed_outlines
+        .iter()
+        .map(|feed_outline| feed_outline.feed_url.to_owned())
+        .collect::<Vec<_>>();
+
+    let mut existing_feed_ids = crate::rss::get_feed_ids(conn)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    let results = crate::rss::subscribe_many(http_client, conn, &feed_urls, concurrency, |_, _| ());
+
+    let mut import_results = Vec::with_capacity(results.len());
+
+    for ((feed_url, result), FeedOutline { category, .. }) in results.into_iter().zip(feed_outlines)
+    {
+        let import_result = match result {
+            // the feed keeps the category it already has
+            Ok(feed_id) if existing_feed_ids.contains(&feed_id) => {
+                ImportResult::AlreadySubscribed(feed_id)
+            }
+            Ok(feed_id) => {
+                existing_feed_ids.insert(feed_id);
+                match crate::rss::set_feed_category(conn, feed_id, category.as_deref()) {
+                    Ok(()) => ImportResult::Subscribed(feed_id),
+                    Err(e) => ImportResult::Failed(e),
+                }
+            }
+            Err(e) => ImportResult::Failed(e),
+        };
+
+        import_results.push((feed_url, import_result));
+    }
+
+    Ok(import_results)
+}
+
+/// an OPML 2.0 document with an `<outline>` for every subscribed feed.
+/// feeds without a title use their feed url as the outline text,
+/// and feeds in a category are nested in an outline for that category.
+pub fn export_opml(conn: &rusqlite::Connection) -> Result<String> {
+    let uncategorized_feeds = crate::rss::get_feeds_by_category(conn, None)?;
+
+    let mut categories = vec![];
+    for category in crate::rss::list_categories(conn)? {
+        let feeds = crate::rss::get_feeds_by_category(conn, Some(&category))?;
+        categories.push((category, feeds));
+    }
+
+    let mut writer = Writer::new_with_indent(vec![], b' ', 2);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    writer
+        .create_element("opml")
+        .with_attribute(("version", "2.0"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("head")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("title")
+                        .write_text_content(BytesText::new("Russ subscriptions"))?;
+                    Ok(())
+                })?;
+
+            writer
+                .create_element("body")
+                .write_inner_content(|writer| {
+                    for (category, feeds) in &categories {
+                        writer
+                            .create_element("outline")
+                            .with_attribute(("text", category.as_str()))
+                            .with_attribute(("title", category.as_str()))
+                            .write_inner_content(|writer| write_feed_outlines(writer, feeds))?;
+                    }
+
+                    write_feed_outlines(writer, &uncategorized_feeds)
+                })?;
+
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn write_feed_outlines(
+    writer: &mut Writer<Vec<u8>>,
+    feeds: &[crate::rss::Feed],
+) -> quick_xml::Result<()> {
+    // a feed without a feed url can't be subscribed to again
+    for feed in feeds.iter().filter(|feed| feed.feed_link.is_some()) {
+        let feed_link = feed.feed_link.as_deref().unwrap_or_default();
+        let text = feed.title.as_deref().unwrap_or(feed_link);
+        let feed_kind = match feed.feed_kind {
+            FeedKind::Atom => "atom",
+            FeedKind::Json => "json",
+            FeedKind::Rss => "rss",
+        };
+
+        let mut outline = writer
+            .create_element("outline")
+            .with_attribute(("type", feed_kind))
+            .with_attribute(("text", text))
+            .with_attribute(("title", text))
+            .with_attribute(("xmlUrl", feed_link));
+
+        if let Some(link) = &feed.link {
+            outline = outline.with_attribute(("htmlUrl", link.as_str()));
+        }
+
+        outline.write_empty()?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct FeedOutline {
+    feed_url: String,
+    /// the innermost outline without an `xmlUrl` that this outline is nested in
+    category: Option<String>,
+}
+
+/// every `<outline>` with an `xmlUrl` in the document, in document order.
+/// outlines without an `xmlUrl` are categories for the outlines nested in them.
+/// categories can nest too, but russ only has one level of categories,
+/// so feeds go in the innermost one.
+fn parse_feed_outlines(opml: &str) -> Result<Vec<FeedOutline>> {
+    let mut reader = Reader::from_str(opml);
+    reader.trim_text(true);
+
+    let mut feed_outlines = vec![];
+    // the category of each open `<outline>`, `None` for feeds
+    let mut open_outlines: Vec<Option<String>> = vec![];
+
+    loop {
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) if element.name().as_ref() == b"outline" => (element, false),
+            Event::Empty(element) if element.name().as_ref() == b"outline" => (element, true),
+            Event::End(element) if element.name().as_ref() == b"outline" => {
+                open_outlines.pop();
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let mut feed_url = None;
+        let mut text = None;
+        let mut title = None;
+
+        for attribute in element.attributes() {
+            let attribute = attribute?;
+            let value = attribute
+                .decode_and_unescape_value(&reader)?
+                .trim()
+                .to_owned();
+
+            if value.is_empty() {
+                continue;
+            }
+
+            match attribute.key.as_ref() {
+                key if key.eq_ignore_ascii_case(b"xmlUrl") => feed_url = Some(value),
+                b"text" => text = Some(value),
+                b"title" => title = Some(value),
+                _ => (),
+            }
+        }
+
+        let category = match feed_url {
+            Some(feed_url) => {
+                let category = open_outlines.iter().rev().flatten().next().cloned();
+                feed_outlines.push(FeedOutline { feed_url, category });
+                None
+            }
+            None => text.or(title),
+        };
+
+        if !is_empty {
+            open_outlines.push(category);
+        }
+    }
+
+    Ok(feed_outlines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve_feed;
+
+    fn opml_fixture(base_url: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <opml version="2.0">
+              <head><title>Subscriptions</title></head>
+              <body>
+                <outline text="Dev" title="Dev">
+                  <outline type="rss" text="A" xmlUrl="{base_url}/a" htmlUrl="https://example.com/a"/>
+                  <outline text="Nested">
+                    <outline type="rss" text="B" xmlUrl="{base_url}/b?x=1&amp;y=2"/>
+                  </outline>
+                </outline>
+                <outline type="rss" text="Dead" xmlUrl="{base_url}/dead"/>
+                <outline type="rss" text="A again" xmlUrl="{base_url}/a"/>
+              </body>
+            </opml>"#
+        )
+    }
+
+    #[test]
+    fn it_parses_nested_outlines() {
+        let feed_outlines = parse_feed_outlines(&opml_fixture("http://localhost")).unwrap();
+
+        let feed_outline = |feed_url: &str, category: Option<&str>| FeedOutline {
+            feed_url: feed_url.to_owned(),
+            category: category.map(|category| category.to_owned()),
+        };
+
+        assert_eq!(
+            feed_outlines,
+            vec![
+                feed_outline("http://localhost/a", Some("Dev")),
+                feed_outline("http://localhost/b?x=1&y=2", Some("Nested")),
+                feed_outline("http://localhost/dead", None),
+                feed_outline("http://localhost/a", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_imports_feeds_and_skips_existing_and_failing_ones() {
+        let base_url = serve_feed(&["/a", "/b?x=1&y=2"]);
+
+        let http_client = crate::rss::FetchOptions::default().http_client();
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::rss::initialize_db(&mut conn).unwrap();
+
+        let results = import_opml(&http_client, &mut conn, &opml_fixture(&base_url), 2).unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[0].1, ImportResult::Subscribed(_)));
+        assert!(matches!(results[1].1, ImportResult::Subscribed(_)));
+        assert!(matches!(results[2].1, ImportResult::Failed(_)));
+        match (&results[0].1, &results[3].1) {
+            (ImportResult::Subscribed(a), ImportResult::AlreadySubscribed(a_again)) => {
+                assert_eq!(a, a_again)
+            }
+            other => panic!("expected the duplicate to be skipped, got {other:?}"),
+        }
+
+        assert_eq!(crate::rss::get_feeds(&conn).unwrap().len(), 2);
+        assert_eq!(
+            crate::rss::list_categories(&conn).unwrap(),
+            vec!["Dev", "Nested"]
+        );
+    }
+
+    #[test]
+    fn it_exports_opml_that_round_trips_through_import() {
+        let base_url = serve_feed(&["/a", "/b?x=1&y=2", "/untitled"]);
+
+        let http_client = crate::rss::FetchOptions::default().http_client();
+
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::rss::initialize_db(&mut conn).unwrap();
+        let feed_id =
+            crate::rss::subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/a"))
+                .unwrap();
+        crate::rss::set_feed_category(&conn, feed_id, Some("Dev")).unwrap();
+        crate::rss::subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/b?x=1&y=2"))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO feeds (title, feed_link, feed_kind) VALUES (NULL, ?1, 'RSS')",
+            [format!("{base_url}/untitled")],
+        )
+        .unwrap();
+
+        let opml = export_opml(&conn).unwrap();
+
+        assert!(opml.contains(r#"htmlUrl="https://example.com""#));
+        assert!(opml.contains(&format!(r#"xmlUrl="{base_url}/b?x=1&amp;y=2""#)));
+        assert!(opml.contains(&format!(r#"text="{base_url}/untitled""#)));
+
+        let mut fresh_conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::rss::initialize_db(&mut fresh_conn).unwrap();
+        let results = import_opml(&http_client, &mut fresh_conn, &opml, 2).unwrap();
+        assert!(results
+            .iter()
+            .all(|(_, result)| !matches!(result, ImportResult::Failed(_))));
+
+        let feed_links = |conn: &rusqlite::Connection| {
+            let mut feed_links = crate::rss::get_feeds(conn)
+                .unwrap()
+                .into_iter()
+                .filter_map(|feed| feed.feed_link)
+                .collect::<Vec<_>>();
+            feed_links.sort();
+            feed_links.dedup();
+            feed_links
+        };
+
+        assert_eq!(feed_links(&conn), feed_links(&fresh_conn));
+
+        let dev_feeds = crate::rss::get_feeds_by_category(&fresh_conn, Some("Dev")).unwrap();
+        assert_eq!(dev_feeds.len(), 1);
+        assert_eq!(dev_feeds[0].feed_link, Some(format!("{base_url}/a")));
+    }
+
+    #[test]
+    fn it_does_not_import_malformed_opml() {
+        assert!(parse_feed_outlines("<opml><body><outline xmlUrl=\"a></body>").is_err());
+    }
+}
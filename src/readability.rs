@@ -0,0 +1,204 @@
+//! Finding the article in a web page, for feeds that only have summaries
+//! of their entries, see `rss::fetch_full_content`.
+
+use std::ops::Range;
+
+/// elements that are never part of the article, removed with everything in them
+const BOILERPLATE: &[&str] = &[
+    "script", "style", "noscript", "template", "iframe", "svg", "form", "nav", "aside", "footer",
+];
+
+/// elements that hold the article, best first.
+/// the whole page is used if it has none of them.
+const CONTAINERS: &[&str] = &["article", "main", "body"];
+
+/// the HTML of the main content of the page `html`, like an article without
+/// the site's navigation, sidebars, footers and scripts around it.
+/// it is the `<article>` with the most text, or else the `<main>` with the most text,
+/// or else the `<body>`, with boilerplate elements removed from it.
+/// `None` if it has no text.
+///
+/// HTML is too loose to parse as XML, so like `discovery::discover_feeds`,
+/// this only looks for the tags it cares about.
+pub(crate) fn extract_main_content(html: &str) -> Option<String> {
+    let html = BOILERPLATE
+        .iter()
+        .fold(html.to_owned(), |html, name| remove_elements(&html, name));
+
+    let content = CONTAINERS
+        .iter()
+        .find_map(|name| {
+            elements(&html, name)
+                .into_iter()
+                .filter_map(|element| {
+                    let inner_html = &html[element.inner];
+                    let text_len = crate::rss::html_to_plain_text(inner_html)?.len();
+                    Some((text_len, inner_html))
+                })
+                .max_by_key(|(text_len, _)| *text_len)
+                .map(|(_, inner_html)| inner_html)
+        })
+        .unwrap_or(&html)
+        .trim();
+
+    crate::rss::html_to_plain_text(content).map(|_| content.to_owned())
+}
+
+/// a `<name ...>` or `</name>` tag
+struct Tag {
+    range: Range<usize>,
+    is_open: bool,
+}
+
+/// an element with its tags (`outer`) and without them (`inner`)
+struct Element {
+    outer: Range<usize>,
+    inner: Range<usize>,
+}
+
+/// every `<name>` and `</name>` tag in `html`, in order
+fn tags(html: &str, name: &str) -> Vec<Tag> {
+    let mut tags = vec![];
+    let mut position = 0;
+
+    while let Some(offset) = html[position..].find('<') {
+        let start = position + offset;
+        position = start + 1;
+
+        let (is_open, rest) = match html[position..].strip_prefix('/') {
+            Some(rest) => (false, rest),
+            None => (true, &html[position..]),
+        };
+
+        let is_name = rest
+            .get(..name.len())
+            .is_some_and(|tag_name| tag_name.eq_ignore_ascii_case(name))
+            && rest[name.len()..]
+                .starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>');
+
+        if !is_name {
+            continue;
+        }
+
+        let end = html[start..]
+            .find('>')
+            .map_or(html.len(), |end| start + end + 1);
+
+        tags.push(Tag {
+            range: start..end,
+            is_open,
+        });
+        position = end;
+    }
+
+    tags
+}
+
+/// every `<name>` element in `html` that is not inside another one, in order.
+/// an element that is never closed runs to the end of `html`.
+fn elements(html: &str, name: &str) -> Vec<Element> {
+    let mut elements = vec![];
+    let mut open_tags: Vec<Tag> = vec![];
+
+    for tag in tags(html, name) {
+        if tag.is_open {
+            open_tags.push(tag);
+            continue;
+        }
+
+        // a stray closing tag closes nothing
+        let Some(open_tag) = open_tags.pop() else {
+            continue;
+        };
+
+        if open_tags.is_empty() {
+            elements.push(Element {
+                outer: open_tag.range.start..tag.range.end,
+                inner: open_tag.range.end..tag.range.start,
+            });
+        }
+    }
+
+    if let Some(open_tag) = open_tags.first() {
+        elements.push(Element {
+            outer: open_tag.range.start..html.len(),
+            inner: open_tag.range.end..html.len(),
+        });
+    }
+
+    elements
+}
+
+/// `html` without its `<name>` elements or anything in them
+fn remove_elements(html: &str, name: &str) -> String {
+    let mut kept = String::with_capacity(html.len());
+    let mut position = 0;
+
+    for element in elements(html, name) {
+        kept.push_str(&html[position..element.outer.start]);
+        position = element.outer.end;
+    }
+
+    kept.push_str(&html[position..]);
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_extracts_the_article_without_the_page_around_it() {
+        let html = r#"<html>
+          <head><title>A post</title><style>p { color: red; }</style></head>
+          <body>
+            <nav><a href="/">Home</a></nav>
+            <ARTICLE class="post">
+              <h1>A post</h1>
+              <p>The first paragraph.</p>
+              <aside>Related posts</aside>
+              <p>The second paragraph.<script>track("<article>")</script></p>
+            </ARTICLE>
+            <footer>Copyright</footer>
+          </body>
+        </html>"#;
+
+        let content = extract_main_content(html).unwrap();
+        let text = crate::rss::html_to_plain_text(&content).unwrap();
+
+        assert_eq!(
+            text,
+            "A post\n\nThe first paragraph.\n\nThe second paragraph."
+        );
+    }
+
+    #[test]
+    fn it_prefers_the_article_with_the_most_text() {
+        let html = "<main>
+          <article><p>A teaser.</p></article>
+          <article><p>The whole story, which is longer.</p></article>
+        </main>";
+
+        assert_eq!(
+            extract_main_content(html).as_deref(),
+            Some("<p>The whole story, which is longer.</p>")
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_the_body_and_then_the_whole_page() {
+        assert_eq!(
+            extract_main_content("<body><div><p>Just a div.</p></div><nav>Menu</nav></body>")
+                .as_deref(),
+            Some("<div><p>Just a div.</p></div>")
+        );
+        assert_eq!(
+            extract_main_content("<p>Not even a body.</p>").as_deref(),
+            Some("<p>Not even a body.</p>")
+        );
+        assert_eq!(
+            extract_main_content("<body><nav>Menu</nav><script>app()</script></body>"),
+            None
+        );
+    }
+}
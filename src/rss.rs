@@ -1,20 +1,28 @@
+use crate::error::Error;
+use crate::json_feed;
 use crate::modes::ReadMode;
 use anyhow::{Context, Result};
 use atom_syndication as atom;
 use chrono::prelude::{DateTime, Utc};
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
 use rss::Channel;
-use rusqlite::params;
 use rusqlite::types::ToSqlOutput;
-use std::collections::HashSet;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
+use std::io::Read;
 use std::str::FromStr;
+use std::time::Duration;
 
-type EntryId = i64;
+pub type EntryId = i64;
 pub type FeedId = i64;
 
 #[derive(Clone, Copy, Debug)]
 pub enum FeedKind {
     Atom,
+    Json,
     Rss,
 }
 
@@ -39,6 +47,7 @@ impl Display for FeedKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let out = match self {
             FeedKind::Atom => "Atom",
+            FeedKind::Json => "JSON",
             FeedKind::Rss => "RSS",
         };
 
@@ -52,51 +61,818 @@ impl FromStr for FeedKind {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "Atom" => Ok(FeedKind::Atom),
+            "JSON" => Ok(FeedKind::Json),
             "RSS" => Ok(FeedKind::Rss),
             _ => Err(anyhow::anyhow!(format!("{s} is not a valid FeedKind"))),
         }
     }
 }
 
+/// how a refresh tells whether the feed already has an entry, see `set_feed_dedupe_strategy`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupeStrategy {
+    /// the entry's guid, or its link if it does not have one
+    #[default]
+    GuidThenLink,
+    /// only the guid, for feeds whose entries share a link, like the site's home page
+    Guid,
+    /// only the link, for feeds whose guids change
+    Link,
+    /// the title and publication date, for feeds whose guids and links both change.
+    /// titles are compared without case or punctuation (see `normalize_title`),
+    /// and entries with the same title are the same entry if they were published
+    /// within `TITLE_DATE_WINDOW_HOURS` of each other, or either has no publication date.
+    TitleDate,
+}
+
+/// see `DedupeStrategy::TitleDate`. short enough that posts with the same title
+/// every day, like a daily digest, are still told apart.
+const TITLE_DATE_WINDOW_HOURS: i64 = 12;
+
+impl DedupeStrategy {
+    /// what tells the entry apart from the feed's other entries,
+    /// `None` if it does not have what the strategy needs.
+    /// `link` is normalized, see `normalize_entry_link`.
+    /// refreshing matches `GuidThenLink` entries on either the guid or the link,
+    /// so this is only the whole story for the other strategies,
+    /// and `TitleDate` entries with the same key can still be told apart by date.
+    fn key(self, guid: Option<&str>, link: Option<&str>, title: Option<&str>) -> Option<String> {
+        match self {
+            DedupeStrategy::GuidThenLink => guid
+                .map(|guid| format!("guid {guid}"))
+                .or_else(|| link.map(|link| format!("link {link}"))),
+            DedupeStrategy::Guid => guid.map(|guid| guid.to_owned()),
+            DedupeStrategy::Link => link.map(|link| link.to_owned()),
+            // dates are compared in `is_same_entry`, as they only have to be close
+            DedupeStrategy::TitleDate => title.and_then(normalize_title),
+        }
+    }
+
+    /// whether two entries with the same `key` are the same entry,
+    /// given when they were published
+    fn is_same_entry(
+        self,
+        published_at: Option<DateTime<Utc>>,
+        other_published_at: Option<DateTime<Utc>>,
+    ) -> bool {
+        match (self, published_at, other_published_at) {
+            (DedupeStrategy::TitleDate, Some(published_at), Some(other_published_at)) => {
+                (published_at - other_published_at).num_hours().abs() < TITLE_DATE_WINDOW_HOURS
+            }
+            _ => true,
+        }
+    }
+}
+
+impl rusqlite::types::FromSql for DedupeStrategy {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let s = value.as_str()?;
+        match DedupeStrategy::from_str(s) {
+            Ok(dedupe_strategy) => Ok(dedupe_strategy),
+            Err(e) => Err(rusqlite::types::FromSqlError::Other(e.into())),
+        }
+    }
+}
+
+impl rusqlite::types::ToSql for DedupeStrategy {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let s = self.to_string();
+        Ok(ToSqlOutput::from(s))
+    }
+}
+
+impl Display for DedupeStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let out = match self {
+            DedupeStrategy::GuidThenLink => "GuidThenLink",
+            DedupeStrategy::Guid => "Guid",
+            DedupeStrategy::Link => "Link",
+            DedupeStrategy::TitleDate => "TitleDate",
+        };
+
+        write!(f, "{out}")
+    }
+}
+
+impl FromStr for DedupeStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GuidThenLink" => Ok(DedupeStrategy::GuidThenLink),
+            "Guid" => Ok(DedupeStrategy::Guid),
+            "Link" => Ok(DedupeStrategy::Link),
+            "TitleDate" => Ok(DedupeStrategy::TitleDate),
+            _ => Err(anyhow::anyhow!(format!(
+                "{s} is not a valid DedupeStrategy"
+            ))),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Feed {
     pub id: FeedId,
     pub title: Option<String>,
     pub feed_link: Option<String>,
     pub link: Option<String>,
+    /// where the feed says it lives, from `<atom:link rel="self">`
+    /// or a JSON Feed's `feed_url`. may differ from `feed_link`,
+    /// the url the feed was subscribed to.
+    pub self_link: Option<String>,
+    /// the feed's image, like a logo for showing next to its title,
+    /// from RSS's `<image>`, Atom's `<logo>` or `<icon>`, or a JSON Feed's `icon` or `favicon`
+    pub image_url: Option<String>,
+    /// the language the feed is written in, like `en-us`, from RSS's `<language>`,
+    /// Atom's `xml:lang` or a JSON Feed's `language`, see `get_feeds_by_language`
+    pub language: Option<String>,
+    /// how often the feed asks to be fetched at most,
+    /// from its `<ttl>` or `<sy:updatePeriod>`, see `feeds_due_for_refresh`
+    pub min_refresh_interval: Option<Duration>,
+    /// how often the user wants the feed fetched, see `set_feed_refresh_interval`
+    pub refresh_interval: Option<Duration>,
+    /// how many entries the feed keeps, see `set_feed_max_entries`
+    pub max_entries: Option<usize>,
+    /// whether refreshing stores the response body, see `set_feed_keeps_raw_body`
+    pub keeps_raw_body: bool,
+    /// how long an entry's description or content may be, see `set_feed_max_content_bytes`
+    pub max_content_bytes: Option<usize>,
+    /// how refreshing tells entries apart, see `set_feed_dedupe_strategy`
+    pub dedupe_strategy: DedupeStrategy,
+    /// the `Accept` header to fetch the feed with, see `set_feed_accept`
+    pub accept: Option<String>,
     pub feed_kind: FeedKind,
+    /// a user-chosen group, like "News" or "Dev"
+    pub category: Option<String>,
     pub refreshed_at: Option<chrono::DateTime<Utc>>,
+    /// why the last refresh failed, cleared by the next successful refresh
+    pub last_error: Option<String>,
+    pub last_error_at: Option<chrono::DateTime<Utc>>,
+    /// the HTTP status of the last refresh, like 200, or 304 if the feed had not changed.
+    /// `None` if the fetch failed other than with an error status,
+    /// or the feed is not fetched over HTTP.
+    pub last_status: Option<u16>,
+    /// how long the last refresh took to fetch the feed, whether or not it succeeded
+    pub last_fetch_duration: Option<Duration>,
     pub inserted_at: chrono::DateTime<Utc>,
     pub updated_at: chrono::DateTime<Utc>,
 }
 
+impl Feed {
+    /// what to call the feed in lists: its title,
+    /// or its url for feeds that do not have one
+    pub fn display_title(&self) -> &str {
+        self.title
+            .as_deref()
+            .filter(|title| !title.trim().is_empty())
+            .or(self.feed_link.as_deref())
+            .unwrap_or("(untitled)")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Entry {
     pub id: EntryId,
     pub feed_id: FeedId,
     pub title: Option<String>,
+    /// the author's name
     pub author: Option<String>,
-    pub pub_date: Option<chrono::DateTime<Utc>>,
+    /// the publication date exactly as the feed has it
+    pub pub_date: Option<String>,
+    /// `pub_date`, if it could be parsed
+    pub published_at: Option<chrono::DateTime<Utc>>,
     pub description: Option<String>,
     pub content: Option<String>,
     pub link: Option<String>,
+    pub guid: Option<String>,
     pub read_at: Option<chrono::DateTime<Utc>>,
+    pub starred: bool,
+    /// the author's email address, see `Entry::author_details`
+    pub author_email: Option<String>,
+    /// the author's web page, which only Atom has
+    pub author_uri: Option<String>,
+    /// whether the description or content was cut short, see `set_feed_max_content_bytes`
+    pub truncated: bool,
+    /// the language the entry is written in, from its `xml:lang`
+    /// or a JSON Feed item's `language`, or else the feed's, see `Feed::language`
+    pub language: Option<String>,
+    /// the article from the entry's web page, see `fetch_full_content`
+    pub full_content: Option<String>,
+    /// why the last `fetch_full_content` failed
+    pub full_content_error: Option<String>,
+    /// an attached file, like a podcast episode
+    pub enclosure: Option<Enclosure>,
+    /// the entry's categories, see `get_entries_by_tag`
+    pub tags: Vec<String>,
+    /// an image to show with the entry, from Media RSS
+    /// (`<media:thumbnail>` or an image `<media:content>`) or a JSON Feed's `image`
+    pub thumbnail_url: Option<String>,
     pub inserted_at: chrono::DateTime<Utc>,
     pub updated_at: chrono::DateTime<Utc>,
 }
 
+impl Entry {
+    /// the description, or the content if there is no description, as plain text,
+    /// for showing a preview of the entry. see `html_to_plain_text`.
+    pub fn plain_text_description(&self) -> Option<String> {
+        self.description
+            .as_deref()
+            .and_then(html_to_plain_text)
+            .or_else(|| self.content.as_deref().and_then(html_to_plain_text))
+    }
+
+    /// whether the feed has the whole entry, in its content,
+    /// rather than just a summary in its description, like feeds that want
+    /// people to go to their site to read the rest.
+    /// content that was cut short (see `Entry::truncated`) is not the whole entry.
+    pub fn has_full_content(&self) -> bool {
+        !self.truncated
+            && self
+                .content
+                .as_deref()
+                .is_some_and(|content| !content.trim().is_empty())
+    }
+
+    /// the article fetched with `fetch_full_content`, or else the content,
+    /// or else the description, as the feed has it,
+    /// for showing as much of the entry as there is
+    pub fn best_body(&self) -> Option<&str> {
+        [
+            self.full_content.as_deref(),
+            self.content.as_deref(),
+            self.description.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|body| !body.trim().is_empty())
+    }
+
+    /// who wrote the entry, if the feed says
+    pub fn author_details(&self) -> Option<Author> {
+        let author = Author {
+            name: self.author.clone(),
+            email: self.author_email.clone(),
+            uri: self.author_uri.clone(),
+        };
+
+        (author != Author::default()).then_some(author)
+    }
+
+    /// the entry as a Markdown document, for saving it:
+    /// the title as a heading, then the author and publication date,
+    /// the content (or the description, if there is no content) converted to Markdown,
+    /// and a link to the entry.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "# {}\n",
+            self.title
+                .as_deref()
+                .map(|title| title.trim())
+                .filter(|title| !title.is_empty())
+                .unwrap_or("(untitled)")
+        );
+
+        let published = self
+            .published_at
+            .map(|published_at| published_at.format("%Y-%m-%d").to_string())
+            .or_else(|| self.pub_date.clone());
+
+        let author = self.author.as_deref().or(self.author_email.as_deref());
+
+        let byline = match (author, published) {
+            (Some(author), Some(published)) => Some(format!("{author}, {published}")),
+            (Some(author), None) => Some(author.to_owned()),
+            (None, Some(published)) => Some(published),
+            (None, None) => None,
+        };
+
+        if let Some(byline) = byline {
+            markdown.push_str(&format!("\n*{byline}*\n"));
+        }
+
+        let body = self
+            .content
+            .as_deref()
+            .and_then(html_to_markdown)
+            .or_else(|| self.description.as_deref().and_then(html_to_markdown));
+
+        if let Some(body) = body {
+            markdown.push_str(&format!("\n{body}\n"));
+        }
+
+        if let Some(link) = &self.link {
+            markdown.push_str(&format!("\n[Source]({link})\n"));
+        }
+
+        markdown
+    }
+}
+
+/// `html` as Markdown: headings, emphasis, lists, quotes and code stay what they are,
+/// and links become reference links, listed at the end.
+/// like `html_to_plain_text`, lines are not wrapped
+/// and runs of blank lines become a single blank line.
+/// `None` if there is no text.
+pub fn html_to_markdown(html: &str) -> Option<String> {
+    // wide enough that nothing is wrapped
+    const WIDTH: usize = 1_000_000;
+
+    let text = html2text::from_read(html.as_bytes(), WIDTH);
+
+    let mut markdown = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+
+    // unlike plain text, leading whitespace nests lists
+    for line in text.lines().map(|line| line.trim_end()) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        if !markdown.is_empty() {
+            markdown.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+
+        markdown.push_str(line);
+        blank_lines = 0;
+    }
+
+    Some(markdown).filter(|markdown| !markdown.is_empty())
+}
+
+/// strips the tags from `html` and decodes its entities.
+/// block elements like `<p>` and `<br>` become line breaks,
+/// but lines are not wrapped, and runs of blank lines become a single blank line.
+/// `None` if there is no text.
+pub fn html_to_plain_text(html: &str) -> Option<String> {
+    // wide enough that nothing is wrapped
+    const WIDTH: usize = 1_000_000;
+
+    let text = html2text::from_read_with_decorator(
+        html.as_bytes(),
+        WIDTH,
+        html2text::render::text_renderer::TrivialDecorator::new(),
+    );
+
+    let mut plain_text = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+
+    for line in text.lines().map(|line| line.trim()) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        if !plain_text.is_empty() {
+            plain_text.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+
+        plain_text.push_str(line);
+        blank_lines = 0;
+    }
+
+    Some(plain_text).filter(|plain_text| !plain_text.is_empty())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Enclosure {
+    pub url: String,
+    /// the MIME type, like `audio/mpeg`
+    pub mime_type: Option<String>,
+    /// the size in bytes
+    pub length: Option<u64>,
+}
+
+impl Enclosure {
+    fn new(url: &str, mime_type: Option<&str>, length: Option<&str>) -> Option<Self> {
+        let url = url.trim();
+
+        if url.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            url: url.to_owned(),
+            mime_type: mime_type
+                .map(|mime_type| mime_type.trim())
+                .filter(|mime_type| !mime_type.is_empty())
+                .map(|mime_type| mime_type.to_owned()),
+            // feeds often have `length="0"` when they don't know the size.
+            // a length too big for SQLite would fail to store the whole feed.
+            length: length
+                .and_then(|length| length.trim().parse::<i64>().ok())
+                .filter(|length| *length > 0)
+                .map(|length| length as u64),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Author {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub uri: Option<String>,
+}
+
+impl Author {
+    /// RSS has one string for the author, meant to be an email address,
+    /// conventionally followed by the name in parentheses, like `jo@example.com (Jo Doe)`.
+    /// feeds also write `Jo Doe <jo@example.com>`, just an email address, or just a name.
+    pub fn from_rss(author: &str) -> Self {
+        let author = author.trim();
+
+        let (name, email) = if let Some((email, name)) = author
+            .strip_suffix(')')
+            .and_then(|author| author.split_once('('))
+        {
+            (name, email)
+        } else if let Some((name, email)) = author
+            .strip_suffix('>')
+            .and_then(|author| author.split_once('<'))
+        {
+            (name, email)
+        } else if author.contains('@') && !author.contains(char::is_whitespace) {
+            ("", author)
+        } else {
+            (author, "")
+        };
+
+        Self {
+            name: non_empty(name),
+            email: non_empty(email),
+            uri: None,
+        }
+    }
+}
+
+impl From<&atom::Person> for Author {
+    fn from(person: &atom::Person) -> Self {
+        Self {
+            name: non_empty(person.name()),
+            email: person.email().and_then(non_empty),
+            uri: person.uri().and_then(non_empty),
+        }
+    }
+}
+
+/// `s` trimmed, if there is anything left
+fn non_empty(s: &str) -> Option<String> {
+    Some(s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_owned())
+}
+
+/// a title or author as it should read in a list, with runs of whitespace,
+/// like line breaks and doubled spaces, made one space.
+/// titles that are HTML are already text, see `title_text`.
+/// `None` if there is nothing left.
+fn clean_text(text: &str) -> Option<String> {
+    non_empty(&text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// a title as the feed means it to read. the feed parsers decode XML's entities,
+/// so a plain title is already text: `Vec&lt;String&gt;` is `Vec<String>`,
+/// and `AT&amp;amp;T` is `AT&amp;T`, neither of which is decoded again.
+/// a title that is HTML, one in CDATA (see `rss_titles_in_cdata`),
+/// an Atom `type="html"` title, or one in a CDATA section the feed escaped,
+/// like `&lt;![CDATA[Hello &amp;amp; goodbye]]&gt;`, is made text, see `html_title_text`.
+fn title_text(title: &str, is_html: bool) -> String {
+    let escaped_cdata = title
+        .trim()
+        .strip_prefix("<![CDATA[")
+        .and_then(|title| title.strip_suffix("]]>"));
+
+    match escaped_cdata {
+        Some(html) => html_title_text(html),
+        None if is_html => html_title_text(title),
+        None => title.to_owned(),
+    }
+}
+
+/// an HTML title as text: its tags removed (see `strip_tags`), and then
+/// its entities decoded, once, so an escaped tag like `&lt;div&gt;` stays `<div>`.
+/// a title with something that only looks like an entity, like `Q&A;`, keeps it.
+fn html_title_text(html: &str) -> String {
+    let text = strip_tags(html);
+
+    match quick_xml::escape::unescape_with(&text, html_entity) {
+        Ok(unescaped) => unescaped.into_owned(),
+        Err(_) => text,
+    }
+}
+
+/// an Atom title as the feed means it to read, see `title_text`
+fn atom_title_text(title: &atom::Text) -> String {
+    let is_html = matches!(title.r#type, atom::TextType::Html | atom::TextType::Xhtml);
+    title_text(&title.value, is_html)
+}
+
+/// the HTML elements that show up in titles, see `strip_tags`
+const TITLE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "big", "br", "cite", "code", "del", "div", "em", "font", "i", "img", "ins",
+    "kbd", "mark", "p", "q", "s", "small", "span", "strike", "strong", "sub", "sup", "time", "u",
+    "wbr",
+];
+
+/// `html` without the tags of the elements in `TITLE_ELEMENTS`, like `<b>` or `</a>`.
+/// anything else that looks like a tag, like `<String>` in `Vec<String>`,
+/// is more likely to be text than markup, so it is kept,
+/// as is a `<` that does not start a tag, like in `1 < 2`.
+fn strip_tags(html: &str) -> String {
+    let mut stripped = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        let tag = &rest[start..];
+
+        let name = tag[1..].strip_prefix('/').unwrap_or(&tag[1..]);
+        let name_len = name
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(name.len());
+        let is_tag = TITLE_ELEMENTS
+            .iter()
+            .any(|element| element.eq_ignore_ascii_case(&name[..name_len]))
+            && name[name_len..]
+                .starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>');
+
+        match tag.find('>') {
+            Some(end) if is_tag => rest = &tag[end + 1..],
+            _ => {
+                stripped.push('<');
+                rest = &tag[1..];
+            }
+        }
+    }
+
+    stripped.push_str(rest);
+    stripped
+}
+
+/// a title as `DedupeStrategy::TitleDate` compares it: cleaned (see `clean_text`),
+/// lowercase, and with punctuation made spaces, so that `Hello, World!` and
+/// `hello world` are the same title. titles that are all punctuation are kept as they are.
+fn normalize_title(title: &str) -> Option<String> {
+    let title = clean_text(title)?.to_lowercase();
+    let words = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    non_empty(&words).or(Some(title))
+}
+
+/// a language tag as feeds are compared by it, trimmed and lowercase,
+/// with `_` made `-`, as in `en_US`, which some feeds use for `en-US`
+fn normalize_language(language: &str) -> Option<String> {
+    non_empty(&language.to_lowercase().replace('_', "-"))
+}
+
+/// the `xml:lang` of every `<name>` element in `xml`, in order,
+/// for the entries the feed parsers do not give the language of.
+/// if there are not `entries_len` of them, there is no telling which entry
+/// each one belongs to, so every entry gets `None`.
+fn entry_languages(xml: &str, name: &[u8], entries_len: usize) -> Vec<Option<String>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut languages = vec![];
+
+    loop {
+        match reader.read_event() {
+            Ok(
+                quick_xml::events::Event::Start(element) | quick_xml::events::Event::Empty(element),
+            ) if element.local_name().as_ref() == name => {
+                let language = element
+                    .try_get_attribute("xml:lang")
+                    .ok()
+                    .flatten()
+                    .and_then(|language| language.decode_and_unescape_value(&reader).ok())
+                    .and_then(|language| normalize_language(&language));
+                languages.push(language);
+            }
+            Ok(quick_xml::events::Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+    }
+
+    if languages.len() == entries_len {
+        languages
+    } else {
+        vec![None; entries_len]
+    }
+}
+
+/// whether the channel's `<title>`, and the `<title>` of each `<item>`, in order,
+/// has CDATA in it, which feeds use for titles with HTML in them, see `title_text`.
+/// if there are not `items_len` items, there is no telling which item
+/// each title belongs to, so every item gets `false`, like `entry_languages`.
+fn rss_titles_in_cdata(xml: &str, items_len: usize) -> (bool, Vec<bool>) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    // the names of the elements the reader is in
+    let mut open_elements: Vec<Vec<u8>> = vec![];
+    let mut channel_title_in_cdata = false;
+    let mut item_titles_in_cdata = vec![];
+
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Start(element)) => {
+                if element.local_name().as_ref() == b"item" {
+                    item_titles_in_cdata.push(false);
+                }
+                open_elements.push(element.name().as_ref().to_owned());
+            }
+            Ok(quick_xml::events::Event::Empty(element))
+                if element.local_name().as_ref() == b"item" =>
+            {
+                item_titles_in_cdata.push(false);
+            }
+            Ok(quick_xml::events::Event::End(_)) => {
+                open_elements.pop();
+            }
+            Ok(quick_xml::events::Event::CData(_)) => {
+                if let [.., parent, name] = open_elements.as_slice() {
+                    match (parent.as_slice(), name.as_slice()) {
+                        (b"channel", b"title") => channel_title_in_cdata = true,
+                        (b"item", b"title") => {
+                            if let Some(item_title_in_cdata) = item_titles_in_cdata.last_mut() {
+                                *item_title_in_cdata = true;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Ok(quick_xml::events::Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+    }
+
+    if item_titles_in_cdata.len() != items_len {
+        item_titles_in_cdata = vec![false; items_len];
+    }
+
+    (channel_title_in_cdata, item_titles_in_cdata)
+}
+
+/// the HTML entities that show up in titles, beyond the ones XML has
+fn html_entity(name: &str) -> Option<&'static str> {
+    let s = match name {
+        "nbsp" => "\u{a0}",
+        "ndash" => "–",
+        "mdash" => "—",
+        "hellip" => "…",
+        "lsquo" => "‘",
+        "rsquo" => "’",
+        "ldquo" => "“",
+        "rdquo" => "”",
+        "laquo" => "«",
+        "raquo" => "»",
+        "copy" => "©",
+        "reg" => "®",
+        "trade" => "™",
+        _ => return None,
+    };
+    Some(s)
+}
+
+/// trimmed, without blanks or repeats, in the order the feed has them
+fn tags<'a>(tags: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut unique_tags: Vec<String> = vec![];
+
+    for tag in tags.map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !unique_tags.iter().any(|unique_tag| unique_tag == tag) {
+            unique_tags.push(tag.to_owned());
+        }
+    }
+
+    unique_tags
+}
+
+/// Media RSS, see <https://www.rssboard.org/media-rss>
+const MEDIA_RSS_NAMESPACE: &str = "http://search.yahoo.com/mrss/";
+
+/// the prefixes a feed uses for Media RSS elements.
+/// almost every feed uses `media`, so that is the guess for feeds that do not say.
+fn media_prefixes(namespaces: &BTreeMap<String, String>) -> Vec<&str> {
+    let prefixes = namespaces
+        .iter()
+        .filter(|(_, namespace)| {
+            namespace.trim_end_matches('/') == MEDIA_RSS_NAMESPACE.trim_end_matches('/')
+        })
+        .map(|(prefix, _)| prefix.as_str())
+        .collect::<Vec<_>>();
+
+    if prefixes.is_empty() {
+        vec!["media"]
+    } else {
+        prefixes
+    }
+}
+
+/// the extension elements of RSS and Atom, which are different types with the same shape
+trait ExtensionElement: Sized {
+    fn attr(&self, name: &str) -> Option<&str>;
+    fn children(&self) -> &BTreeMap<String, Vec<Self>>;
+}
+
+impl ExtensionElement for rss::extension::Extension {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(|value| value.as_str())
+    }
+
+    fn children(&self) -> &BTreeMap<String, Vec<Self>> {
+        &self.children
+    }
+}
+
+impl ExtensionElement for atom::extension::Extension {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(|value| value.as_str())
+    }
+
+    fn children(&self) -> &BTreeMap<String, Vec<Self>> {
+        &self.children
+    }
+}
+
+fn media_thumbnail_url<E: ExtensionElement>(
+    extensions: &BTreeMap<String, BTreeMap<String, Vec<E>>>,
+    media_prefixes: &[&str],
+) -> Option<String> {
+    media_prefixes
+        .iter()
+        .filter_map(|prefix| extensions.get(*prefix))
+        .find_map(media_elements_thumbnail_url)
+}
+
+/// the first of: a `<media:thumbnail>`, a `<media:thumbnail>` of a `<media:content>`,
+/// the same inside a `<media:group>`, or a `<media:content>` that is an image
+fn media_elements_thumbnail_url<E: ExtensionElement>(
+    elements: &BTreeMap<String, Vec<E>>,
+) -> Option<String> {
+    let url = |element: &E| {
+        element
+            .attr("url")
+            .map(|url| url.trim())
+            .filter(|url| !url.is_empty())
+            .map(|url| url.to_owned())
+    };
+    let named = |name: &str| elements.get(name).into_iter().flatten();
+
+    let is_image = |element: &&E| {
+        element.attr("medium") == Some("image")
+            || element
+                .attr("type")
+                .is_some_and(|mime_type| mime_type.trim().starts_with("image/"))
+    };
+
+    named("thumbnail")
+        .find_map(url)
+        .or_else(|| {
+            named("content")
+                .chain(named("group"))
+                .find_map(|element| media_elements_thumbnail_url(element.children()))
+        })
+        .or_else(|| named("content").filter(is_image).find_map(url))
+}
+
 impl From<&atom::Entry> for Entry {
     fn from(entry: &atom::Entry) -> Self {
+        // `published` is optional in Atom, but `updated` is required
+        let published_at = entry.published().unwrap_or_else(|| entry.updated());
+        let author = entry
+            .authors()
+            .first()
+            .map(Author::from)
+            .unwrap_or_default();
+
         Self {
             id: -1,
             feed_id: -1,
-            title: Some(entry.title().to_string()),
-            author: entry.authors().get(0).map(|author| author.name.to_owned()),
-            pub_date: entry.published().map(|date| date.with_timezone(&Utc)),
-            description: None,
+            title: Some(atom_title_text(entry.title())),
+            author: author.name,
+            pub_date: Some(published_at.to_rfc3339()),
+            published_at: Some(published_at.with_timezone(&Utc)),
+            description: entry.summary().map(|summary| summary.to_string()),
             content: entry.content().and_then(|content| content.value.to_owned()),
-            link: entry.links().get(0).map(|link| link.href().to_string()),
+            link: entry.links().first().map(|link| link.href().to_string()),
+            guid: Some(entry.id())
+                .filter(|id| !id.is_empty())
+                .map(|id| id.to_owned()),
             read_at: None,
+            starred: false,
+            author_email: author.email,
+            author_uri: author.uri,
+            truncated: false,
+            language: None,
+            full_content: None,
+            full_content_error: None,
+            enclosure: entry
+                .links()
+                .iter()
+                .find(|link| link.rel() == "enclosure")
+                .and_then(|link| Enclosure::new(link.href(), link.mime_type(), link.length())),
+            tags: tags(entry.categories().iter().map(|category| category.term())),
+            thumbnail_url: None,
             inserted_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -105,18 +881,86 @@ impl From<&atom::Entry> for Entry {
 
 impl From<&rss::Item> for Entry {
     fn from(entry: &rss::Item) -> Self {
+        let author = entry.author().map(Author::from_rss).unwrap_or_default();
+
         Self {
             id: -1,
             feed_id: -1,
             title: entry.title().map(|title| title.to_owned()),
-            author: entry.author().map(|author| author.to_owned()),
-            pub_date: entry.pub_date().and_then(parse_datetime),
+            author: author.name,
+            pub_date: entry.pub_date().map(|pub_date| pub_date.to_owned()),
+            published_at: entry.pub_date().and_then(parse_datetime),
             description: entry
                 .description()
                 .map(|description| description.to_owned()),
             content: entry.content().map(|content| content.to_owned()),
             link: entry.link().map(|link| link.to_owned()),
+            guid: entry.guid().map(|guid| guid.value().to_owned()),
+            read_at: None,
+            starred: false,
+            author_email: author.email,
+            author_uri: None,
+            truncated: false,
+            language: None,
+            full_content: None,
+            full_content_error: None,
+            enclosure: entry.enclosure().and_then(|enclosure| {
+                Enclosure::new(
+                    enclosure.url(),
+                    Some(enclosure.mime_type()),
+                    Some(enclosure.length()),
+                )
+            }),
+            tags: tags(entry.categories().iter().map(|category| category.name())),
+            thumbnail_url: None,
+            inserted_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl From<&json_feed::Item> for Entry {
+    fn from(item: &json_feed::Item) -> Self {
+        Self {
+            id: -1,
+            feed_id: -1,
+            title: item.title.to_owned(),
+            author: item.author_name().map(|name| name.to_owned()),
+            pub_date: item.date_published.to_owned(),
+            published_at: item.date_published.as_deref().and_then(parse_datetime),
+            description: item.summary.to_owned(),
+            content: item
+                .content_html
+                .as_ref()
+                .or(item.content_text.as_ref())
+                .cloned(),
+            link: item.url.as_ref().or(item.external_url.as_ref()).cloned(),
+            guid: item.id(),
             read_at: None,
+            starred: false,
+            author_email: None,
+            author_uri: None,
+            truncated: false,
+            language: item.language.as_deref().and_then(normalize_language),
+            full_content: None,
+            full_content_error: None,
+            enclosure: item.attachments.first().and_then(|attachment| {
+                Enclosure::new(
+                    &attachment.url,
+                    attachment.mime_type.as_deref(),
+                    attachment
+                        .size_in_bytes
+                        .map(|size_in_bytes| size_in_bytes.to_string())
+                        .as_deref(),
+                )
+            }),
+            tags: tags(item.tags.iter().map(|tag| tag.as_str())),
+            thumbnail_url: item
+                .image
+                .as_deref()
+                .map(|image| image.trim())
+                .filter(|image| !image.is_empty())
+                .map(|image| image.to_owned()),
             inserted_at: Utc::now(),
             updated_at: Utc::now(),
         }
@@ -129,7 +973,8 @@ pub struct EntryMeta {
     pub feed_id: FeedId,
     pub title: Option<String>,
     pub author: Option<String>,
-    pub pub_date: Option<chrono::DateTime<Utc>>,
+    pub pub_date: Option<String>,
+    pub published_at: Option<chrono::DateTime<Utc>>,
     pub link: Option<String>,
     pub read_at: Option<chrono::DateTime<Utc>>,
     pub inserted_at: chrono::DateTime<Utc>,
@@ -146,607 +991,10212 @@ impl EntryMeta {
     }
 
     fn mark_as_read(&self, conn: &rusqlite::Connection) -> Result<()> {
-        let mut statement = conn.prepare("UPDATE entries SET read_at = ?2 WHERE id = ?1")?;
-        statement.execute(params![self.id, Utc::now()])?;
-        Ok(())
+        mark_entry_read(conn, self.id)
     }
 
     fn mark_as_unread(&self, conn: &rusqlite::Connection) -> Result<()> {
-        let mut statement = conn.prepare("UPDATE entries SET read_at = NULL WHERE id = ?1")?;
-        statement.execute([self.id])?;
-        Ok(())
+        mark_entry_unread(conn, self.id)
     }
 }
 
-pub struct EntryContent {
-    pub content: Option<String>,
-    pub description: Option<String>,
+pub fn mark_entry_read(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<()> {
+    let now = Utc::now();
+    let mut statement =
+        conn.prepare("UPDATE entries SET read_at = ?2, updated_at = ?3 WHERE id = ?1")?;
+    statement.execute(params![entry_id, now, now])?;
+    Ok(())
 }
 
-fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
-    diligent_date_parser::parse_date(s).map(|dt| dt.with_timezone(&Utc))
+pub fn mark_entry_unread(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<()> {
+    let mut statement =
+        conn.prepare("UPDATE entries SET read_at = NULL, updated_at = ?2 WHERE id = ?1")?;
+    statement.execute(params![entry_id, Utc::now()])?;
+    Ok(())
 }
 
-struct FeedAndEntries {
-    pub feed: Feed,
-    pub entries: Vec<Entry>,
+/// marks the entry read if it is unread, and unread if it is read,
+/// returning whether it is read now.
+/// a single statement, so two toggles at once can't both see the entry unread.
+pub fn toggle_entry_read(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<bool> {
+    let read = conn
+        .query_row(
+            "UPDATE entries SET
+              read_at = CASE WHEN read_at IS NULL THEN ?2 END,
+              updated_at = ?2
+            WHERE id = ?1
+            RETURNING read_at IS NOT NULL",
+            params![entry_id, Utc::now()],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(Error::EntryNotFound(entry_id))?;
+
+    Ok(read)
 }
 
-impl FeedAndEntries {
-    pub fn set_feed_link(&mut self, url: &str) {
-        self.feed.feed_link = Some(url.to_owned());
-    }
+/// marks an entry as worth keeping.
+/// cleaning up old entries must leave starred entries alone.
+pub fn star_entry(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<()> {
+    let mut statement =
+        conn.prepare("UPDATE entries SET starred = 1, updated_at = ?2 WHERE id = ?1")?;
+    statement.execute(params![entry_id, Utc::now()])?;
+    Ok(())
 }
 
-impl FromStr for FeedAndEntries {
-    type Err = anyhow::Error;
+pub fn unstar_entry(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<()> {
+    let mut statement =
+        conn.prepare("UPDATE entries SET starred = 0, updated_at = ?2 WHERE id = ?1")?;
+    statement.execute(params![entry_id, Utc::now()])?;
+    Ok(())
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match atom::Feed::from_str(s) {
-            Ok(atom_feed) => {
-                let feed = Feed {
-                    id: 0,
-                    title: Some(atom_feed.title.to_string()),
-                    feed_link: None,
-                    link: atom_feed.links.get(0).map(|link| link.href().to_string()),
-                    feed_kind: FeedKind::Atom,
-                    refreshed_at: None,
-                    inserted_at: Utc::now(),
-                    updated_at: Utc::now(),
-                };
+/// marks every unread entry in the feed as read,
+/// returning the number of entries that changed.
+/// entries that are already read keep their original `read_at`.
+pub fn mark_feed_read(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<usize> {
+    let now = Utc::now();
+    let mut statement = conn.prepare(
+        "UPDATE entries SET read_at = ?2, updated_at = ?3
+        WHERE feed_id = ?1
+        AND read_at IS NULL",
+    )?;
+    let updated = statement.execute(params![feed_id, now, now])?;
+    Ok(updated)
+}
 
-                let entries = atom_feed
-                    .entries()
-                    .iter()
-                    .map(|entry| entry.into())
-                    .collect::<Vec<_>>();
-
-                Ok(FeedAndEntries { feed, entries })
-            }
-
-            Err(_e) => match Channel::from_str(s) {
-                Ok(channel) => {
-                    let feed = Feed {
-                        id: 0,
-                        title: Some(channel.title().to_string()),
-                        feed_link: None,
-                        link: Some(channel.link().to_string()),
-                        feed_kind: FeedKind::Rss,
-                        refreshed_at: None,
-                        inserted_at: Utc::now(),
-                        updated_at: Utc::now(),
-                    };
-
-                    let entries = channel
-                        .items()
-                        .iter()
-                        .map(|item| item.into())
-                        .collect::<Vec<_>>();
-
-                    Ok(FeedAndEntries { feed, entries })
-                }
-                Err(e) => Err(e.into()),
-            },
-        }
-    }
+/// marks every read entry in the feed as unread, the opposite of `mark_feed_read`,
+/// for reading a feed again, returning the number of entries that changed.
+pub fn mark_feed_unread(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<usize> {
+    let mut statement = conn.prepare(
+        "UPDATE entries SET read_at = NULL, updated_at = ?2
+        WHERE feed_id = ?1
+        AND read_at IS NOT NULL",
+    )?;
+    let updated = statement.execute(params![feed_id, Utc::now()])?;
+    Ok(updated)
 }
 
-pub fn subscribe_to_feed(
-    http_client: &ureq::Agent,
-    conn: &mut rusqlite::Connection,
-    url: &str,
-) -> Result<FeedId> {
-    let feed_and_entries: FeedAndEntries = fetch_feed(http_client, url)?;
-    let feed_id = in_transaction(conn, |tx| {
-        let feed_id = create_feed(tx, &feed_and_entries.feed)?;
-        add_entries_to_feed(tx, feed_id, &feed_and_entries.entries)?;
-        Ok(feed_id)
-    })?;
-
-    Ok(feed_id)
+/// marks every unread entry in every feed as read,
+/// returning the number of entries that changed.
+/// entries that are already read keep their original `read_at`.
+pub fn mark_all_read(conn: &rusqlite::Connection) -> Result<usize> {
+    let now = Utc::now();
+    let mut statement = conn.prepare(
+        "UPDATE entries SET read_at = ?1, updated_at = ?2
+        WHERE read_at IS NULL",
+    )?;
+    let updated = statement.execute(params![now, now])?;
+    Ok(updated)
 }
 
-fn fetch_feed(http_client: &ureq::Agent, url: &str) -> Result<FeedAndEntries> {
-    let resp = http_client.get(url).call()?.into_string()?;
-    let mut feed = FeedAndEntries::from_str(&resp)?;
-    feed.set_feed_link(url);
+/// marks the unread entries published before `cutoff` as read,
+/// in one feed, or in every feed when `feed_id` is `None`,
+/// returning the number of entries that changed.
+/// entries without a publication date are left alone,
+/// as there is no telling how old they are.
+/// with `keep_starred_unread`, starred entries are left alone too.
+pub fn mark_read_before(
+    conn: &rusqlite::Connection,
+    feed_id: Option<FeedId>,
+    cutoff: DateTime<Utc>,
+    keep_starred_unread: bool,
+) -> Result<usize> {
+    let now = Utc::now();
+    let mut statement = conn.prepare(
+        "UPDATE entries SET read_at = ?3, updated_at = ?3
+        WHERE read_at IS NULL
+        AND published_at < ?1
+        AND (?2 IS NULL OR feed_id = ?2)
+        AND NOT (?4 AND starred)",
+    )?;
+    let updated = statement.execute(params![cutoff, feed_id, now, keep_starred_unread])?;
+    Ok(updated)
+}
 
-    Ok(feed)
+/// deletes read entries that were read before `cutoff`, across all feeds,
+/// returning the number of entries deleted.
+/// starred entries are never deleted.
+/// feeds keep old entries around for a while, so refreshing a feed
+/// remembers not to add the deleted entries again.
+pub fn purge_read_entries_older_than(
+    conn: &mut rusqlite::Connection,
+    cutoff: DateTime<Utc>,
+) -> Result<usize> {
+    purge_read_entries(conn, None, cutoff)
 }
 
-/// fetches the feed and stores the new entries
-/// uses the link as the uniqueness key.
-/// TODO hash the content to see if anything changed, and update that way.
-pub fn refresh_feed(
-    client: &ureq::Agent,
+/// like `purge_read_entries_older_than`, for a single feed
+pub fn purge_feed_read_entries_older_than(
     conn: &mut rusqlite::Connection,
     feed_id: FeedId,
-) -> Result<()> {
-    let feed_url = get_feed_url(conn, feed_id)
-        .with_context(|| format!("Unable to get url for feed id {feed_id} from the database",))?;
-
-    let remote_feed: FeedAndEntries = fetch_feed(client, &feed_url)
-        .with_context(|| format!("Failed to fetch feed {feed_url}"))?;
-
-    let remote_items = remote_feed.entries;
-    let remote_items_links = remote_items
-        .iter()
-        .flat_map(|item| &item.link)
-        .cloned()
-        .collect::<HashSet<String>>();
-
-    let local_entries_links = get_entries_links(conn, &ReadMode::All, feed_id)?
-        .into_iter()
-        .flatten()
-        .collect::<HashSet<_>>();
-
-    let difference = remote_items_links
-        .difference(&local_entries_links)
-        .cloned()
-        .collect::<HashSet<_>>();
-
-    let items_to_add = remote_items
-        .into_iter()
-        .filter(|item| match &item.link {
-            Some(link) => difference.contains(link.as_str()),
-            None => false,
-        })
-        .collect::<Vec<_>>();
-
-    in_transaction(conn, |tx| {
-        add_entries_to_feed(tx, feed_id, &items_to_add)?;
-        update_feed_refreshed_at(tx, feed_id)?;
-        Ok(())
-    })?;
-
-    Ok(())
+    cutoff: DateTime<Utc>,
+) -> Result<usize> {
+    purge_read_entries(conn, Some(feed_id), cutoff)
 }
 
-pub fn initialize_db(conn: &mut rusqlite::Connection) -> Result<()> {
-    in_transaction(conn, |tx| {
-        tx.execute(
-            "CREATE TABLE IF NOT EXISTS feeds (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        title TEXT,
-        feed_link TEXT,
-        link TEXT,
-        feed_kind TEXT,
-        refreshed_at TIMESTAMP,
-        inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )",
-            [],
-        )?;
+fn purge_read_entries(
+    conn: &mut rusqlite::Connection,
+    feed_id: Option<FeedId>,
+    cutoff: DateTime<Utc>,
+) -> Result<usize> {
+    const PURGEABLE: &str = "read_at IS NOT NULL
+        AND read_at < ?1
+        AND starred = 0
+        AND (?2 IS NULL OR feed_id = ?2)";
 
+    in_transaction(conn, |tx| {
         tx.execute(
-            "CREATE TABLE IF NOT EXISTS entries (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        feed_id INTEGER,
-        title TEXT,
-        author TEXT,
-        pub_date TIMESTAMP,
-        description TEXT,
-        content TEXT,
-        link TEXT,
-        read_at TIMESTAMP,
-        inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )",
-            [],
+            &format!(
+                "INSERT INTO purged_entries (feed_id, guid, link, title, published_at)
+                SELECT feed_id, guid, link, title, published_at FROM entries
+                WHERE {PURGEABLE}
+                AND (guid IS NOT NULL OR link IS NOT NULL OR title IS NOT NULL)"
+            ),
+            params![cutoff, feed_id],
         )?;
 
-        tx.execute(
-            "CREATE INDEX IF NOT EXISTS entries_feed_id_and_pub_date_and_inserted_at_index
-        ON entries (feed_id, pub_date, inserted_at)",
-            [],
+        let purged = tx.execute(
+            &format!("DELETE FROM entries WHERE {PURGEABLE}"),
+            params![cutoff, feed_id],
         )?;
 
-        Ok(())
+        Ok(purged)
     })
 }
 
-fn create_feed(tx: &rusqlite::Transaction, feed: &Feed) -> Result<FeedId> {
-    let feed_id = tx.query_row::<FeedId, _, _>(
-        "INSERT INTO feeds (title, link, feed_link, feed_kind)
-        VALUES (?1, ?2, ?3, ?4)
-        RETURNING id",
-        params![feed.title, feed.link, feed.feed_link, feed.feed_kind],
-        |r| r.get(0),
+/// deletes the feed's read, unstarred entries that are older than its newest
+/// `max_entries` entries, see `set_feed_max_entries`, returning the number deleted.
+/// like `purge_read_entries_older_than`, the feed remembers them,
+/// so refreshing does not add them again.
+fn prune_entries_beyond_max(tx: &rusqlite::Transaction, feed_id: FeedId) -> Result<usize> {
+    // a feed without a limit has a NULL `max_entries`, which no position is greater than.
+    // entries are in the same order as `query_entries` lists them.
+    const PRUNABLE: &str = "id IN (
+          SELECT id FROM (
+            SELECT
+              id,
+              ROW_NUMBER() OVER (
+                ORDER BY published_at DESC NULLS LAST, inserted_at DESC, id DESC
+              ) AS position
+            FROM entries WHERE feed_id = ?1
+          )
+          WHERE position > (SELECT max_entries FROM feeds WHERE id = ?1)
+        )
+        AND read_at IS NOT NULL
+        AND starred = 0";
+
+    tx.execute(
+        &format!(
+            "INSERT INTO purged_entries (feed_id, guid, link, title, published_at)
+            SELECT feed_id, guid, link, title, published_at FROM entries
+            WHERE {PRUNABLE}
+            AND (guid IS NOT NULL OR link IS NOT NULL OR title IS NOT NULL)"
+        ),
+        [feed_id],
     )?;
 
-    Ok(feed_id)
+    let pruned = tx.execute(&format!("DELETE FROM entries WHERE {PRUNABLE}"), [feed_id])?;
+
+    Ok(pruned)
 }
 
-pub fn delete_feed(conn: &mut rusqlite::Connection, feed_id: FeedId) -> Result<()> {
-    in_transaction(conn, |tx| {
-        tx.execute("DELETE FROM feeds WHERE id = ?1", [feed_id])?;
-        tx.execute("DELETE FROM entries WHERE feed_id = ?1", [feed_id])?;
-        Ok(())
-    })
+/// the entries a refresh stored
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RefreshSummary {
+    /// entries the feed did not have before
+    pub inserted: Vec<EntryId>,
+    /// entries whose title, description, or content changed
+    pub updated: Vec<EntryId>,
+    pub stats: RefreshStats,
 }
 
-fn add_entries_to_feed(
-    tx: &rusqlite::Transaction,
-    feed_id: FeedId,
-    entries: &[Entry],
-) -> Result<()> {
-    if !entries.is_empty() {
-        let now = Utc::now();
+/// what a refresh did with each of the entries it fetched,
+/// for telling a feed with nothing new apart from one that churns or misbehaves.
+/// a feed the server says has not been modified fetches nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    /// entries in the fetched feed
+    pub fetched: usize,
+    /// entries the feed did not have before
+    pub inserted: usize,
+    /// entries the feed already had unchanged, or that were purged
+    pub duplicates: usize,
+    /// entries the feed already had, whose title, description, or content changed
+    pub updated: usize,
+    /// entries that were skipped as they have neither a guid nor a link,
+    /// or as the database would not store them
+    pub errors: usize,
+}
 
-        let columns = [
-            "feed_id",
-            "title",
-            "author",
-            "pub_date",
-            "description",
-            "content",
-            "link",
-            "updated_at",
-        ];
+#[derive(Debug)]
+pub struct EntryContent {
+    pub content: Option<String>,
+    pub description: Option<String>,
+}
 
-        let mut entries_values = Vec::with_capacity(entries.len() * columns.len());
+/// a digest of the parts of an entry that change when it is edited,
+/// so a refresh can tell that an entry it already has is different now
+fn content_hash(entry: &Entry) -> String {
+    let mut hasher = Sha256::new();
 
-        for entry in entries {
-            let values = params![
-                feed_id,
-                entry.title,
-                entry.author,
-                entry.pub_date,
-                entry.description,
-                entry.content,
-                entry.link,
-                now,
-            ];
-            entries_values.extend_from_slice(values);
+    for field in [&entry.title, &entry.description, &entry.content] {
+        // length-prefixed, so moving text between fields changes the hash
+        match field {
+            Some(field) => {
+                hasher.update([1]);
+                hasher.update((field.len() as u64).to_be_bytes());
+                hasher.update(field.as_bytes());
+            }
+            None => hasher.update([0]),
         }
-
-        let query = build_bulk_insert_query("entries", &columns, entries);
-
-        tx.execute(&query, entries_values.as_slice())?;
     }
 
-    Ok(())
+    format!("{:x}", hasher.finalize())
 }
 
-fn build_bulk_insert_query<C: AsRef<str>, R>(table: &str, columns: &[C], rows: &[R]) -> String {
-    let idxs = (1..(rows.len() * columns.len() + 1)).collect::<Vec<_>>();
-
-    let values_groups_string = idxs
-        .chunks(columns.len())
-        .map(|chunk| {
-            let values_string = chunk
-                .iter()
-                .map(|i| format!("?{i}"))
-                .collect::<Vec<_>>()
-                .join(", ");
-            ["(", &values_string, ")"].concat()
-        })
-        .collect::<Vec<_>>()
-        .join(", ");
+/// RSS dates are supposed to be RFC 2822, but feeds use all sorts of formats,
+/// so fall back to a more forgiving parser
+fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(s.trim())
+        .ok()
+        .or_else(|| diligent_date_parser::parse_date(s))
+        .map(|dt| dt.with_timezone(&Utc))
+}
 
-    let columns_strs = columns
-        .iter()
-        .map(|column| column.as_ref())
-        .collect::<Vec<&str>>();
-
-    let columns_joined = columns_strs.join(", ");
-
-    let mut query = String::with_capacity(
-        "INSERT INTO ".len()
-            + table.len()
-            + 1 // '(' is a char
-            + columns_joined.len()
-            + ") ".len()
-            + "VALUES ".len()
-            + values_groups_string.len(),
-    );
+struct FeedAndEntries {
+    pub feed: Feed,
+    pub entries: Vec<Entry>,
+    /// when the feed says it last changed, exactly as it has it:
+    /// the RSS channel's `<lastBuildDate>`, or the Atom feed's `<updated>`
+    pub last_build_date: Option<String>,
+}
 
-    query.push_str("INSERT INTO ");
-    query.push_str(table);
-    query.push('(');
-    query.push_str(&columns_joined);
-    query.push_str(") ");
-    query.push_str("VALUES ");
-    query.push_str(&values_groups_string);
+/// how feeds are fetched over HTTP
+#[derive(Clone, Debug)]
+pub struct FetchOptions {
+    /// how long a whole request may take, from connecting to reading the body
+    pub timeout: Duration,
+    /// some servers refuse requests without a recognizable user agent
+    pub user_agent: String,
+    /// how many times to retry a request that failed in a way that may not happen again,
+    /// like a dropped connection or a 503
+    pub retries: u32,
+    /// how long to wait before the first retry.
+    /// every retry after that waits about twice as long as the one before,
+    /// unless the server says how long to wait with `Retry-After`.
+    pub retry_base_delay: Duration,
+}
 
-    query
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            user_agent: format!("russ/{}", env!("CARGO_PKG_VERSION")),
+            retries: 2,
+            retry_base_delay: Duration::from_millis(500),
+        }
+    }
 }
 
-pub fn get_feed(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<Feed> {
-    let s = conn.query_row(
-        "SELECT id, title, feed_link, link, feed_kind, refreshed_at, inserted_at, updated_at FROM feeds WHERE id=?1",
-        [feed_id],
-        |row| {
-            let feed_kind_str: String = row.get(4)?;
-            let feed_kind: FeedKind = FeedKind::from_str(&feed_kind_str)
-                .unwrap_or_else(|_| panic!("FeedKind must be Atom or RSS, got {feed_kind_str}"));
+impl FetchOptions {
+    /// an http client to pass to `subscribe_to_feed`, `refresh_feed`, and friends.
+    /// build it once and clone it, so connections are reused.
+    pub fn http_client(&self) -> HttpClient {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(self.timeout)
+            .user_agent(&self.user_agent)
+            // `fetch_feed` follows redirects itself, to see which ones are permanent
+            .redirects(0)
+            .build();
 
-            Ok(Feed {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                feed_link: row.get(2)?,
-                link: row.get(3)?,
-                feed_kind,
-                refreshed_at: row.get(5)?,
-                inserted_at: row.get(6)?,
-                updated_at: row.get(7)?,
-            })
-        },
-    )?;
+        HttpClient {
+            agent,
+            retries: self.retries,
+            retry_base_delay: self.retry_base_delay,
+        }
+    }
+}
 
-    Ok(s)
+/// see `FetchOptions::http_client`.
+/// clones share their connections.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    agent: ureq::Agent,
+    retries: u32,
+    retry_base_delay: Duration,
 }
 
-fn update_feed_refreshed_at(tx: &rusqlite::Transaction, feed_id: FeedId) -> Result<()> {
-    tx.execute(
-        "UPDATE feeds SET refreshed_at = ?2 WHERE id = ?1",
-        params![feed_id, Utc::now()],
-    )?;
+/// a username and password for feeds behind HTTP basic auth
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
 
-    Ok(())
+impl Credentials {
+    fn authorization(&self) -> String {
+        let user_pass = format!("{}:{}", self.username, self.password);
+        format!("Basic {}", base64::encode(user_pass))
+    }
 }
 
-pub fn get_feed_url(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<String> {
-    let s: String = conn.query_row(
-        "SELECT feed_link FROM feeds WHERE id=?1",
-        [feed_id],
-        |row| row.get(0),
-    )?;
+// so the password does not end up in logs or error messages
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
 
-    Ok(s)
+/// the `ETag` and `Last-Modified` headers of a feed response,
+/// sent back as `If-None-Match` and `If-Modified-Since`
+/// so the server can skip sending a feed that has not changed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
 }
 
-pub fn get_feeds(conn: &rusqlite::Connection) -> Result<Vec<Feed>> {
-    let mut statement = conn.prepare(
-        "SELECT 
-          id, 
-          title, 
-          feed_link, 
-          link, 
-          feed_kind, 
-          refreshed_at, 
-          inserted_at, 
-          updated_at 
-        FROM feeds ORDER BY lower(title) ASC",
-    )?;
-    let mut feeds = vec![];
-    for feed in statement.query_map([], |row| {
-        Ok(Feed {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            feed_link: row.get(2)?,
-            link: row.get(3)?,
-            feed_kind: row.get(4)?,
-            refreshed_at: row.get(5)?,
-            inserted_at: row.get(6)?,
-            updated_at: row.get(7)?,
-        })
-    })? {
-        feeds.push(feed?)
+struct FetchResult {
+    /// `None` when the server responded `304 Not Modified`
+    pub feed_and_entries: Option<FeedAndEntries>,
+    /// `None` for feeds that are not fetched over HTTP
+    pub status: Option<u16>,
+    pub cache_validators: CacheValidators,
+    /// where the feed was permanently redirected to, if it was
+    pub moved_to: Option<String>,
+    /// the response body the feed was parsed from, decoded, see `set_feed_keeps_raw_body`
+    pub body: Option<String>,
+}
+
+impl FeedAndEntries {
+    pub fn set_feed_link(&mut self, url: &str) {
+        self.feed.feed_link = Some(url.to_owned());
     }
 
-    Ok(feeds)
-}
+    /// identifies the feed by its self link, title and the guids of its first entries,
+    /// which stay the same when the feed moves to another url, see `find_feed_by_fingerprint`.
+    /// the self link tells apart feeds of the same site that list the same entries,
+    /// like one per category or format.
+    /// `None` for feeds whose entries have no guids, as a title alone says too little.
+    fn fingerprint(&self) -> Option<String> {
+        let guids = self
+            .entries
+            .iter()
+            .filter_map(|entry| entry.guid.as_deref())
+            .take(FINGERPRINT_GUIDS_LEN)
+            .collect::<Vec<_>>();
 
-pub fn get_feed_ids(conn: &rusqlite::Connection) -> Result<Vec<FeedId>> {
-    let mut statement = conn.prepare("SELECT id FROM feeds ORDER BY lower(title) ASC")?;
-    let mut ids = vec![];
-    for id in statement.query_map([], |row| row.get(0))? {
-        ids.push(id?)
+        if guids.is_empty() {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+
+        let self_link = self.feed.self_link.as_deref().map(normalize_feed_link);
+        // so a title is never taken for a self link
+        hasher.update([u8::from(self_link.is_some())]);
+
+        // length-prefixed, so moving text between fields changes the hash
+        for field in self_link
+            .as_deref()
+            .into_iter()
+            .chain(self.feed.title.as_deref())
+            .chain(guids)
+        {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+
+        Some(format!("{:x}", hasher.finalize()))
     }
 
-    Ok(ids)
-}
+    /// removes entries the feed lists more than once, keeping the first,
+    /// returning how many were removed.
+    /// entries are the same if they have the same key under `dedupe_strategy`,
+    /// the way refreshing tells them apart, see `DedupeStrategy::key`.
+    /// entries without one are kept, since there is no way to tell.
+    fn dedupe_entries(&mut self, dedupe_strategy: DedupeStrategy) -> usize {
+        let entries_len = self.entries.len();
+        // the publication dates of the entries kept with each key
+        let mut seen_keys: HashMap<String, Vec<Option<DateTime<Utc>>>> = HashMap::new();
 
-pub fn get_entry_meta(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<EntryMeta> {
-    let result = conn.query_row(
-        "SELECT 
-          id, 
-          feed_id, 
-          title, 
-          author, 
-          pub_date, 
-          link, 
-          read_at, 
-          inserted_at, 
-          updated_at 
-        FROM entries WHERE id=?1",
-        [entry_id],
-        |row| {
-            Ok(EntryMeta {
-                id: row.get(0)?,
-                feed_id: row.get(1)?,
-                title: row.get(2)?,
-                author: row.get(3)?,
-                pub_date: row.get(4)?,
-                link: row.get(5)?,
-                read_at: row.get(6)?,
-                inserted_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        },
-    )?;
+        self.entries.retain(|entry| {
+            let link = entry.link.as_deref().map(normalize_entry_link);
+            let Some(key) = dedupe_strategy.key(
+                entry.guid.as_deref(),
+                link.as_deref(),
+                entry.title.as_deref(),
+            ) else {
+                return true;
+            };
 
-    Ok(result)
-}
+            let seen_published_ats = seen_keys.entry(key).or_default();
+            let is_duplicate = seen_published_ats.iter().any(|seen_published_at| {
+                dedupe_strategy.is_same_entry(entry.published_at, *seen_published_at)
+            });
+            if !is_duplicate {
+                seen_published_ats.push(entry.published_at);
+            }
 
-pub fn get_entry_content(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<EntryContent> {
-    let result = conn.query_row(
-        "SELECT content, description FROM entries WHERE id=?1",
-        [entry_id],
-        |row| {
-            Ok(EntryContent {
-                content: row.get(0)?,
-                description: row.get(1)?,
-            })
-        },
-    )?;
+            !is_duplicate
+        });
 
-    Ok(result)
+        entries_len - self.entries.len()
+    }
 }
 
-pub fn get_entries_metas(
-    conn: &rusqlite::Connection,
-    read_mode: &ReadMode,
-    feed_id: FeedId,
-) -> Result<Vec<EntryMeta>> {
-    let read_at_predicate = match read_mode {
-        ReadMode::ShowUnread => "\nAND read_at IS NULL",
-        ReadMode::ShowRead => "\nAND read_at IS NOT NULL",
-        ReadMode::All => "\n",
-    };
+/// how many of a feed's first guids go into its fingerprint, see `find_feed_by_fingerprint`
+const FINGERPRINT_GUIDS_LEN: usize = 5;
 
-    // we get weird pubDate formats from feeds,
-    // so sort by inserted at as this as a stable order at least
-    let mut query = "SELECT 
-        id, 
-        feed_id, 
-        title, 
-        author, 
-        pub_date, 
-        link, 
-        read_at, 
-        inserted_at, 
-        updated_at 
-        FROM entries 
-        WHERE feed_id=?1"
-        .to_string();
+impl FromStr for FeedAndEntries {
+    type Err = anyhow::Error;
 
-    query.push_str(read_at_predicate);
-    query.push_str("\nORDER BY pub_date DESC, inserted_at DESC");
+    /// JSON is a JSON Feed. otherwise try RSS first, as it is the most common,
+    /// and fall back to Atom if the input is not a valid RSS channel
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_start().starts_with('{') {
+            return FeedAndEntries::from_json_feed(s);
+        }
+
+        match Channel::from_str(s) {
+            Ok(channel) => {
+                let (channel_title_in_cdata, item_titles_in_cdata) =
+                    rss_titles_in_cdata(s, channel.items().len());
+
+                let feed = Feed {
+                    id: 0,
+                    title: Some(title_text(channel.title(), channel_title_in_cdata)),
+                    feed_link: None,
+                    link: Some(channel.link().to_string()),
+                    self_link: channel.atom_ext().and_then(|atom_ext| {
+                        atom_ext
+                            .links()
+                            .iter()
+                            .find(|link| link.rel() == "self")
+                            .map(|link| link.href().to_owned())
+                    }),
+                    image_url: channel.image().and_then(|image| non_empty(image.url())),
+                    language: channel.language().and_then(normalize_language),
+                    min_refresh_interval: min_refresh_interval(&channel),
+                    refresh_interval: None,
+                    feed_kind: FeedKind::Rss,
+                    category: None,
+                    refreshed_at: None,
+                    last_error: None,
+                    last_error_at: None,
+                    last_status: None,
+                    last_fetch_duration: None,
+                    max_entries: None,
+                    keeps_raw_body: false,
+                    max_content_bytes: None,
+                    dedupe_strategy: DedupeStrategy::GuidThenLink,
+                    accept: None,
+                    inserted_at: Utc::now(),
+                    updated_at: Utc::now(),
+                };
+
+                let media_prefixes = media_prefixes(channel.namespaces());
+                let languages = entry_languages(s, b"item", channel.items().len());
+
+                let entries = channel
+                    .items()
+                    .iter()
+                    .zip(languages)
+                    .zip(item_titles_in_cdata)
+                    .map(|((item, language), title_in_cdata)| Entry {
+                        title: item.title().map(|title| title_text(title, title_in_cdata)),
+                        thumbnail_url: media_thumbnail_url(item.extensions(), &media_prefixes),
+                        language: language.or_else(|| feed.language.clone()),
+                        ..item.into()
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(FeedAndEntries {
+                    feed,
+                    entries,
+                    last_build_date: channel.last_build_date().map(|date| date.trim().to_owned()),
+                })
+            }
+
+            Err(rss_error) => match atom::Feed::from_str(s) {
+                Ok(atom_feed) => {
+                    let feed = Feed {
+                        id: 0,
+                        title: Some(atom_title_text(&atom_feed.title)),
+                        feed_link: None,
+                        link: atom_feed.links.first().map(|link| link.href().to_string()),
+                        self_link: atom_feed
+                            .links()
+                            .iter()
+                            .find(|link| link.rel() == "self")
+                            .map(|link| link.href().to_owned()),
+                        image_url: atom_feed
+                            .logo()
+                            .and_then(non_empty)
+                            .or_else(|| atom_feed.icon().and_then(non_empty)),
+                        language: atom_feed.lang().and_then(normalize_language),
+                        min_refresh_interval: None,
+                        refresh_interval: None,
+                        feed_kind: FeedKind::Atom,
+                        category: None,
+                        refreshed_at: None,
+                        last_error: None,
+                        last_error_at: None,
+                        last_status: None,
+                        last_fetch_duration: None,
+                        max_entries: None,
+                        keeps_raw_body: false,
+                        max_content_bytes: None,
+                        dedupe_strategy: DedupeStrategy::GuidThenLink,
+                        accept: None,
+                        inserted_at: Utc::now(),
+                        updated_at: Utc::now(),
+                    };
+
+                    let media_prefixes = media_prefixes(atom_feed.namespaces());
+                    let languages = entry_languages(s, b"entry", atom_feed.entries().len());
+
+                    let entries = atom_feed
+                        .entries()
+                        .iter()
+                        .zip(languages)
+                        .map(|(entry, language)| Entry {
+                            thumbnail_url: media_thumbnail_url(entry.extensions(), &media_prefixes),
+                            language: language.or_else(|| feed.language.clone()),
+                            ..entry.into()
+                        })
+                        .collect::<Vec<_>>();
+
+                    Ok(FeedAndEntries {
+                        feed,
+                        entries,
+                        last_build_date: Some(atom_feed.updated().to_rfc3339()),
+                    })
+                }
+                // neither parser accepted the input,
+                // the RSS error is usually the more informative of the two
+                Err(_atom_error) => Err(anyhow::Error::new(rss_error).context(Error::NotAFeed)),
+            },
+        }
+    }
+}
+
+/// no feed waits longer than this between refreshes,
+/// whatever it asks for, in case it asks for something silly
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// the longer of how many minutes the channel says it may be cached for, with `<ttl>`,
+/// and how often it says it is updated, with `<sy:updatePeriod>` and `<sy:updateFrequency>`
+fn min_refresh_interval(channel: &Channel) -> Option<Duration> {
+    use rss::extension::syndication::UpdatePeriod;
+
+    let ttl = channel
+        .ttl()
+        .and_then(|ttl| ttl.trim().parse::<u64>().ok())
+        // more minutes than fit in a `Duration` are more than the maximum anyway
+        .map(|minutes| {
+            minutes
+                .checked_mul(60)
+                .map_or(MAX_REFRESH_INTERVAL, Duration::from_secs)
+        });
+
+    let update_period = channel.syndication_ext().map(|syndication| {
+        let period = match syndication.period() {
+            UpdatePeriod::Hourly => 60 * 60,
+            UpdatePeriod::Daily => 24 * 60 * 60,
+            UpdatePeriod::Weekly => 7 * 24 * 60 * 60,
+            UpdatePeriod::Monthly => 30 * 24 * 60 * 60,
+            UpdatePeriod::Yearly => 365 * 24 * 60 * 60,
+        };
+        // the number of updates per period
+        let frequency = u64::from(syndication.frequency().max(1));
+        Duration::from_secs(period / frequency)
+    });
+
+    ttl.max(update_period)
+        .filter(|interval| !interval.is_zero())
+        .map(|interval| interval.min(MAX_REFRESH_INTERVAL))
+}
+
+impl FeedAndEntries {
+    fn from_json_feed(s: &str) -> Result<Self> {
+        let json_feed = serde_json::from_str::<json_feed::JsonFeed>(s)
+            .map_err(anyhow::Error::new)
+            .and_then(|json_feed| {
+                if json_feed.is_json_feed() {
+                    Ok(json_feed)
+                } else {
+                    Err(anyhow::anyhow!(
+                        "{} is not a JSON Feed version",
+                        json_feed.version
+                    ))
+                }
+            })
+            .context(Error::NotAFeed)?;
+
+        let feed = Feed {
+            id: 0,
+            title: json_feed.title.to_owned(),
+            feed_link: None,
+            link: json_feed.home_page_url.to_owned(),
+            self_link: json_feed.feed_url.to_owned(),
+            image_url: json_feed
+                .icon
+                .as_deref()
+                .and_then(non_empty)
+                .or_else(|| json_feed.favicon.as_deref().and_then(non_empty)),
+            language: json_feed.language.as_deref().and_then(normalize_language),
+            min_refresh_interval: None,
+            refresh_interval: None,
+            feed_kind: FeedKind::Json,
+            category: None,
+            refreshed_at: None,
+            last_error: None,
+            last_error_at: None,
+            last_status: None,
+            last_fetch_duration: None,
+            max_entries: None,
+            keeps_raw_body: false,
+            max_content_bytes: None,
+            dedupe_strategy: DedupeStrategy::GuidThenLink,
+            accept: None,
+            inserted_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let entries = json_feed
+            .items
+            .iter()
+            .map(|item| {
+                let entry = Entry::from(item);
+                Entry {
+                    language: entry.language.or_else(|| feed.language.clone()),
+                    ..entry
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(FeedAndEntries {
+            feed,
+            entries,
+            last_build_date: None,
+        })
+    }
+}
+
+/// subscribes to the feed at `url`.
+/// if `url` is a web page rather than a feed, subscribes to the first feed
+/// the page links to instead, see `discovery::discover_feeds`.
+/// if there already is a feed with this url (see `normalize_feed_link`),
+/// or one `url` redirects to, that feed is refreshed instead, and its id returned.
+pub fn subscribe_to_feed(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    url: &str,
+) -> Result<FeedId> {
+    subscribe(http_client, conn, url, None).map(|(feed_id, _)| feed_id)
+}
+
+/// like `subscribe_to_feed`, also returning what it did with the feed's entries,
+/// like how many of them could not be stored, see `RefreshStats::errors`
+pub fn subscribe_to_feed_with_summary(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    url: &str,
+) -> Result<(FeedId, RefreshSummary)> {
+    subscribe(http_client, conn, url, None)
+}
+
+/// like `subscribe_to_feed`, returning the feed subscribed to rather than just its id,
+/// for showing what was added
+pub fn subscribe_to_feed_and_get_feed(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    url: &str,
+) -> Result<Feed> {
+    let (feed_id, _) = subscribe(http_client, conn, url, None)?;
+    get_feed(conn, feed_id)
+}
+
+/// like `subscribe_to_feed`, for a feed behind HTTP basic auth.
+/// the credentials are stored with the feed and sent every time it is refreshed.
+pub fn subscribe_to_protected_feed(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    url: &str,
+    credentials: &Credentials,
+) -> Result<FeedId> {
+    subscribe(http_client, conn, url, Some(credentials)).map(|(feed_id, _)| feed_id)
+}
+
+fn subscribe(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    url: &str,
+    credentials: Option<&Credentials>,
+) -> Result<(FeedId, RefreshSummary)> {
+    let url = parse_feed_url(url)?;
+
+    // subscribing again pulls in what is new, rather than adding the feed twice
+    if let Some(feed_id) = get_feed_id_by_feed_link(conn, &url)? {
+        // with the credentials it was subscribed with this time,
+        // like after the password changed
+        if credentials.is_some() {
+            set_feed_credentials(conn, feed_id, credentials)?;
+        }
+        let refresh_summary = refresh_feed(http_client, conn, feed_id)?;
+        return Ok((feed_id, refresh_summary));
+    }
+
+    let fetch_result = fetch_new_feed(http_client, &url, credentials)?;
+    store_new_feed(conn, fetch_result, credentials)
+}
+
+/// subscribes to every feed in `urls`, like `subscribe_to_feed`,
+/// fetching at most `concurrency` feeds at a time,
+/// and calling `on_progress` with how many of the urls are done so far,
+/// and how many there are, as each one is done.
+/// like `refresh_feeds`, the fetches happen on worker threads,
+/// and the database writes happen on the calling thread, one feed at a time.
+/// a feed that fails to subscribe does not stop the others:
+/// every url gets its own result, in the same order as `urls`.
+pub fn subscribe_many(
+    http_client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    urls: &[String],
+    concurrency: usize,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Vec<(String, Result<FeedId>)> {
+    enum Fetch {
+        New(String),
+        Existing(FeedId, FeedRequest),
+    }
+
+    let mut results = urls.iter().map(|_| None).collect::<Vec<_>>();
+    let mut done = 0;
+    let mut fetches = Vec::with_capacity(urls.len());
+
+    for (i, url) in urls.iter().enumerate() {
+        let fetch =
+            parse_feed_url(url).and_then(|url| match get_feed_id_by_feed_link(conn, &url)? {
+                Some(feed_id) => Ok(Fetch::Existing(feed_id, get_feed_request(conn, feed_id)?)),
+                None => Ok(Fetch::New(url)),
+            });
+
+        match fetch {
+            Ok(fetch) => fetches.push((i, fetch)),
+            Err(e) => {
+                results[i] = Some(Err(e));
+                done += 1;
+                on_progress(done, urls.len());
+            }
+        }
+    }
+
+    fetch_concurrently(
+        fetches,
+        concurrency,
+        |(i, fetch)| match fetch {
+            Fetch::New(url) => (i, None, fetch_new_feed(http_client, &url, None)),
+            Fetch::Existing(feed_id, feed_request) => {
+                let started_at = std::time::Instant::now();
+                let fetch_result = fetch_feed_request(http_client, &feed_request);
+                (i, Some((feed_id, started_at.elapsed())), fetch_result)
+            }
+        },
+        |(i, existing_feed, fetch_result)| {
+            let result = match existing_feed {
+                Some((feed_id, fetch_duration)) => {
+                    store_refresh(conn, feed_id, fetch_result, fetch_duration).map(|_| feed_id)
+                }
+                None => fetch_result
+                    .and_then(|fetch_result| store_new_feed(conn, fetch_result, None))
+                    .map(|(feed_id, _)| feed_id),
+            };
+
+            results[i] = Some(result);
+            done += 1;
+            on_progress(done, urls.len());
+        },
+    );
+
+    urls.iter()
+        .cloned()
+        .zip(results.into_iter().flatten())
+        .collect()
+}
+
+/// the network half of subscribing: fetches the feed at `url`,
+/// or if `url` is a web page rather than a feed, the first feed the page links to
+fn fetch_new_feed(
+    http_client: &HttpClient,
+    url: &str,
+    credentials: Option<&Credentials>,
+) -> Result<FetchResult> {
+    let fetch_result = match fetch_feed(
+        http_client,
+        url,
+        &CacheValidators::default(),
+        credentials,
+        None,
+    ) {
+        Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)) => {
+            match crate::discovery::discover_feeds(http_client, url, credentials)
+                .ok()
+                .and_then(|feed_urls| feed_urls.into_iter().next())
+            {
+                Some(feed_url) if feed_url != url => fetch_feed(
+                    http_client,
+                    &feed_url,
+                    &CacheValidators::default(),
+                    credentials,
+                    None,
+                )?,
+                _ => return Err(e),
+            }
+        }
+        result => result?,
+    };
+
+    if fetch_result.feed_and_entries.is_none() {
+        return Err(anyhow::anyhow!(
+            "{url} responded 304 Not Modified to an unconditional request"
+        ));
+    }
+
+    Ok(fetch_result)
+}
+
+/// the database half of subscribing, returning the feed's id
+/// and what was done with its entries.
+/// if the feed redirected or was discovered at a url there already is a feed for,
+/// the entries go to that feed, like refreshing it, and its id is returned.
+fn store_new_feed(
+    conn: &mut rusqlite::Connection,
+    fetch_result: FetchResult,
+    credentials: Option<&Credentials>,
+) -> Result<(FeedId, RefreshSummary)> {
+    let Some(mut feed_and_entries) = fetch_result.feed_and_entries else {
+        return Err(anyhow::anyhow!("there is no feed to subscribe to"));
+    };
+    // new feeds tell entries apart the default way
+    let duplicates = feed_and_entries.dedupe_entries(DedupeStrategy::GuidThenLink);
+
+    if let Some(feed_link) = &feed_and_entries.feed.feed_link {
+        if let Some(feed_id) = get_feed_id_by_feed_link(conn, &normalize_feed_link(feed_link))? {
+            if credentials.is_some() {
+                set_feed_credentials(conn, feed_id, credentials)?;
+            }
+            let refresh_summary = merge_remote_feed(
+                conn,
+                feed_id,
+                FetchResult {
+                    feed_and_entries: Some(feed_and_entries),
+                    ..fetch_result
+                },
+            )?;
+            return Ok((feed_id, refresh_summary));
+        }
+    }
+
+    let fingerprint = feed_and_entries.fingerprint();
+
+    // the same feed at a new url, like after the site moved,
+    // keeps its entries and moves to the new url.
+    // without a self link, feeds of the same site that list the same entries
+    // can't be told apart, so they are subscribed to separately.
+    if let Some(feed_id) = match &fingerprint {
+        Some(fingerprint) if feed_and_entries.feed.self_link.is_some() => {
+            find_feed_by_fingerprint(conn, fingerprint)?
+        }
+        _ => None,
+    } {
+        if credentials.is_some() {
+            set_feed_credentials(conn, feed_id, credentials)?;
+        }
+        let moved_to = feed_and_entries.feed.feed_link.clone();
+        let refresh_summary = merge_remote_feed(
+            conn,
+            feed_id,
+            FetchResult {
+                feed_and_entries: Some(feed_and_entries),
+                moved_to,
+                ..fetch_result
+            },
+        )?;
+        return Ok((feed_id, refresh_summary));
+    }
+
+    let (feed_id, inserted) = in_transaction(conn, |tx| {
+        let feed_id = create_feed(tx, &feed_and_entries.feed)?;
+        update_feed_fingerprint(tx, feed_id, fingerprint.as_deref())?;
+        update_feed_last_build_date(tx, feed_id, feed_and_entries.last_build_date.as_deref())?;
+        let inserted = add_entries_to_feed(tx, feed_id, &feed_and_entries.entries)?;
+        update_feed_cache_validators(tx, feed_id, &fetch_result.cache_validators)?;
+        set_feed_credentials(tx, feed_id, credentials)?;
+        Ok((feed_id, inserted))
+    })?;
+
+    let entries_len = feed_and_entries.entries.len();
+
+    Ok((
+        feed_id,
+        RefreshSummary {
+            stats: RefreshStats {
+                fetched: entries_len + duplicates,
+                inserted: inserted.len(),
+                duplicates,
+                updated: 0,
+                errors: entries_len - inserted.len(),
+            },
+            inserted,
+            updated: vec![],
+        },
+    ))
+}
+
+/// what `validate_feed` found
+#[derive(Clone, Debug)]
+pub struct FeedPreview {
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub item_count: usize,
+    pub detected_format: FeedKind,
+}
+
+impl From<FeedAndEntries> for FeedPreview {
+    fn from(feed_and_entries: FeedAndEntries) -> Self {
+        FeedPreview {
+            title: feed_and_entries.feed.title,
+            link: feed_and_entries.feed.link,
+            item_count: feed_and_entries.entries.len(),
+            detected_format: feed_and_entries.feed.feed_kind,
+        }
+    }
+}
+
+impl FromStr for FeedPreview {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FeedAndEntries::from_str(s).map(FeedPreview::from)
+    }
+}
+
+/// fetches and parses the feed at `url` without subscribing to it,
+/// so people can see what they would get before they subscribe.
+/// fails with `Error::NotAFeed` if `url` is not an RSS, Atom, or JSON feed.
+/// nothing is written to the database.
+pub fn validate_feed(http_client: &HttpClient, url: &str) -> Result<FeedPreview> {
+    let url = parse_feed_url(url)?;
+
+    let fetch_result = fetch_feed(http_client, &url, &CacheValidators::default(), None, None)?;
+    let feed_and_entries = fetch_result
+        .feed_and_entries
+        .with_context(|| format!("{url} responded 304 Not Modified to an unconditional request"))?;
+
+    Ok(feed_and_entries.into())
+}
+
+const MAX_REDIRECTS: usize = 5;
+
+/// the schemes of the urls `fetch_feed` can fetch
+const FEED_URL_SCHEMES: &[&str] = &["http", "https", "file"];
+
+/// `url` normalized (see `normalize_feed_link`),
+/// or `Error::InvalidUrl` if it does not parse or `fetch_feed` can't fetch it,
+/// rather than whatever error fetching it would fail with
+fn parse_feed_url(url: &str) -> Result<String> {
+    let url = url.trim();
+
+    match url::Url::parse(url) {
+        Ok(parsed_url) if FEED_URL_SCHEMES.contains(&parsed_url.scheme()) => {
+            Ok(normalize_feed_link(url))
+        }
+        _ => Err(Error::InvalidUrl(url.to_owned()).into()),
+    }
+}
+
+/// fetches and parses the feed at `url`.
+/// if the feed has not changed since the response `cache_validators` came from,
+/// the server may respond `304 Not Modified` and the result has no feed.
+fn fetch_feed(
+    http_client: &HttpClient,
+    url: &str,
+    cache_validators: &CacheValidators,
+    credentials: Option<&Credentials>,
+    accept: Option<&str>,
+) -> Result<FetchResult> {
+    if let Some(url) = url::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+    {
+        return fetch_feed_file(&url);
+    }
+
+    let Fetched { resp, moved_to, .. } =
+        get(http_client, url, cache_validators, credentials, accept)?;
+
+    let response_cache_validators = CacheValidators {
+        etag: resp.header("ETag").map(|etag| etag.to_owned()),
+        last_modified: resp
+            .header("Last-Modified")
+            .map(|last_modified| last_modified.to_owned()),
+    };
+
+    if resp.status() == 304 {
+        // a 304 may leave out the validators, in which case the old ones still hold
+        let cache_validators = CacheValidators {
+            etag: response_cache_validators
+                .etag
+                .or_else(|| cache_validators.etag.clone()),
+            last_modified: response_cache_validators
+                .last_modified
+                .or_else(|| cache_validators.last_modified.clone()),
+        };
+
+        return Ok(FetchResult {
+            feed_and_entries: None,
+            status: Some(304),
+            cache_validators,
+            moved_to,
+            body: None,
+        });
+    }
+
+    let status = resp.status();
+    let body = read_body(resp)?;
+
+    let mut feed = FeedAndEntries::from_str(&body)?;
+    feed.set_feed_link(moved_to.as_deref().unwrap_or(url));
+
+    Ok(FetchResult {
+        feed_and_entries: Some(feed),
+        status: Some(status),
+        cache_validators: response_cache_validators,
+        moved_to,
+        body: Some(body),
+    })
+}
+
+/// reads a feed from a local file, like `file:///home/me/feed.xml`,
+/// for trying out a feed before publishing it.
+/// files have no cache validators, so every refresh reads the whole file.
+fn fetch_feed_file(url: &url::Url) -> Result<FetchResult> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow::anyhow!("{url} is not a local file"))?;
+
+    let body = std::fs::read(&path).with_context(|| format!("could not read {url}"))?;
+
+    let body = decode_body(&body, None);
+    let mut feed = FeedAndEntries::from_str(&body)?;
+    feed.set_feed_link(url.as_str());
+
+    Ok(FetchResult {
+        feed_and_entries: Some(feed),
+        status: None,
+        cache_validators: CacheValidators::default(),
+        moved_to: None,
+        body: Some(body),
+    })
+}
+
+pub(crate) struct Fetched {
+    /// a 2xx or 304 response
+    pub resp: ureq::Response,
+    /// the url `resp` came from, after redirects
+    pub url: String,
+    /// where `url` permanently moved to, if it did
+    pub moved_to: Option<String>,
+}
+
+/// the `Accept` header of requests, preferring feeds,
+/// for servers that send a web page to requests that do not ask for a feed
+pub(crate) const FEED_ACCEPT: &str = "application/rss+xml, application/atom+xml, \
+    application/feed+json, application/xml;q=0.9, text/xml;q=0.9, \
+    application/json;q=0.8, */*;q=0.7";
+
+/// GETs `url`, following redirects.
+/// redirects are followed here rather than by `http_client`
+/// (see `FetchOptions::http_client`), so that the result can say
+/// where `url` permanently moved to.
+/// an `http_client` that follows redirects itself works too,
+/// but never reports a move.
+/// `credentials` are only sent to the scheme, host, and port of `url`,
+/// not to other sites it redirects to.
+/// the `Accept` header is `accept`, or `FEED_ACCEPT` without one.
+pub(crate) fn get(
+    http_client: &HttpClient,
+    url: &str,
+    cache_validators: &CacheValidators,
+    credentials: Option<&Credentials>,
+    accept: Option<&str>,
+) -> Result<Fetched> {
+    let mut current_url = url.to_owned();
+    let mut moved_to = None;
+    let mut only_permanent_redirects = true;
+    let mut redirects = 0;
+
+    let origin = url::Url::parse(url).map(|url| url.origin()).ok();
+
+    let resp = loop {
+        let mut request = http_client
+            .agent
+            .get(&current_url)
+            .set("Accept", accept.unwrap_or(FEED_ACCEPT))
+            .set("Accept-Encoding", "gzip, deflate");
+
+        if let Some(credentials) = credentials {
+            let current_origin = url::Url::parse(&current_url).map(|url| url.origin()).ok();
+
+            if origin.is_some() && current_origin == origin {
+                request = request.set("Authorization", &credentials.authorization());
+            }
+        }
+
+        if let Some(etag) = &cache_validators.etag {
+            request = request.set("If-None-Match", etag);
+        }
+
+        if let Some(last_modified) = &cache_validators.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+
+        let resp = call_with_retries(http_client, request)?;
+
+        if !matches!(resp.status(), 301 | 302 | 303 | 307 | 308) {
+            break resp;
+        }
+
+        if redirects == MAX_REDIRECTS {
+            return Err(anyhow::anyhow!(
+                "{url} redirected more than {MAX_REDIRECTS} times"
+            ));
+        }
+
+        let location = resp
+            .header("Location")
+            .with_context(|| format!("{current_url} redirected without a Location"))?;
+
+        let next_url = url::Url::parse(&current_url)?
+            .join(location)
+            .with_context(|| format!("{current_url} redirected to an invalid url {location}"))?
+            .to_string();
+
+        // the feed has moved as far as the first temporary redirect,
+        // past that it might come back to where it was
+        only_permanent_redirects &= matches!(resp.status(), 301 | 308);
+        if only_permanent_redirects {
+            moved_to = Some(next_url.clone());
+        }
+
+        current_url = next_url;
+        redirects += 1;
+    };
+
+    // ureq only errors on 4xx and 5xx
+    if !(200..300).contains(&resp.status()) && resp.status() != 304 {
+        return Err(Error::HttpStatus(resp.status()).into());
+    }
+
+    Ok(Fetched {
+        resp,
+        url: current_url,
+        moved_to,
+    })
+}
+
+/// `call_with_retries` gives up rather than wait longer than this for a retry
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// sends `request`, retrying up to `http_client.retries` times
+/// if it fails in a way that may not happen again.
+/// the waits between retries back off exponentially, with jitter so that
+/// feeds on the same struggling server are not all retried at once.
+fn call_with_retries(http_client: &HttpClient, request: ureq::Request) -> Result<ureq::Response> {
+    let mut retries = 0;
+
+    loop {
+        let e = match request.clone().call() {
+            Ok(resp) => return Ok(resp),
+            Err(e) => e,
+        };
+
+        let delay = match &e {
+            ureq::Error::Status(status, resp) if is_retryable_status(*status) => Some(
+                resp.header("Retry-After")
+                    .and_then(parse_retry_after)
+                    .unwrap_or_else(|| backoff(http_client.retry_base_delay, retries)),
+            ),
+            ureq::Error::Transport(transport) if is_retryable_transport(transport) => {
+                Some(backoff(http_client.retry_base_delay, retries))
+            }
+            _ => None,
+        };
+
+        match delay {
+            Some(delay) if retries < http_client.retries && delay <= MAX_RETRY_DELAY => {
+                std::thread::sleep(delay);
+                retries += 1;
+            }
+            _ => {
+                return Err(match e {
+                    ureq::Error::Status(status, _) => Error::HttpStatus(status).into(),
+                    e if is_timeout(&e) => anyhow::Error::new(e).context(Error::Timeout),
+                    e => anyhow::Error::new(e).context(Error::Network),
+                })
+            }
+        }
+    }
+}
+
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 500 | 502 | 503 | 504)
+}
+
+/// connections that could not be made or were dropped.
+/// timeouts are not retried, as they already took as long as a request may take.
+fn is_retryable_transport(transport: &ureq::Transport) -> bool {
+    matches!(
+        transport.kind(),
+        ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+    ) && !is_timeout(transport)
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date
+fn parse_retry_after(retry_after: &str) -> Option<Duration> {
+    let retry_after = retry_after.trim();
+
+    if let Ok(seconds) = retry_after.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = DateTime::parse_from_rfc2822(retry_after).ok()?;
+    Some(
+        (retry_at.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// `base_delay` doubled for every retry so far, less up to half of that at random
+fn backoff(base_delay: Duration, retries: u32) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let delay = base_delay.saturating_mul(2u32.saturating_pow(retries));
+
+    // `RandomState` is randomly seeded, which is random enough for jitter
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let jitter = (random >> 11) as f64 / (1u64 << 53) as f64;
+
+    delay.mul_f64(1.0 - jitter / 2.0)
+}
+
+/// the most `read_body` reads, before and after decompressing.
+/// the same limit as ureq's `Response::into_string`.
+const MAX_BODY_LEN: u64 = 10 * 1024 * 1024;
+
+/// the body of `resp` as text, decompressed if it was sent with gzip or deflate.
+/// some servers label bodies as compressed when they aren't,
+/// so a body that fails to decompress is used as it is.
+/// if it really was compressed, it then fails to parse as a feed.
+/// brotli is not supported, and `get` does not ask for it.
+/// see `decode_body` for how the text is decoded.
+pub(crate) fn read_body(resp: ureq::Response) -> Result<String> {
+    let content_encoding = resp
+        .header("Content-Encoding")
+        .map(|content_encoding| content_encoding.trim().to_ascii_lowercase());
+    let content_type = resp
+        .header("Content-Type")
+        .map(|content_type| content_type.to_owned());
+
+    let mut body = vec![];
+    resp.into_reader()
+        .take(MAX_BODY_LEN + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| {
+            if is_timeout(&e) {
+                anyhow::Error::new(e).context(Error::Timeout)
+            } else {
+                anyhow::Error::new(e).context(Error::Network)
+            }
+        })?;
+
+    let body = match content_encoding.as_deref() {
+        Some("gzip" | "x-gzip") => decompress(MultiGzDecoder::new(&body[..])).unwrap_or(body),
+        // deflate is supposed to be wrapped in zlib, but some servers send it raw
+        Some("deflate") => decompress(ZlibDecoder::new(&body[..]))
+            .or_else(|_| decompress(DeflateDecoder::new(&body[..])))
+            .unwrap_or(body),
+        _ => body,
+    };
+
+    if body.len() as u64 > MAX_BODY_LEN {
+        return Err(anyhow::anyhow!(
+            "the response is bigger than {MAX_BODY_LEN} bytes"
+        ));
+    }
+
+    Ok(decode_body(&body, content_type.as_deref()))
+}
+
+/// `body` as text, in the encoding its byte order mark says,
+/// or else the charset of its `Content-Type`,
+/// or else the encoding its XML declaration says, or else UTF-8.
+/// ISO-8859-1 is decoded as its superset Windows-1252, like browsers do.
+/// bytes that are not valid in the encoding become U+FFFD.
+///
+/// the feed parsers decode by the XML declaration too,
+/// so the declaration's encoding is changed to UTF-8 to match the text.
+fn decode_body(body: &[u8], content_type: Option<&str>) -> String {
+    let charset = content_type.and_then(|content_type| {
+        content_type.split(';').skip(1).find_map(|parameter| {
+            let (name, value) = parameter.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("charset")
+                .then(|| value.trim().trim_matches('"'))
+        })
+    });
+
+    let declared_encoding = xml_declared_encoding(body)
+        .and_then(|range| encoding_rs::Encoding::for_label(&body[range]))
+        // a declaration that can be read as ASCII is not in UTF-16
+        .map(|encoding| encoding.output_encoding());
+
+    let encoding = charset
+        .and_then(|charset| encoding_rs::Encoding::for_label(charset.as_bytes()))
+        .or(declared_encoding)
+        .unwrap_or(encoding_rs::UTF_8);
+
+    let (text, _, _) = encoding.decode(body);
+    let mut text = text.into_owned();
+
+    if let Some(range) = xml_declared_encoding(text.as_bytes()) {
+        text.replace_range(range, "UTF-8");
+    }
+
+    text
+}
+
+/// where the value of the `encoding` in the XML declaration at the start of `body` is
+fn xml_declared_encoding(body: &[u8]) -> Option<std::ops::Range<usize>> {
+    const UTF_8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    let start = if body.starts_with(UTF_8_BOM) {
+        UTF_8_BOM.len()
+    } else {
+        0
+    };
+
+    if !body[start..].starts_with(b"<?xml") {
+        return None;
+    }
+
+    let end = start
+        + body[start..]
+            .windows(2)
+            .position(|window| window == b"?>")?;
+    let declaration = &body[..end];
+
+    let skip_whitespace = |mut position: usize| {
+        while declaration.get(position)?.is_ascii_whitespace() {
+            position += 1;
+        }
+        Some(position)
+    };
+
+    let mut position = declaration
+        .windows(b"encoding".len())
+        .position(|window| window == b"encoding")?
+        + b"encoding".len();
+
+    position = skip_whitespace(position)?;
+    if declaration.get(position)? != &b'=' {
+        return None;
+    }
+    position = skip_whitespace(position + 1)?;
+
+    let quote = *declaration.get(position)?;
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+
+    let value_start = position + 1;
+    let value_len = declaration[value_start..]
+        .iter()
+        .position(|byte| *byte == quote)?;
+
+    Some(value_start..value_start + value_len)
+}
+
+fn decompress(decoder: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    decoder
+        .take(MAX_BODY_LEN + 1)
+        .read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// whether `s` parses as RSS, Atom, or JSON Feed
+pub(crate) fn is_feed(s: &str) -> bool {
+    FeedAndEntries::from_str(s).is_ok()
+}
+
+/// ureq reports timeouts as `std::io::ErrorKind::TimedOut`,
+/// somewhere in the chain of error sources
+fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(e);
+
+    while let Some(e) = source {
+        if e.downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return true;
+        }
+
+        source = e.source();
+    }
+
+    false
+}
+
+/// fetches the feed and stores the new entries.
+/// uses the guid as the uniqueness key, falling back to the link
+/// for entries that do not have a guid.
+/// entries that are already stored are updated if their title,
+/// description, or content has changed.
+/// the fetch is conditional on the feed's stored `CacheValidators`,
+/// so a feed the server says has not been modified adds no entries.
+pub fn refresh_feed(
+    client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<RefreshSummary> {
+    let feed_request = get_feed_request(conn, feed_id)
+        .with_context(|| format!("Unable to get url for feed id {feed_id} from the database",))?;
+
+    let started_at = std::time::Instant::now();
+    let fetch_result = fetch_feed_request(client, &feed_request);
+
+    store_refresh(conn, feed_id, fetch_result, started_at.elapsed())
+}
+
+/// like `refresh_feed`, returning the entries the refresh inserted,
+/// in the order the feed has them, rather than just their ids
+pub fn refresh_feed_entries(
+    client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<Vec<Entry>> {
+    let refresh_summary = refresh_feed(client, conn, feed_id)?;
+    get_entries_by_ids(conn, &refresh_summary.inserted)
+}
+
+/// like `refresh_feed`, calling `on_new_entry` with each entry the refresh inserted,
+/// in the order the feed has them, for notifying people of new entries.
+/// entries the feed already had, including ones whose content changed, are not new.
+pub fn refresh_feed_and_notify(
+    client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+    mut on_new_entry: impl FnMut(&Entry),
+) -> Result<RefreshSummary> {
+    let refresh_summary = refresh_feed(client, conn, feed_id)?;
+
+    for entry in get_entries_by_ids(conn, &refresh_summary.inserted)? {
+        on_new_entry(&entry);
+    }
+
+    Ok(refresh_summary)
+}
+
+/// an entry a refresh would add, see `refresh_feed_dry_run`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryPreview {
+    /// as it would be stored, see `clean_text`
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub link: Option<String>,
+    pub guid: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+impl From<Entry> for EntryPreview {
+    fn from(entry: Entry) -> Self {
+        Self {
+            title: entry.title.as_deref().and_then(clean_text),
+            author: entry.author.as_deref().and_then(clean_text),
+            link: entry.link,
+            guid: entry.guid,
+            published_at: entry.published_at,
+        }
+    }
+}
+
+/// fetches the feed and compares its entries to the ones it has, like `refresh_feed`,
+/// returning the entries a refresh would add, in the order the feed has them.
+/// nothing is stored, not even that the feed was fetched,
+/// so this is for seeing what a refresh would do, like with another `DedupeStrategy`.
+pub fn refresh_feed_dry_run(
+    client: &HttpClient,
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<Vec<EntryPreview>> {
+    let feed_request = get_feed_request(conn, feed_id)
+        .with_context(|| format!("Unable to get url for feed id {feed_id} from the database",))?;
+
+    let Some(mut remote_feed) = fetch_feed_request(client, &feed_request)?.feed_and_entries else {
+        return Ok(vec![]);
+    };
+
+    if is_unchanged(
+        conn,
+        feed_id,
+        &remote_feed,
+        remote_feed.fingerprint().as_deref(),
+    )? {
+        return Ok(vec![]);
+    }
+
+    let diff = diff_remote_entries(conn, feed_id, &mut remote_feed)?;
+
+    Ok(diff.to_add.into_iter().map(EntryPreview::from).collect())
+}
+
+/// what `fetch_full_content` asks for, since entry links are web pages rather than feeds
+const ARTICLE_ACCEPT: &str = "text/html, application/xhtml+xml;q=0.9, */*;q=0.8";
+
+/// downloads the web page the entry links to and stores its main content,
+/// see `readability::extract_main_content`, as `Entry::full_content`,
+/// for feeds that only have a summary of each entry.
+/// nothing does this on its own, it is for entries someone wants to read in full.
+/// if the entry has no link, the page can't be fetched or it has no content,
+/// the error is stored in `Entry::full_content_error` and returned,
+/// and any content from an earlier fetch is kept.
+pub fn fetch_full_content(
+    http_client: &HttpClient,
+    conn: &rusqlite::Connection,
+    entry_id: EntryId,
+) -> Result<()> {
+    let link = get_entry(conn, entry_id)?.link;
+
+    let full_content = link
+        .ok_or_else(|| anyhow::anyhow!("the entry has no link"))
+        .and_then(|link| {
+            let Fetched { resp, .. } = get(
+                http_client,
+                &link,
+                &CacheValidators::default(),
+                None,
+                Some(ARTICLE_ACCEPT),
+            )?;
+            let body = read_body(resp)?;
+            crate::readability::extract_main_content(&body)
+                .ok_or_else(|| anyhow::anyhow!("no article could be found at {link}"))
+        });
+
+    match full_content {
+        Ok(full_content) => {
+            conn.execute(
+                "UPDATE entries SET full_content = ?2, full_content_error = NULL WHERE id = ?1",
+                params![entry_id, full_content],
+            )?;
+            Ok(())
+        }
+        Err(e) => {
+            // failing to store the error should not hide it from the caller
+            let _ = conn.execute(
+                "UPDATE entries SET full_content_error = ?2 WHERE id = ?1",
+                params![entry_id, format!("{e:#}")],
+            );
+            Err(e)
+        }
+    }
+}
+
+/// refreshes every feed, see `refresh_feeds`
+pub fn refresh_all_feeds(
+    client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    concurrency: usize,
+) -> Result<Vec<(FeedId, Result<RefreshSummary>)>> {
+    let feed_ids = get_feed_ids(conn)?;
+    Ok(refresh_feeds(client, conn, &feed_ids, concurrency))
+}
+
+/// refreshes `feed_ids`, fetching at most `concurrency` feeds at a time.
+/// the fetches happen on worker threads, but as a connection
+/// cannot be shared between threads, all database writes happen
+/// on the calling thread, one feed at a time.
+/// workers go on fetching while a feed is written, so slow writes
+/// do not hold up fetches, and slow fetches do not hold up writes.
+/// a feed that fails to refresh does not stop the others:
+/// every feed gets its own result, in the order the feeds finished.
+pub fn refresh_feeds(
+    client: &HttpClient,
+    conn: &mut rusqlite::Connection,
+    feed_ids: &[FeedId],
+    concurrency: usize,
+) -> Vec<(FeedId, Result<RefreshSummary>)> {
+    let mut results = Vec::with_capacity(feed_ids.len());
+    let mut feeds_to_fetch = Vec::with_capacity(feed_ids.len());
+
+    for feed_id in feed_ids {
+        match get_feed_request(conn, *feed_id) {
+            Ok(feed_request) => feeds_to_fetch.push((*feed_id, feed_request)),
+            Err(e) => results.push((
+                *feed_id,
+                Err(e.context(format!(
+                    "Unable to get url for feed id {feed_id} from the database"
+                ))),
+            )),
+        }
+    }
+
+    fetch_concurrently(
+        feeds_to_fetch,
+        concurrency,
+        |(feed_id, feed_request)| {
+            let started_at = std::time::Instant::now();
+            let fetch_result = fetch_feed_request(client, &feed_request);
+            (feed_id, fetch_result, started_at.elapsed())
+        },
+        |(feed_id, fetch_result, fetch_duration)| {
+            let result = store_refresh(conn, feed_id, fetch_result, fetch_duration);
+            results.push((feed_id, result));
+        },
+    );
+
+    results
+}
+
+/// calls `fetch` with each of `items` on at most `concurrency` worker threads,
+/// and `on_fetched` on the calling thread with what it returns,
+/// in the order the items finished, see `refresh_feeds`, `subscribe_many`
+/// and `check_feeds_health`
+fn fetch_concurrently<T: Send, R: Send>(
+    items: Vec<T>,
+    concurrency: usize,
+    fetch: impl Fn(T) -> R + Sync,
+    mut on_fetched: impl FnMut(R),
+) {
+    let workers_len = concurrency.clamp(1, items.len().max(1));
+    let items = std::sync::Mutex::new(items.into_iter());
+    let (fetched_s, fetched_r) = std::sync::mpsc::channel();
+
+    std::thread::scope(|s| {
+        for _ in 0..workers_len {
+            let fetched_s = fetched_s.clone();
+            let items = &items;
+            let fetch = &fetch;
+
+            s.spawn(move || loop {
+                let next = items.lock().unwrap().next();
+                let Some(item) = next else {
+                    break;
+                };
+
+                if fetched_s.send(fetch(item)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // so the receive loop ends when the last worker is done
+        drop(fetched_s);
+
+        for fetched in fetched_r {
+            on_fetched(fetched);
+        }
+    });
+}
+
+/// what `check_feeds_health` found out about a feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedHealth {
+    /// the feed is there and parses
+    Ok,
+    /// the server says the feed has not changed since it was last refreshed,
+    /// so it is still there
+    NotModified,
+    /// the server responded with an error status, like 404 or 410 for a feed that is gone
+    HttpError(u16),
+    /// the server could not be reached, or took too long to respond
+    Unreachable,
+    /// the server responded, but not with a feed, see `Error::NotAFeed`
+    ParseError,
+}
+
+impl FeedHealth {
+    fn from_fetch_result(fetch_result: &Result<FetchResult>) -> Self {
+        match fetch_result {
+            Ok(fetch_result) if fetch_result.feed_and_entries.is_none() => FeedHealth::NotModified,
+            Ok(_) => FeedHealth::Ok,
+            Err(e) => match e.downcast_ref::<Error>() {
+                Some(Error::HttpStatus(status)) => FeedHealth::HttpError(*status),
+                Some(Error::NotAFeed) => FeedHealth::ParseError,
+                _ => FeedHealth::Unreachable,
+            },
+        }
+    }
+}
+
+/// fetches every feed at the url it was subscribed to, with the feed's cache validators,
+/// so feeds that have not changed cost little, to find the feeds that are gone
+/// or broken, fetching at most `concurrency` feeds at a time.
+/// unlike a refresh, it does not fall back to the feed's self link,
+/// so a feed whose url is gone is reported as such.
+/// nothing is written to the database, not even the refresh's status,
+/// see `refresh_feeds` for that.
+/// every feed gets its own result, in the same order as `get_feeds`,
+/// and a feed without a url to fetch is `FeedHealth::Unreachable`.
+pub fn check_feeds_health(
+    http_client: &HttpClient,
+    conn: &rusqlite::Connection,
+    concurrency: usize,
+) -> Result<Vec<(FeedId, FeedHealth)>> {
+    let feed_ids = get_feed_ids(conn)?;
+    let mut results = feed_ids
+        .iter()
+        .map(|feed_id| (*feed_id, FeedHealth::Unreachable))
+        .collect::<Vec<_>>();
+
+    let feeds_to_fetch = feed_ids
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, feed_id)| Some((i, get_feed_request(conn, feed_id).ok()?)))
+        .collect::<Vec<_>>();
+
+    fetch_concurrently(
+        feeds_to_fetch,
+        concurrency,
+        |(i, feed_request)| {
+            let fetch_result = fetch_feed(
+                http_client,
+                &feed_request.url,
+                &feed_request.cache_validators,
+                feed_request.credentials.as_ref(),
+                feed_request.accept.as_deref(),
+            );
+            (i, FeedHealth::from_fetch_result(&fetch_result))
+        },
+        |(i, feed_health)| results[i].1 = feed_health,
+    );
+
+    Ok(results)
+}
+
+/// fetches the feed at the url it was subscribed to.
+/// if that fails, but the feed said it lives somewhere else (see `Feed::self_link`),
+/// fetches it from there instead. the result only says the feed moved there
+/// if the url it was subscribed to is gone for good, with a 404 or 410,
+/// as a url that is down for now, or timed out, may well be back next time.
+/// credentials are only sent to the self link if it is on the same site.
+fn fetch_feed_request(client: &HttpClient, feed_request: &FeedRequest) -> Result<FetchResult> {
+    let FeedRequest {
+        url,
+        self_link,
+        cache_validators,
+        credentials,
+        accept,
+    } = feed_request;
+
+    let e = match fetch_feed(
+        client,
+        url,
+        cache_validators,
+        credentials.as_ref(),
+        accept.as_deref(),
+    ) {
+        Ok(fetch_result) => return Ok(fetch_result),
+        Err(e) => e.context(format!("Failed to fetch feed {url}")),
+    };
+
+    let Some(self_link) = self_link
+        .as_deref()
+        .filter(|self_link| normalize_feed_link(self_link) != normalize_feed_link(url))
+    else {
+        return Err(e);
+    };
+
+    let is_gone = matches!(
+        e.downcast_ref::<Error>(),
+        Some(Error::HttpStatus(404 | 410))
+    );
+
+    let origin = |url: &str| url::Url::parse(url).map(|url| url.origin()).ok();
+    let credentials = credentials
+        .as_ref()
+        .filter(|_| origin(url).is_some() && origin(url) == origin(self_link));
+
+    // the cache validators came from `url`, so they may not hold for the self link
+    match fetch_feed(
+        client,
+        self_link,
+        &CacheValidators::default(),
+        credentials,
+        accept.as_deref(),
+    ) {
+        Ok(fetch_result) => Ok(FetchResult {
+            moved_to: if is_gone {
+                fetch_result.moved_to.or_else(|| Some(self_link.to_owned()))
+            } else {
+                None
+            },
+            ..fetch_result
+        }),
+        // the error for the url people subscribed to is the one they can do something about
+        Err(_) => Err(e),
+    }
+}
+
+/// the database half of a refresh, once the feed was fetched:
+/// stores its entries, see `merge_remote_feed`, and how the refresh went,
+/// see `record_fetch_status`, `record_refresh_error`, and `log_refresh`
+fn store_refresh(
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+    fetch_result: Result<FetchResult>,
+    fetch_duration: Duration,
+) -> Result<RefreshSummary> {
+    let status = record_fetch_status(conn, feed_id, &fetch_result, fetch_duration);
+    let result = fetch_result.and_then(|remote_feed| merge_remote_feed(conn, feed_id, remote_feed));
+    let result = record_refresh_error(conn, feed_id, result);
+    log_refresh(conn, feed_id, status, fetch_duration, &result);
+    result
+}
+
+/// stores the HTTP status of the fetch of a refresh, and how long the fetch took,
+/// see `Feed::last_status`, returning the status
+fn record_fetch_status(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    fetch_result: &Result<FetchResult>,
+    fetch_duration: Duration,
+) -> Option<u16> {
+    let status = match fetch_result {
+        Ok(fetch_result) => fetch_result.status,
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::HttpStatus(status)) => Some(*status),
+            _ => None,
+        },
+    };
+
+    // failing to store the status should not fail the refresh
+    let _ = conn.execute(
+        "UPDATE feeds SET last_status = ?2, last_fetch_duration_ms = ?3 WHERE id = ?1",
+        params![
+            feed_id,
+            status,
+            u64::try_from(fetch_duration.as_millis()).unwrap_or(u64::MAX)
+        ],
+    );
+
+    status
+}
+
+/// how many of each feed's refreshes `get_refresh_history` remembers
+const REFRESH_HISTORY_LEN: usize = 100;
+
+/// adds the refresh to the feed's history, see `get_refresh_history`,
+/// forgetting all but its last `REFRESH_HISTORY_LEN` refreshes
+fn log_refresh(
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+    status: Option<u16>,
+    fetch_duration: Duration,
+    result: &Result<RefreshSummary>,
+) {
+    let (inserted, updated, error) = match result {
+        Ok(refresh_summary) => (
+            refresh_summary.inserted.len(),
+            refresh_summary.updated.len(),
+            None,
+        ),
+        Err(e) => (0, 0, Some(format!("{e:#}"))),
+    };
+
+    // failing to log the refresh should not fail it either
+    let _ = in_transaction(conn, |tx| {
+        tx.execute(
+            "INSERT INTO refresh_log (
+              feed_id,
+              refreshed_at,
+              status,
+              inserted,
+              updated,
+              fetch_duration_ms,
+              error
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                feed_id,
+                Utc::now(),
+                status,
+                inserted,
+                updated,
+                u64::try_from(fetch_duration.as_millis()).unwrap_or(u64::MAX),
+                error,
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM refresh_log
+            WHERE feed_id = ?1
+            AND id NOT IN (
+              SELECT id FROM refresh_log WHERE feed_id = ?1 ORDER BY id DESC LIMIT ?2
+            )",
+            params![feed_id, REFRESH_HISTORY_LEN],
+        )?;
+
+        Ok(())
+    });
+}
+
+/// one refresh of a feed, see `get_refresh_history`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefreshLogEntry {
+    pub refreshed_at: DateTime<Utc>,
+    /// see `Feed::last_status`
+    pub status: Option<u16>,
+    /// how many entries the refresh added
+    pub inserted: usize,
+    /// how many entries the refresh updated
+    pub updated: usize,
+    pub fetch_duration: Duration,
+    /// why the refresh failed
+    pub error: Option<String>,
+}
+
+/// the feed's last `limit` refreshes, newest first, for telling why a feed is flaky.
+/// only the last `REFRESH_HISTORY_LEN` refreshes are remembered.
+/// subscribing to a feed that is already subscribed to refreshes it,
+/// see `subscribe_to_feed`, so that is in the history too,
+/// but the fetch that first subscribes to a feed is not.
+pub fn get_refresh_history(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    limit: usize,
+) -> Result<Vec<RefreshLogEntry>> {
+    let mut statement = conn.prepare(
+        "SELECT refreshed_at, status, inserted, updated, fetch_duration_ms, error
+        FROM refresh_log
+        WHERE feed_id = ?1
+        ORDER BY id DESC
+        LIMIT ?2",
+    )?;
+
+    let refresh_history = statement
+        .query_map(params![feed_id, limit], |row| {
+            Ok(RefreshLogEntry {
+                refreshed_at: row.get(0)?,
+                status: row.get(1)?,
+                inserted: row.get(2)?,
+                updated: row.get(3)?,
+                fetch_duration: Duration::from_millis(row.get(4)?),
+                error: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(refresh_history)
+}
+
+/// stores the error of a failed refresh on the feed, passing `result` through.
+/// a successful refresh clears the error in `update_feed_refreshed_at`.
+fn record_refresh_error(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    result: Result<RefreshSummary>,
+) -> Result<RefreshSummary> {
+    if let Err(e) = &result {
+        // failing to store the error should not hide it from the caller
+        let _ = conn.execute(
+            "UPDATE feeds SET last_error = ?2, last_error_at = ?3 WHERE id = ?1",
+            params![feed_id, format!("{e:#}"), Utc::now()],
+        );
+    }
+
+    result
+}
+
+/// the database half of `refresh_feed`:
+/// stores the entries of `remote_feed` that `feed_id` does not already have,
+/// and updates the entries it does have whose content has changed.
+/// if the feed's `<lastBuildDate>` and the entries it lists first are the same
+/// as last time, its entries are not compared at all.
+fn merge_remote_feed(
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+    remote_feed: FetchResult,
+) -> Result<RefreshSummary> {
+    let cache_validators = remote_feed.cache_validators;
+    let moved_to = remote_feed.moved_to;
+    let body = remote_feed.body;
+
+    let Some(mut remote_feed) = remote_feed.feed_and_entries else {
+        in_transaction(conn, |tx| {
+            update_feed_refreshed_at(tx, feed_id, &cache_validators)?;
+            if let Some(moved_to) = &moved_to {
+                update_feed_link(tx, feed_id, moved_to)?;
+            }
+            prune_entries_beyond_max(tx, feed_id)?;
+            Ok(())
+        })?;
+
+        return Ok(RefreshSummary::default());
+    };
+
+    let fingerprint = remote_feed.fingerprint();
+
+    if is_unchanged(conn, feed_id, &remote_feed, fingerprint.as_deref())? {
+        in_transaction(conn, |tx| {
+            update_feed_refreshed_at(tx, feed_id, &cache_validators)?;
+            if let Some(body) = &body {
+                update_feed_raw_body(tx, feed_id, body)?;
+            }
+            if let Some(moved_to) = &moved_to {
+                update_feed_link(tx, feed_id, moved_to)?;
+            }
+            prune_entries_beyond_max(tx, feed_id)?;
+            Ok(())
+        })?;
+
+        return Ok(RefreshSummary {
+            stats: RefreshStats {
+                fetched: remote_feed.entries.len(),
+                duplicates: remote_feed.entries.len(),
+                ..RefreshStats::default()
+            },
+            ..RefreshSummary::default()
+        });
+    }
+
+    let EntriesDiff {
+        to_add,
+        to_update,
+        content_hashes_to_backfill,
+        purged_entries_to_forget,
+        stats,
+    } = diff_remote_entries(conn, feed_id, &mut remote_feed)?;
+
+    let refresh_summary = in_transaction(conn, |tx| {
+        update_feed_title_and_link(
+            tx,
+            feed_id,
+            remote_feed.feed.title.as_deref(),
+            remote_feed.feed.link.as_deref(),
+        )?;
+        update_feed_self_link(tx, feed_id, remote_feed.feed.self_link.as_deref())?;
+        update_feed_image_url(tx, feed_id, remote_feed.feed.image_url.as_deref())?;
+        update_feed_language(tx, feed_id, remote_feed.feed.language.as_deref())?;
+        update_feed_fingerprint(tx, feed_id, fingerprint.as_deref())?;
+        update_feed_last_build_date(tx, feed_id, remote_feed.last_build_date.as_deref())?;
+        update_feed_min_refresh_interval(tx, feed_id, remote_feed.feed.min_refresh_interval)?;
+        let inserted = add_entries_to_feed(tx, feed_id, &to_add)?;
+        let updated = update_entries_content(tx, feed_id, &to_update)?;
+        backfill_content_hashes(tx, &content_hashes_to_backfill)?;
+        forget_purged_entries(tx, &purged_entries_to_forget)?;
+        update_feed_refreshed_at(tx, feed_id, &cache_validators)?;
+        if let Some(body) = &body {
+            update_feed_raw_body(tx, feed_id, body)?;
+        }
+        if let Some(moved_to) = &moved_to {
+            update_feed_link(tx, feed_id, moved_to)?;
+        }
+        prune_entries_beyond_max(tx, feed_id)?;
+        Ok(RefreshSummary {
+            stats: RefreshStats {
+                inserted: inserted.len(),
+                updated: updated.len(),
+                errors: stats.errors + to_add.len() - inserted.len(),
+                ..stats
+            },
+            inserted,
+            updated,
+        })
+    })?;
+
+    Ok(refresh_summary)
+}
+
+/// whether the feed says it has not changed, with its `<lastBuildDate>`,
+/// and lists the same entries first as it did, see `FeedAndEntries::fingerprint`,
+/// so there is no need to compare its entries.
+/// feeds that keep the same date as they add entries list different ones first.
+fn is_unchanged(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    remote_feed: &FeedAndEntries,
+    fingerprint: Option<&str>,
+) -> Result<bool> {
+    let Some(fingerprint) = fingerprint.filter(|_| remote_feed.last_build_date.is_some()) else {
+        return Ok(false);
+    };
+
+    let (last_build_date, stored_fingerprint) =
+        get_feed_last_build_date_and_fingerprint(conn, feed_id)?;
+
+    Ok(last_build_date == remote_feed.last_build_date
+        && stored_fingerprint.as_deref() == Some(fingerprint))
+}
+
+/// how the entries of a fetched feed differ from the ones the feed has,
+/// see `diff_remote_entries`
+struct EntriesDiff {
+    /// entries the feed does not have
+    to_add: Vec<Entry>,
+    /// entries the feed has, whose title, description, or content changed
+    to_update: Vec<(EntryId, Entry)>,
+    /// entries the feed has that were stored before content was hashed
+    content_hashes_to_backfill: Vec<(EntryId, String)>,
+    /// entries the feed once had and no longer lists, which it can forget it purged,
+    /// see `forget_purged_entries`
+    purged_entries_to_forget: Vec<i64>,
+    /// without the inserted and updated counts, which are up to the caller
+    stats: RefreshStats,
+}
+
+/// compares the entries of `remote_feed`, which it takes, to the entries `feed_id` has,
+/// without changing anything, for `merge_remote_feed` and `refresh_feed_dry_run`
+fn diff_remote_entries(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    remote_feed: &mut FeedAndEntries,
+) -> Result<EntriesDiff> {
+    let dedupe_strategy = get_feed_dedupe_strategy(conn, feed_id)?;
+
+    // for `DedupeStrategy::GuidThenLink`
+    let mut local_by_guid = HashMap::new();
+    // entries stored before guids were tracked
+    let mut local_by_link_without_guid = HashMap::new();
+    let mut local_by_link = HashMap::new();
+    // for the other strategies, which can have more than one entry with a key,
+    // see `DedupeStrategy::is_same_entry`
+    let mut local_by_key: HashMap<String, Vec<EntryKeys>> = HashMap::new();
+
+    for local_entry in get_entries_keys(conn, feed_id)? {
+        if let Some(key) = dedupe_strategy.key(
+            local_entry.guid.as_deref(),
+            local_entry.link.as_deref(),
+            local_entry.title.as_deref(),
+        ) {
+            local_by_key
+                .entry(key)
+                .or_default()
+                .push(local_entry.clone());
+        }
+
+        if let Some(guid) = &local_entry.guid {
+            local_by_guid.insert(guid.to_owned(), local_entry.clone());
+        }
+
+        if let Some(link) = &local_entry.link {
+            if local_entry.guid.is_none() {
+                local_by_link_without_guid.insert(link.to_owned(), local_entry.clone());
+            }
+            local_by_link.insert(link.to_owned(), local_entry.clone());
+        }
+    }
+
+    let mut remote_guids = HashSet::new();
+    let mut remote_links = HashSet::new();
+    let mut remote_keys = HashSet::new();
+
+    for item in &remote_feed.entries {
+        let link = item.link.as_deref().map(normalize_entry_link);
+        if let Some(key) =
+            dedupe_strategy.key(item.guid.as_deref(), link.as_deref(), item.title.as_deref())
+        {
+            remote_keys.insert(key);
+        }
+        remote_links.extend(link);
+        remote_guids.extend(item.guid.clone());
+    }
+
+    let mut purged_guids = HashSet::new();
+    let mut purged_links_without_guid = HashSet::new();
+    let mut purged_links = HashSet::new();
+    let mut purged_keys: HashMap<String, Vec<Option<DateTime<Utc>>>> = HashMap::new();
+    let mut purged_entries_to_forget = vec![];
+
+    for PurgedEntryKeys {
+        id,
+        guid,
+        link,
+        title,
+        published_at,
+    } in get_purged_entries_keys(conn, feed_id)?
+    {
+        let key = dedupe_strategy.key(guid.as_deref(), link.as_deref(), title.as_deref());
+
+        // the feed no longer lists the entry, so it can't add it again.
+        // a feed that lists nothing is more likely broken than empty.
+        let is_listed = guid
+            .as_ref()
+            .is_some_and(|guid| remote_guids.contains(guid))
+            || link
+                .as_ref()
+                .is_some_and(|link| remote_links.contains(link))
+            || key.as_ref().is_some_and(|key| remote_keys.contains(key));
+        if !is_listed && !remote_feed.entries.is_empty() {
+            purged_entries_to_forget.push(id);
+        }
+
+        if let Some(key) = key {
+            purged_keys.entry(key).or_default().push(published_at);
+        }
+
+        if let Some(link) = link {
+            if guid.is_none() {
+                purged_links_without_guid.insert(link.clone());
+            }
+            purged_links.insert(link);
+        }
+
+        if let Some(guid) = guid {
+            purged_guids.insert(guid);
+        }
+    }
+
+    let mut diff = EntriesDiff {
+        to_add: vec![],
+        to_update: vec![],
+        content_hashes_to_backfill: vec![],
+        purged_entries_to_forget,
+        stats: RefreshStats {
+            fetched: remote_feed.entries.len(),
+            ..RefreshStats::default()
+        },
+    };
+    // otherwise each copy of a new entry would be inserted
+    diff.stats.duplicates += remote_feed.dedupe_entries(dedupe_strategy);
+
+    for item in std::mem::take(&mut remote_feed.entries) {
+        let link = item.link.as_deref().map(normalize_entry_link);
+
+        let (local_entry, is_purged) = match (dedupe_strategy, &item.guid, &link) {
+            (DedupeStrategy::GuidThenLink, Some(guid), link) => (
+                local_by_guid.get(guid).or_else(|| {
+                    link.as_ref()
+                        .and_then(|link| local_by_link_without_guid.get(link))
+                }),
+                purged_guids.contains(guid)
+                    || link
+                        .as_ref()
+                        .is_some_and(|link| purged_links_without_guid.contains(link)),
+            ),
+            (DedupeStrategy::GuidThenLink, None, Some(link)) => {
+                (local_by_link.get(link), purged_links.contains(link))
+            }
+            // without a guid or a link there is no way to tell
+            // whether we have already seen this entry
+            (DedupeStrategy::GuidThenLink, None, None) => {
+                diff.stats.errors += 1;
+                continue;
+            }
+            (_, guid, link) => {
+                match dedupe_strategy.key(guid.as_deref(), link.as_deref(), item.title.as_deref()) {
+                    Some(key) => (
+                        local_by_key.get(&key).and_then(|local_entries| {
+                            local_entries.iter().find(|local_entry| {
+                                dedupe_strategy
+                                    .is_same_entry(item.published_at, local_entry.published_at)
+                            })
+                        }),
+                        purged_keys.get(&key).is_some_and(|purged_published_ats| {
+                            purged_published_ats.iter().any(|purged_published_at| {
+                                dedupe_strategy
+                                    .is_same_entry(item.published_at, *purged_published_at)
+                            })
+                        }),
+                    ),
+                    // likewise without what the strategy tells entries apart by
+                    None => {
+                        diff.stats.errors += 1;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        match local_entry {
+            None if is_purged => diff.stats.duplicates += 1,
+            None => diff.to_add.push(item),
+            Some(local_entry) => {
+                let remote_content_hash = content_hash(&item);
+
+                match &local_entry.content_hash {
+                    Some(local_content_hash) if *local_content_hash == remote_content_hash => {
+                        diff.stats.duplicates += 1
+                    }
+                    Some(_) => diff.to_update.push((local_entry.id, item)),
+                    // stored before content was hashed, so there is nothing to compare to
+                    None => {
+                        diff.stats.duplicates += 1;
+                        diff.content_hashes_to_backfill
+                            .push((local_entry.id, remote_content_hash))
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
+/// configures the connection (see `configure_connection`)
+/// and brings the database up to date (see `run_migrations`)
+pub fn initialize_db(conn: &mut rusqlite::Connection) -> Result<()> {
+    configure_connection(conn)?;
+    run_migrations(conn)
+}
+
+/// the settings russ expects of every connection:
+/// WAL mode, so readers do not wait for writers, and `synchronous = NORMAL`,
+/// which is safe in WAL mode and does not sync on every commit.
+/// foreign keys are enforced, and a connection waits a while for
+/// another one to finish writing before failing with "database is locked".
+/// most of these only last as long as the connection, so every connection needs them.
+pub fn configure_connection(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // in-memory databases stay in "memory" mode
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(())
+}
+
+/// a connection to the database at `database_path`,
+/// creating and initializing it if needed (see `initialize_db`).
+/// every database is separate, so opening a different file
+/// is how to keep separate sets of feeds, like work and personal ones.
+pub fn open_database(database_path: impl AsRef<std::path::Path>) -> Result<rusqlite::Connection> {
+    let mut conn = rusqlite::Connection::open(database_path)?;
+    initialize_db(&mut conn)?;
+    Ok(conn)
+}
+
+/// like `open_database`, for a database that only lives as long as the connection
+pub fn open_in_memory_database() -> Result<rusqlite::Connection> {
+    let mut conn = rusqlite::Connection::open_in_memory()?;
+    initialize_db(&mut conn)?;
+    Ok(conn)
+}
+
+/// connections to the same database for several threads at once.
+/// pooled connections deref to connections,
+/// so they go wherever a connection goes.
+pub type ConnectionPool = r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>;
+
+/// how long a connection waits for another one to finish writing
+/// before giving up with "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// a pool of connections to the database at `database_path`,
+/// creating and initializing it if needed (see `initialize_db`).
+/// every connection is configured with `configure_connection`.
+pub fn connection_pool(database_path: impl AsRef<std::path::Path>) -> Result<ConnectionPool> {
+    let manager = r2d2_sqlite::SqliteConnectionManager::file(database_path)
+        .with_init(|conn| configure_connection(conn));
+
+    let pool = r2d2::Pool::new(manager)?;
+
+    initialize_db(&mut *pool.get()?)?;
+
+    Ok(pool)
+}
+
+/// the steps that build the schema, in order.
+/// a database at schema version `n` has had the first `n` steps applied.
+/// databases created before the schema was versioned are at version 0,
+/// but have some of the later steps' changes already,
+/// so every step has to work whether or not its change is already there.
+/// only ever add steps to the end.
+const MIGRATIONS: &[fn(&rusqlite::Transaction) -> Result<()>] = &[
+    create_feeds_and_entries,
+    add_entry_guids,
+    add_feed_cache_validators,
+    add_feed_errors,
+    add_feed_categories,
+    add_entry_content_hashes,
+    add_entry_stars,
+    add_entry_published_at,
+    make_feed_links_unique,
+    initialize_search,
+    add_entry_enclosures,
+    add_purged_entries,
+    add_feed_user_titles,
+    add_feed_credentials,
+    add_feed_self_links,
+    add_entry_normalized_links,
+    add_feed_min_refresh_intervals,
+    add_entry_tags,
+    add_entry_thumbnails,
+    add_entry_feed_foreign_key,
+    add_feed_refresh_intervals,
+    add_feed_fetch_statuses,
+    add_entry_author_details,
+    add_feed_max_entries,
+    add_feed_raw_bodies,
+    add_feed_fingerprints,
+    add_feed_last_build_dates,
+    add_max_content_bytes,
+    add_dedupe_strategies,
+    add_refresh_log,
+    add_feed_accepts,
+    add_entry_full_content,
+    add_feed_sort_positions,
+    add_feed_image_urls,
+    add_languages,
+];
+
+/// the schema version `run_migrations` brings databases up to
+pub const LATEST_SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+/// brings the database up to `LATEST_SCHEMA_VERSION`,
+/// applying the migrations it is missing in a single transaction.
+/// fails without changing anything if the database is from a newer version of russ.
+pub fn run_migrations(conn: &mut rusqlite::Connection) -> Result<()> {
+    in_transaction(conn, |tx| {
+        let schema_version = schema_version(tx)?;
+
+        if schema_version > LATEST_SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "the database is at schema version {schema_version}, \
+                but this version of russ only knows up to {LATEST_SCHEMA_VERSION}"
+            ));
+        }
+
+        for migration in &MIGRATIONS[schema_version..] {
+            migration(tx)?;
+        }
+
+        tx.pragma_update(None, "user_version", LATEST_SCHEMA_VERSION)?;
+
+        Ok(())
+    })
+}
+
+/// the number of migrations that have been applied to the database,
+/// stored in SQLite's `user_version`
+pub fn schema_version(conn: &rusqlite::Connection) -> Result<usize> {
+    let schema_version =
+        conn.pragma_query_value(None, "user_version", |row| row.get::<_, usize>(0))?;
+    Ok(schema_version)
+}
+
+/// parses the `Entry::pub_date` of entries without a `published_at` again,
+/// returning the number of entries that now have one.
+/// for databases from before `published_at`, or with dates that russ
+/// could not parse when they were stored but can now.
+/// dates that still do not parse are left alone.
+pub fn backfill_published_at(conn: &mut rusqlite::Connection) -> Result<usize> {
+    in_transaction(conn, |tx| {
+        let pub_dates = tx
+            .prepare(
+                "SELECT id, pub_date FROM entries
+                WHERE published_at IS NULL AND pub_date IS NOT NULL",
+            )?
+            .query_map([], |row| {
+                Ok((row.get::<_, EntryId>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut statement =
+            tx.prepare_cached("UPDATE entries SET published_at = ?2 WHERE id = ?1")?;
+        let mut backfilled = 0;
+
+        for (entry_id, pub_date) in pub_dates {
+            if let Some(published_at) = parse_datetime(&pub_date) {
+                backfilled += statement.execute(params![entry_id, published_at])?;
+            }
+        }
+
+        Ok(backfilled)
+    })
+}
+
+fn create_feeds_and_entries(tx: &rusqlite::Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS feeds (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT,
+        feed_link TEXT,
+        link TEXT,
+        feed_kind TEXT,
+        refreshed_at TIMESTAMP,
+        inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        feed_id INTEGER,
+        title TEXT,
+        author TEXT,
+        pub_date TIMESTAMP,
+        description TEXT,
+        content TEXT,
+        link TEXT,
+        read_at TIMESTAMP,
+        inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // tables from before some of these columns were added are left as they are
+    // by `CREATE TABLE IF NOT EXISTS`, so the columns have to be added to them
+    for (column, definition) in [
+        ("title", "TEXT"),
+        ("feed_link", "TEXT"),
+        ("link", "TEXT"),
+        ("feed_kind", "TEXT"),
+        ("refreshed_at", "TIMESTAMP"),
+    ] {
+        add_column_if_not_exists(tx, "feeds", column, definition)?;
+    }
+    add_timestamps_if_not_exist(tx, "feeds")?;
+
+    for (column, definition) in [
+        ("feed_id", "INTEGER"),
+        ("title", "TEXT"),
+        ("author", "TEXT"),
+        ("pub_date", "TIMESTAMP"),
+        ("description", "TEXT"),
+        ("content", "TEXT"),
+        ("link", "TEXT"),
+        ("read_at", "TIMESTAMP"),
+    ] {
+        add_column_if_not_exists(tx, "entries", column, definition)?;
+    }
+    add_timestamps_if_not_exist(tx, "entries")?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entries_feed_id_and_pub_date_and_inserted_at_index
+        ON entries (feed_id, pub_date, inserted_at)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// like `add_column_if_not_exists` for `inserted_at` and `updated_at`,
+/// which default to `CURRENT_TIMESTAMP`, a default `ALTER TABLE` can't add.
+/// the rows the table already has get the current time instead,
+/// and a trigger gives rows inserted without them the time they were inserted.
+fn add_timestamps_if_not_exist(tx: &rusqlite::Transaction, table: &str) -> Result<()> {
+    for column in ["inserted_at", "updated_at"] {
+        if add_column_if_not_exists(tx, table, column, "TIMESTAMP")? {
+            tx.execute(
+                &format!("UPDATE {table} SET {column} = CURRENT_TIMESTAMP"),
+                [],
+            )?;
+
+            tx.execute(
+                &format!(
+                    "CREATE TRIGGER IF NOT EXISTS {table}_{column}_after_insert
+                    AFTER INSERT ON {table}
+                    WHEN new.{column} IS NULL
+                    BEGIN
+                      UPDATE {table} SET {column} = CURRENT_TIMESTAMP WHERE rowid = new.rowid;
+                    END"
+                ),
+                [],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_entry_guids(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "guid", "TEXT")?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entries_feed_id_and_guid_index
+        ON entries (feed_id, guid)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn add_feed_cache_validators(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "etag", "TEXT")?;
+    add_column_if_not_exists(tx, "feeds", "last_modified", "TEXT")?;
+    Ok(())
+}
+
+fn add_feed_errors(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "last_error", "TEXT")?;
+    add_column_if_not_exists(tx, "feeds", "last_error_at", "TIMESTAMP")?;
+    Ok(())
+}
+
+fn add_feed_categories(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "category", "TEXT")?;
+    Ok(())
+}
+
+fn add_entry_content_hashes(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "content_hash", "TEXT")?;
+    Ok(())
+}
+
+fn add_entry_stars(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "starred", "BOOLEAN NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+fn add_entry_published_at(tx: &rusqlite::Transaction) -> Result<()> {
+    if add_column_if_not_exists(tx, "entries", "published_at", "TIMESTAMP")? {
+        // `pub_date` used to only store dates that parsed
+        tx.execute("UPDATE entries SET published_at = pub_date", [])?;
+    }
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entries_feed_id_and_published_at_and_inserted_at_index
+        ON entries (feed_id, published_at, inserted_at)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn make_feed_links_unique(tx: &rusqlite::Transaction) -> Result<()> {
+    let feed_link_index_exists: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master
+        WHERE type = 'index' AND name = 'feeds_feed_link_index'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    if !feed_link_index_exists {
+        deduplicate_feeds(tx)?;
+
+        tx.execute(
+            "CREATE UNIQUE INDEX feeds_feed_link_index
+        ON feeds (feed_link)",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn add_entry_enclosures(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "enclosure_url", "TEXT")?;
+    add_column_if_not_exists(tx, "entries", "enclosure_type", "TEXT")?;
+    add_column_if_not_exists(tx, "entries", "enclosure_length", "INTEGER")?;
+    Ok(())
+}
+
+/// see `rename_feed`
+fn add_feed_user_titles(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "user_title", "TEXT")?;
+    Ok(())
+}
+
+/// see `subscribe_to_protected_feed`
+fn add_feed_credentials(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "username", "TEXT")?;
+    add_column_if_not_exists(tx, "feeds", "password", "TEXT")?;
+    Ok(())
+}
+
+/// see `Feed::self_link`
+fn add_feed_self_links(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "self_link", "TEXT")?;
+    Ok(())
+}
+
+/// deleting a feed deletes its entries, see `unsubscribe_feed`.
+/// SQLite can't add a foreign key to a table, so this makes the table again with one.
+fn add_entry_feed_foreign_key(tx: &rusqlite::Transaction) -> Result<()> {
+    let has_foreign_key: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_foreign_key_list('entries')",
+        [],
+        |row| row.get(0),
+    )?;
+
+    if has_foreign_key {
+        return Ok(());
+    }
+
+    // indexes and triggers go with the table, so they are made again from their definitions
+    let definitions = tx
+        .prepare(
+            "SELECT sql FROM sqlite_master
+            WHERE tbl_name = 'entries' AND type IN ('index', 'trigger') AND sql IS NOT NULL",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // so ids of deleted entries are not used again
+    let last_entry_id: Option<i64> = tx
+        .query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = 'entries'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    // the entries of feeds that were deleted before this would fail the foreign key
+    tx.execute(
+        "DELETE FROM entries WHERE feed_id IS NULL OR feed_id NOT IN (SELECT id FROM feeds)",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE entries_with_feed_foreign_key (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        feed_id INTEGER REFERENCES feeds (id) ON DELETE CASCADE,
+        title TEXT,
+        author TEXT,
+        pub_date TIMESTAMP,
+        description TEXT,
+        content TEXT,
+        link TEXT,
+        read_at TIMESTAMP,
+        inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        guid TEXT,
+        content_hash TEXT,
+        starred BOOLEAN NOT NULL DEFAULT 0,
+        published_at TIMESTAMP,
+        enclosure_url TEXT,
+        enclosure_type TEXT,
+        enclosure_length INTEGER,
+        thumbnail_url TEXT,
+        normalized_link TEXT
+        )",
+        [],
+    )?;
+
+    const COLUMNS: &str = "
+        id,
+        feed_id,
+        title,
+        author,
+        pub_date,
+        description,
+        content,
+        link,
+        read_at,
+        inserted_at,
+        updated_at,
+        guid,
+        content_hash,
+        starred,
+        published_at,
+        enclosure_url,
+        enclosure_type,
+        enclosure_length,
+        thumbnail_url,
+        normalized_link";
+
+    tx.execute(
+        &format!(
+            "INSERT INTO entries_with_feed_foreign_key ({COLUMNS}) SELECT {COLUMNS} FROM entries"
+        ),
+        [],
+    )?;
+
+    tx.execute("DROP TABLE entries", [])?;
+    tx.execute(
+        "ALTER TABLE entries_with_feed_foreign_key RENAME TO entries",
+        [],
+    )?;
+
+    for definition in definitions {
+        tx.execute(&definition, [])?;
+    }
+
+    if let Some(last_entry_id) = last_entry_id {
+        tx.execute(
+            "UPDATE sqlite_sequence SET seq = MAX(seq, ?1) WHERE name = 'entries'",
+            [last_entry_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// see `Entry::thumbnail_url`
+fn add_entry_thumbnails(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "thumbnail_url", "TEXT")?;
+    Ok(())
+}
+
+/// see `get_entries_by_tag`
+fn add_entry_tags(tx: &rusqlite::Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS entry_tags (
+        entry_id INTEGER,
+        tag TEXT
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entry_tags_entry_id_index
+        ON entry_tags (entry_id)",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entry_tags_tag_index
+        ON entry_tags (tag COLLATE NOCASE)",
+        [],
+    )?;
+
+    // entries are deleted in more than one place
+    tx.execute(
+        "CREATE TRIGGER IF NOT EXISTS entry_tags_after_entry_delete
+        AFTER DELETE ON entries
+        BEGIN
+          DELETE FROM entry_tags WHERE entry_id = old.id;
+        END",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// see `get_feeds_by_language`
+fn add_languages(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "language", "TEXT")?;
+    add_column_if_not_exists(tx, "entries", "language", "TEXT")?;
+    Ok(())
+}
+
+/// see `Feed::image_url`
+fn add_feed_image_urls(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "image_url", "TEXT")?;
+    Ok(())
+}
+
+/// see `set_feed_position`
+fn add_feed_sort_positions(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "sort_position", "INTEGER")?;
+    Ok(())
+}
+
+/// see `fetch_full_content`
+fn add_entry_full_content(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "full_content", "TEXT")?;
+    add_column_if_not_exists(tx, "entries", "full_content_error", "TEXT")?;
+    Ok(())
+}
+
+/// see `set_feed_accept`
+fn add_feed_accepts(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "accept", "TEXT")?;
+    Ok(())
+}
+
+/// see `get_refresh_history`
+fn add_refresh_log(tx: &rusqlite::Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS refresh_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        feed_id INTEGER NOT NULL REFERENCES feeds (id) ON DELETE CASCADE,
+        refreshed_at TIMESTAMP NOT NULL,
+        status INTEGER,
+        inserted INTEGER NOT NULL,
+        updated INTEGER NOT NULL,
+        fetch_duration_ms INTEGER NOT NULL,
+        error TEXT
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS refresh_log_feed_id_index
+        ON refresh_log (feed_id, id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// see `set_feed_dedupe_strategy`
+fn add_dedupe_strategies(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(
+        tx,
+        "feeds",
+        "dedupe_strategy",
+        "TEXT NOT NULL DEFAULT 'GuidThenLink'",
+    )?;
+    add_column_if_not_exists(tx, "purged_entries", "title", "TEXT")?;
+    add_column_if_not_exists(tx, "purged_entries", "published_at", "TIMESTAMP")?;
+    Ok(())
+}
+
+/// see `set_feed_max_content_bytes`
+fn add_max_content_bytes(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "max_content_bytes", "INTEGER")?;
+    add_column_if_not_exists(tx, "entries", "truncated", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+/// see `merge_remote_feed`
+fn add_feed_last_build_dates(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "last_build_date", "TEXT")?;
+    Ok(())
+}
+
+/// see `find_feed_by_fingerprint`
+fn add_feed_fingerprints(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "fingerprint", "TEXT")?;
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS feeds_fingerprint_index ON feeds (fingerprint)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// see `set_feed_keeps_raw_body`
+fn add_feed_raw_bodies(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "keep_raw_body", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_not_exists(tx, "feeds", "last_raw_body", "TEXT")?;
+    Ok(())
+}
+
+/// see `set_feed_max_entries`
+fn add_feed_max_entries(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "max_entries", "INTEGER")?;
+    Ok(())
+}
+
+/// see `Entry::author_details`
+fn add_entry_author_details(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "author_email", "TEXT")?;
+    add_column_if_not_exists(tx, "entries", "author_uri", "TEXT")?;
+    Ok(())
+}
+
+/// see `Feed::last_status`
+fn add_feed_fetch_statuses(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "last_status", "INTEGER")?;
+    add_column_if_not_exists(tx, "feeds", "last_fetch_duration_ms", "INTEGER")?;
+    Ok(())
+}
+
+/// see `set_feed_refresh_interval`
+fn add_feed_refresh_intervals(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "refresh_interval_seconds", "INTEGER")?;
+    Ok(())
+}
+
+/// see `feeds_due_for_refresh`
+fn add_feed_min_refresh_intervals(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "feeds", "min_refresh_interval_seconds", "INTEGER")?;
+    Ok(())
+}
+
+/// see `normalize_entry_link`
+fn add_entry_normalized_links(tx: &rusqlite::Transaction) -> Result<()> {
+    add_column_if_not_exists(tx, "entries", "normalized_link", "TEXT")?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS entries_feed_id_and_normalized_link_index
+        ON entries (feed_id, normalized_link)",
+        [],
+    )?;
+
+    let links = tx
+        .prepare(
+            "SELECT id, link FROM entries
+            WHERE link IS NOT NULL AND normalized_link IS NULL",
+        )?
+        .query_map([], |row| {
+            Ok((row.get::<_, EntryId>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut statement = tx.prepare("UPDATE entries SET normalized_link = ?2 WHERE id = ?1")?;
+    for (entry_id, link) in links {
+        statement.execute(params![entry_id, normalize_entry_link(&link)])?;
+    }
+
+    Ok(())
+}
+
+/// see `purge_read_entries_older_than`
+fn add_purged_entries(tx: &rusqlite::Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS purged_entries (
+        feed_id INTEGER,
+        guid TEXT,
+        link TEXT
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS purged_entries_feed_id_index
+        ON purged_entries (feed_id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// older versions of russ did not normalize feed urls,
+/// and let the same feed be subscribed to more than once.
+/// normalizes every feed url, and merges feeds with the same url into the oldest one.
+/// only the starred entries of the newer feeds are kept,
+/// as the rest are duplicates of the oldest feed's entries.
+fn deduplicate_feeds(tx: &rusqlite::Transaction) -> Result<()> {
+    let mut feed_ids_by_feed_link: HashMap<String, FeedId> = HashMap::new();
+
+    let feeds = {
+        let mut statement =
+            tx.prepare("SELECT id, feed_link FROM feeds WHERE feed_link IS NOT NULL ORDER BY id")?;
+        let feeds = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, FeedId>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        feeds
+    };
+
+    for (feed_id, feed_link) in feeds {
+        let feed_link = normalize_feed_link(&feed_link);
+
+        match feed_ids_by_feed_link.get(&feed_link) {
+            Some(oldest_feed_id) => {
+                tx.execute(
+                    "UPDATE entries SET feed_id = ?2 WHERE feed_id = ?1 AND starred = 1",
+                    [feed_id, *oldest_feed_id],
+                )?;
+                tx.execute("DELETE FROM entries WHERE feed_id = ?1", [feed_id])?;
+                tx.execute("DELETE FROM feeds WHERE id = ?1", [feed_id])?;
+            }
+            None => {
+                tx.execute(
+                    "UPDATE feeds SET feed_link = ?2 WHERE id = ?1",
+                    params![feed_id, feed_link],
+                )?;
+                feed_ids_by_feed_link.insert(feed_link, feed_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// creates the full-text index of entry titles, descriptions, and content
+/// that `search_entries` uses.
+/// the index does not store a copy of the entries, it points into `entries`,
+/// and triggers keep it up to date as entries are inserted, updated, and deleted.
+fn initialize_search(tx: &rusqlite::Transaction) -> Result<()> {
+    let exists: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'entries_search'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        tx.execute(
+            "CREATE VIRTUAL TABLE entries_search USING fts5 (
+        title,
+        description,
+        content,
+        content = 'entries',
+        content_rowid = 'id'
+        )",
+            [],
+        )?;
+
+        // index the entries stored before there was an index
+        tx.execute(
+            "INSERT INTO entries_search (entries_search) VALUES ('rebuild')",
+            [],
+        )?;
+    }
+
+    tx.execute(
+        "CREATE TRIGGER IF NOT EXISTS entries_search_after_insert
+        AFTER INSERT ON entries
+        BEGIN
+          INSERT INTO entries_search (rowid, title, description, content)
+          VALUES (new.id, new.title, new.description, new.content);
+        END",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TRIGGER IF NOT EXISTS entries_search_after_delete
+        AFTER DELETE ON entries
+        BEGIN
+          INSERT INTO entries_search (entries_search, rowid, title, description, content)
+          VALUES ('delete', old.id, old.title, old.description, old.content);
+        END",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TRIGGER IF NOT EXISTS entries_search_after_update
+        AFTER UPDATE OF title, description, content ON entries
+        BEGIN
+          INSERT INTO entries_search (entries_search, rowid, title, description, content)
+          VALUES ('delete', old.id, old.title, old.description, old.content);
+          INSERT INTO entries_search (rowid, title, description, content)
+          VALUES (new.id, new.title, new.description, new.content);
+        END",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// `CREATE TABLE IF NOT EXISTS` does nothing for databases created
+/// by an older version of russ, so columns added since then
+/// have to be added explicitly.
+/// returns whether the column was added.
+fn add_column_if_not_exists(
+    tx: &rusqlite::Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool> {
+    let exists: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        tx.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+            [],
+        )?;
+    }
+
+    Ok(!exists)
+}
+
+/// feed urls are stored and compared normalized,
+/// so the same feed can't be subscribed to twice under slightly different urls:
+/// surrounding whitespace, the case of the host, default ports,
+/// and trailing slashes are not significant.
+/// something that does not parse as a url is only trimmed.
+pub fn normalize_feed_link(feed_link: &str) -> String {
+    let feed_link = feed_link.trim();
+
+    match url::Url::parse(feed_link) {
+        Ok(mut url) => {
+            // parsing already lowercased the host and dropped a default port
+            let path = url.path().trim_end_matches('/').to_owned();
+            // the root path is always serialized as "/"
+            if !path.is_empty() {
+                url.set_path(&path);
+            }
+            url.to_string()
+        }
+        Err(_) => feed_link.to_owned(),
+    }
+}
+
+/// query parameters that only say where a link was clicked,
+/// which `normalize_entry_link` removes.
+/// a name ending in `*` matches every parameter starting with the rest of it.
+pub const TRACKING_QUERY_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "_hsenc", "_hsmi",
+    "igshid",
+];
+
+/// entry links are compared normalized when refreshing,
+/// so an entry whose link gains tracking parameters is not added again:
+/// parameters in `TRACKING_QUERY_PARAMS` are removed,
+/// the other parameters are sorted by name, and the fragment is dropped.
+/// something that does not parse as a url is only trimmed.
+pub fn normalize_entry_link(link: &str) -> String {
+    let link = link.trim();
+
+    let Ok(mut url) = url::Url::parse(link) else {
+        return link.to_owned();
+    };
+
+    let is_tracking = |name: &str| {
+        TRACKING_QUERY_PARAMS
+            .iter()
+            .any(|tracking| match tracking.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == *tracking,
+            })
+    };
+
+    let mut query_pairs = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    // stable, so repeated parameters keep their order
+    query_pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if query_pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query_pairs);
+    }
+
+    url.set_fragment(None);
+
+    url.to_string()
+}
+
+fn create_feed(tx: &rusqlite::Transaction, feed: &Feed) -> Result<FeedId> {
+    let feed_link = feed.feed_link.as_deref().map(normalize_feed_link);
+
+    let feed_id = tx.query_row::<FeedId, _, _>(
+        "INSERT INTO feeds (
+          title,
+          link,
+          feed_link,
+          self_link,
+          min_refresh_interval_seconds,
+          feed_kind,
+          image_url,
+          language
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        RETURNING id",
+        params![
+            feed.title.as_deref().and_then(clean_text),
+            feed.link,
+            feed_link,
+            feed.self_link,
+            feed.min_refresh_interval.map(|interval| interval.as_secs()),
+            feed.feed_kind,
+            feed.image_url,
+            feed.language
+        ],
+        |r| r.get(0),
+    )?;
+
+    Ok(feed_id)
+}
+
+/// deletes the feed and all of its entries,
+/// returning the number of entries deleted.
+/// unsubscribing from a feed that does not exist does nothing.
+/// the entries go with the feed by their foreign key,
+/// which needs foreign keys to be on, see `configure_connection`.
+pub fn unsubscribe_feed(conn: &mut rusqlite::Connection, feed_id: FeedId) -> Result<usize> {
+    in_transaction(conn, |tx| {
+        let entries_count: usize = tx.query_row(
+            "SELECT COUNT(*) FROM entries WHERE feed_id = ?1",
+            [feed_id],
+            |row| row.get(0),
+        )?;
+        tx.execute("DELETE FROM feeds WHERE id = ?1", [feed_id])?;
+        tx.execute("DELETE FROM purged_entries WHERE feed_id = ?1", [feed_id])?;
+        Ok(entries_count)
+    })
+}
+
+/// inserts `entries` for `feed_id` using a single prepared statement,
+/// returning the new entry ids in the same order as `entries`.
+/// each entry is inserted in its own savepoint, so an entry the database
+/// rejects, see `is_entry_error`, is skipped, without its id,
+/// rather than failing the others. callers count those as `RefreshStats::errors`.
+/// any other error fails the whole insert.
+fn add_entries_to_feed(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    entries: &[Entry],
+) -> Result<Vec<EntryId>> {
+    let mut entry_ids = Vec::with_capacity(entries.len());
+
+    if !entries.is_empty() {
+        let now = Utc::now();
+
+        let mut statement = tx.prepare_cached(
+            "INSERT INTO entries (
+              feed_id,
+              title,
+              author,
+              pub_date,
+              published_at,
+              description,
+              content,
+              link,
+              guid,
+              content_hash,
+              enclosure_url,
+              enclosure_type,
+              enclosure_length,
+              updated_at,
+              normalized_link,
+              thumbnail_url,
+              author_email,
+              author_uri,
+              truncated,
+              language
+            )
+            VALUES (
+              ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19,
+              ?20
+            )
+            RETURNING id",
+        )?;
+
+        let mut tag_statement =
+            tx.prepare_cached("INSERT INTO entry_tags (entry_id, tag) VALUES (?1, ?2)")?;
+
+        let max_content_bytes = get_feed_max_content_bytes(tx, feed_id)?;
+
+        let mut add_entry = |entry: &Entry| -> rusqlite::Result<EntryId> {
+            let (description, description_truncated) =
+                truncate_content(entry.description.as_deref(), max_content_bytes);
+            let (content, content_truncated) =
+                truncate_content(entry.content.as_deref(), max_content_bytes);
+
+            let entry_id = statement.query_row(
+                params![
+                    feed_id,
+                    entry.title.as_deref().and_then(clean_text),
+                    entry.author.as_deref().and_then(clean_text),
+                    entry.pub_date,
+                    entry.published_at,
+                    description,
+                    content,
+                    entry.link,
+                    entry.guid,
+                    content_hash(entry),
+                    entry.enclosure.as_ref().map(|enclosure| &enclosure.url),
+                    entry
+                        .enclosure
+                        .as_ref()
+                        .and_then(|enclosure| enclosure.mime_type.as_ref()),
+                    entry
+                        .enclosure
+                        .as_ref()
+                        .and_then(|enclosure| enclosure.length),
+                    now,
+                    entry.link.as_deref().map(normalize_entry_link),
+                    entry.thumbnail_url,
+                    entry.author_email,
+                    entry.author_uri,
+                    description_truncated || content_truncated,
+                    entry.language,
+                ],
+                |row| row.get(0),
+            )?;
+
+            for tag in &entry.tags {
+                tag_statement.execute(params![entry_id, tag])?;
+            }
+
+            Ok(entry_id)
+        };
+
+        for entry in entries {
+            tx.execute_batch("SAVEPOINT add_entry")?;
+
+            match add_entry(entry) {
+                Ok(entry_id) => {
+                    tx.execute_batch("RELEASE add_entry")?;
+                    entry_ids.push(entry_id);
+                }
+                Err(e) if is_entry_error(&e) => {
+                    tx.execute_batch("ROLLBACK TO add_entry; RELEASE add_entry")?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    Ok(entry_ids)
+}
+
+/// whether the database rejected an entry for something about the entry,
+/// like a constraint it breaks or a value it can't store,
+/// rather than for something wrong with the database, like a full disk
+fn is_entry_error(e: &rusqlite::Error) -> bool {
+    match e {
+        rusqlite::Error::SqliteFailure(e, _) => e.code == rusqlite::ErrorCode::ConstraintViolation,
+        rusqlite::Error::ToSqlConversionFailure(_) => true,
+        _ => false,
+    }
+}
+
+/// replaces the title, description, and content of existing entries of the feed,
+/// returning their ids in the same order as `entries`
+fn update_entries_content(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    entries: &[(EntryId, Entry)],
+) -> Result<Vec<EntryId>> {
+    let mut entry_ids = Vec::with_capacity(entries.len());
+
+    if !entries.is_empty() {
+        let now = Utc::now();
+
+        let mut statement = tx.prepare_cached(
+            "UPDATE entries SET
+              title = ?2,
+              description = ?3,
+              content = ?4,
+              content_hash = ?5,
+              updated_at = ?6,
+              truncated = ?7
+            WHERE id = ?1",
+        )?;
+
+        let max_content_bytes = get_feed_max_content_bytes(tx, feed_id)?;
+
+        for (entry_id, entry) in entries {
+            let (description, description_truncated) =
+                truncate_content(entry.description.as_deref(), max_content_bytes);
+            let (content, content_truncated) =
+                truncate_content(entry.content.as_deref(), max_content_bytes);
+
+            statement.execute(params![
+                entry_id,
+                entry.title.as_deref().and_then(clean_text),
+                description,
+                content,
+                // of what the feed has, so refreshes compare it to the whole thing
+                content_hash(entry),
+                now,
+                description_truncated || content_truncated,
+            ])?;
+
+            entry_ids.push(*entry_id);
+        }
+    }
+
+    Ok(entry_ids)
+}
+
+/// follows what was cut, so it is clear there is more at the entry's link
+const TRUNCATION_MARKER: &str = " […]";
+
+/// `text` cut short on a character boundary and followed by `TRUNCATION_MARKER`,
+/// so it is at most `max_bytes` bytes with the marker, and whether it was cut.
+/// the marker is left out when it doesn't fit in `max_bytes` on its own.
+fn truncate_content(text: Option<&str>, max_bytes: Option<usize>) -> (Option<Cow<'_, str>>, bool) {
+    match (text, max_bytes) {
+        (Some(text), Some(max_bytes)) if text.len() > max_bytes => {
+            let marker = if TRUNCATION_MARKER.len() <= max_bytes {
+                TRUNCATION_MARKER
+            } else {
+                ""
+            };
+            let mut end = max_bytes - marker.len();
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            (Some(format!("{}{marker}", &text[..end]).into()), true)
+        }
+        (text, _) => (text.map(Cow::from), false),
+    }
+}
+
+/// stores the hash of entries that were stored before content was hashed.
+/// this is not an edit, so `updated_at` stays the same.
+fn backfill_content_hashes(
+    tx: &rusqlite::Transaction,
+    content_hashes: &[(EntryId, String)],
+) -> Result<()> {
+    let mut statement = tx.prepare_cached("UPDATE entries SET content_hash = ?2 WHERE id = ?1")?;
+
+    for (entry_id, content_hash) in content_hashes {
+        statement.execute(params![entry_id, content_hash])?;
+    }
+
+    Ok(())
+}
+
+/// deletes what the feed remembers of entries it purged,
+/// see `purge_read_entries_older_than` and `prune_entries_beyond_max`,
+/// so they don't pile up as the feed moves on
+fn forget_purged_entries(tx: &rusqlite::Transaction, purged_entry_ids: &[i64]) -> Result<()> {
+    let mut statement = tx.prepare_cached("DELETE FROM purged_entries WHERE rowid = ?1")?;
+
+    for purged_entry_id in purged_entry_ids {
+        statement.execute([purged_entry_id])?;
+    }
+
+    Ok(())
+}
+
+/// the columns `feed_from_row` reads, in order
+const FEED_COLUMNS: &str = "
+          id,
+          COALESCE(user_title, title),
+          feed_link,
+          link,
+          feed_kind,
+          refreshed_at,
+          last_error,
+          last_error_at,
+          inserted_at,
+          updated_at,
+          category,
+          self_link,
+          min_refresh_interval_seconds,
+          refresh_interval_seconds,
+          last_status,
+          last_fetch_duration_ms,
+          max_entries,
+          keep_raw_body,
+          max_content_bytes,
+          dedupe_strategy,
+          accept,
+          image_url,
+          language";
+
+fn feed_from_row(row: &rusqlite::Row) -> rusqlite::Result<Feed> {
+    Ok(Feed {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        feed_link: row.get(2)?,
+        link: row.get(3)?,
+        self_link: row.get(11)?,
+        min_refresh_interval: row.get::<_, Option<u64>>(12)?.map(Duration::from_secs),
+        refresh_interval: row.get::<_, Option<u64>>(13)?.map(Duration::from_secs),
+        last_status: row.get(14)?,
+        last_fetch_duration: row.get::<_, Option<u64>>(15)?.map(Duration::from_millis),
+        max_entries: row.get(16)?,
+        keeps_raw_body: row.get(17)?,
+        max_content_bytes: row.get(18)?,
+        dedupe_strategy: row.get(19)?,
+        accept: row.get(20)?,
+        image_url: row.get(21)?,
+        language: row.get(22)?,
+        feed_kind: row.get(4)?,
+        category: row.get(10)?,
+        refreshed_at: row.get(5)?,
+        last_error: row.get(6)?,
+        last_error_at: row.get(7)?,
+        inserted_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+pub fn get_feed(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<Feed> {
+    let feed = conn
+        .query_row(
+            &format!("SELECT {FEED_COLUMNS} FROM feeds WHERE id = ?1"),
+            [feed_id],
+            feed_from_row,
+        )
+        .optional()?
+        .ok_or(Error::FeedNotFound(feed_id))?;
+
+    Ok(feed)
+}
+
+/// gives the feed a title of the user's choosing,
+/// which refreshing the feed does not change.
+/// an empty title goes back to the title the feed has for itself.
+pub fn rename_feed(conn: &rusqlite::Connection, feed_id: FeedId, new_title: &str) -> Result<()> {
+    let new_title = Some(new_title.trim()).filter(|new_title| !new_title.is_empty());
+
+    conn.execute(
+        "UPDATE feeds SET user_title = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, new_title, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+/// keeps the title and website link the feed has for itself up to date,
+/// bumping `updated_at` if either changed.
+/// a feed that stops having either keeps the one it had.
+/// this is not the title people see if they renamed the feed, see `rename_feed`.
+fn update_feed_title_and_link(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    title: Option<&str>,
+    link: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET
+          title = COALESCE(?2, title),
+          link = COALESCE(?3, link),
+          updated_at = ?4
+        WHERE id = ?1
+        AND (title IS NOT COALESCE(?2, title) OR link IS NOT COALESCE(?3, link))",
+        params![feed_id, title.and_then(clean_text), link, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_self_link(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    self_link: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET self_link = ?2 WHERE id = ?1 AND self_link IS NOT ?2",
+        params![feed_id, self_link],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_image_url(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    image_url: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET image_url = ?2 WHERE id = ?1 AND image_url IS NOT ?2",
+        params![feed_id, image_url],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_language(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    language: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET language = ?2 WHERE id = ?1 AND language IS NOT ?2",
+        params![feed_id, language],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_min_refresh_interval(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    min_refresh_interval: Option<Duration>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET min_refresh_interval_seconds = ?2 WHERE id = ?1",
+        params![
+            feed_id,
+            min_refresh_interval.map(|interval| interval.as_secs())
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_refreshed_at(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    cache_validators: &CacheValidators,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET
+          refreshed_at = ?2,
+          etag = ?3,
+          last_modified = ?4,
+          last_error = NULL,
+          last_error_at = NULL
+        WHERE id = ?1",
+        params![
+            feed_id,
+            Utc::now(),
+            cache_validators.etag,
+            cache_validators.last_modified
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn get_feed_last_build_date_and_fingerprint(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<(Option<String>, Option<String>)> {
+    let last_build_date_and_fingerprint = conn.query_row(
+        "SELECT last_build_date, fingerprint FROM feeds WHERE id = ?1",
+        [feed_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    Ok(last_build_date_and_fingerprint)
+}
+
+fn update_feed_last_build_date(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    last_build_date: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET last_build_date = ?2 WHERE id = ?1",
+        params![feed_id, last_build_date],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_fingerprint(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    fingerprint: Option<&str>,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET fingerprint = ?2 WHERE id = ?1 AND fingerprint IS NOT ?2",
+        params![feed_id, fingerprint],
+    )?;
+
+    Ok(())
+}
+
+/// only for feeds that keep it, see `set_feed_keeps_raw_body`
+fn update_feed_raw_body(tx: &rusqlite::Transaction, feed_id: FeedId, body: &str) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET last_raw_body = ?2 WHERE id = ?1 AND keep_raw_body",
+        params![feed_id, body],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_link(tx: &rusqlite::Transaction, feed_id: FeedId, feed_link: &str) -> Result<()> {
+    // if another feed already has this url, keep the one we have,
+    // rather than fail the refresh on the unique index
+    tx.execute(
+        "UPDATE OR IGNORE feeds SET feed_link = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, normalize_feed_link(feed_link), Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+fn update_feed_cache_validators(
+    tx: &rusqlite::Transaction,
+    feed_id: FeedId,
+    cache_validators: &CacheValidators,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE feeds SET etag = ?2, last_modified = ?3 WHERE id = ?1",
+        params![
+            feed_id,
+            cache_validators.etag,
+            cache_validators.last_modified
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn get_feed_url(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<String> {
+    let s: String = conn
+        .query_row(
+            "SELECT feed_link FROM feeds WHERE id=?1",
+            [feed_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(Error::FeedNotFound(feed_id))?;
+
+    Ok(s)
+}
+
+/// what refreshing a feed needs to fetch it
+struct FeedRequest {
+    url: String,
+    /// see `Feed::self_link`
+    self_link: Option<String>,
+    cache_validators: CacheValidators,
+    credentials: Option<Credentials>,
+    /// see `set_feed_accept`
+    accept: Option<String>,
+}
+
+fn get_feed_request(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<FeedRequest> {
+    let feed_request = conn
+        .query_row(
+            "SELECT feed_link, etag, last_modified, username, password, self_link, accept
+        FROM feeds
+        WHERE id=?1",
+            [feed_id],
+            |row| {
+                let username: Option<String> = row.get(3)?;
+                let password: Option<String> = row.get(4)?;
+
+                Ok(FeedRequest {
+                    url: row.get(0)?,
+                    self_link: row.get(5)?,
+                    cache_validators: CacheValidators {
+                        etag: row.get(1)?,
+                        last_modified: row.get(2)?,
+                    },
+                    credentials: username.map(|username| Credentials {
+                        username,
+                        password: password.unwrap_or_default(),
+                    }),
+                    accept: row.get(6)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or(Error::FeedNotFound(feed_id))?;
+
+    Ok(feed_request)
+}
+
+/// sets or, with `None`, removes the credentials sent when refreshing the feed
+pub fn set_feed_credentials(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    credentials: Option<&Credentials>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET username = ?2, password = ?3, updated_at = ?4 WHERE id = ?1",
+        params![
+            feed_id,
+            credentials.map(|credentials| &credentials.username),
+            credentials.map(|credentials| &credentials.password),
+            Utc::now()
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// the feed whose entries last had this fingerprint, see `get_feed_fingerprint`.
+/// a feed is fingerprinted every time it is refreshed,
+/// so a feed at a new url matches if it has the same self link
+/// and lists the same entries first as the old url did.
+pub fn find_feed_by_fingerprint(
+    conn: &rusqlite::Connection,
+    fingerprint: &str,
+) -> Result<Option<FeedId>> {
+    let feed_id = conn
+        .query_row(
+            "SELECT id FROM feeds WHERE fingerprint = ?1 ORDER BY id LIMIT 1",
+            [fingerprint],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(feed_id)
+}
+
+/// a hash of the feed's self link, title and the guids of its first entries
+/// when it was last refreshed,
+/// which identifies the feed wherever it is fetched from.
+/// `None` if its entries have no guids.
+pub fn get_feed_fingerprint(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<Option<String>> {
+    let fingerprint = conn
+        .query_row(
+            "SELECT fingerprint FROM feeds WHERE id = ?1",
+            [feed_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(Error::FeedNotFound(feed_id))?;
+
+    Ok(fingerprint)
+}
+
+/// the feed with `feed_link`, compared after `normalize_feed_link`
+pub fn get_feed_id_by_feed_link(
+    conn: &rusqlite::Connection,
+    feed_link: &str,
+) -> Result<Option<FeedId>> {
+    let feed_id = conn
+        .query_row(
+            "SELECT id FROM feeds WHERE feed_link=?1",
+            [normalize_feed_link(feed_link)],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(feed_id)
+}
+
+/// the order feeds are listed in: the feeds moved with `set_feed_position`
+/// in their positions, then the rest by title
+const FEED_ORDER: &str = "feeds.sort_position ASC NULLS LAST,
+          lower(COALESCE(feeds.user_title, feeds.title)) ASC";
+
+/// every feed, with everything `get_feed` has, in one query,
+/// in the order feeds are listed in, see `set_feed_position`
+pub fn get_feeds(conn: &rusqlite::Connection) -> Result<Vec<Feed>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {FEED_COLUMNS}
+        FROM feeds ORDER BY {FEED_ORDER}"
+    ))?;
+    let mut feeds = vec![];
+    for feed in statement.query_map([], feed_from_row)? {
+        feeds.push(feed?)
+    }
+
+    Ok(feeds)
+}
+
+/// feeds whose last refresh failed
+pub fn get_feeds_with_errors(conn: &rusqlite::Connection) -> Result<Vec<Feed>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE last_error IS NOT NULL
+        ORDER BY {FEED_ORDER}"
+    ))?;
+    let mut feeds = vec![];
+    for feed in statement.query_map([], feed_from_row)? {
+        feeds.push(feed?)
+    }
+
+    Ok(feeds)
+}
+
+/// feeds in `category`, or feeds without a category if `category` is `None`
+pub fn get_feeds_by_category(
+    conn: &rusqlite::Connection,
+    category: Option<&str>,
+) -> Result<Vec<Feed>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE category IS ?1
+        ORDER BY {FEED_ORDER}"
+    ))?;
+    let mut feeds = vec![];
+    for feed in statement.query_map([category], feed_from_row)? {
+        feeds.push(feed?)
+    }
+
+    Ok(feeds)
+}
+
+/// feeds written in `language`, like `en`, which is compared without case
+/// and also matches feeds in a variety of it, like `en-us`
+pub fn get_feeds_by_language(conn: &rusqlite::Connection, language: &str) -> Result<Vec<Feed>> {
+    let Some(language) = normalize_language(language) else {
+        return Ok(vec![]);
+    };
+
+    let mut statement = conn.prepare(&format!(
+        "SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE language = ?1
+        OR substr(language, 1, length(?1) + 1) = ?1 || '-'
+        ORDER BY {FEED_ORDER}"
+    ))?;
+    let mut feeds = vec![];
+    for feed in statement.query_map([language], feed_from_row)? {
+        feeds.push(feed?)
+    }
+
+    Ok(feeds)
+}
+
+/// every category that has a feed in it
+pub fn list_categories(conn: &rusqlite::Connection) -> Result<Vec<String>> {
+    let mut statement = conn.prepare(
+        "SELECT DISTINCT category FROM feeds
+        WHERE category IS NOT NULL
+        ORDER BY lower(category) ASC",
+    )?;
+    let mut categories = vec![];
+    for category in statement.query_map([], |row| row.get(0))? {
+        categories.push(category?)
+    }
+
+    Ok(categories)
+}
+
+/// moves the feed into `category`, or out of any category if `category` is `None`
+pub fn set_feed_category(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    category: Option<&str>,
+) -> Result<()> {
+    // a blank category is no category
+    let category = category
+        .map(|category| category.trim())
+        .filter(|category| !category.is_empty());
+
+    conn.execute(
+        "UPDATE feeds SET category = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, category, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+/// whether refreshing the feed stores the body of the response it parsed,
+/// for seeing exactly what the feed sent when it behaves oddly, see `reparse_feed`.
+/// it is off by default, as it stores the whole feed again on every refresh.
+/// turning it off deletes the stored body.
+pub fn set_feed_keeps_raw_body(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    keep_raw_body: bool,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET
+          keep_raw_body = ?2,
+          last_raw_body = CASE WHEN ?2 THEN last_raw_body END,
+          updated_at = ?3
+        WHERE id = ?1",
+        params![feed_id, keep_raw_body, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+/// the body of the response the feed was last parsed from,
+/// if it keeps it, see `set_feed_keeps_raw_body`
+pub fn get_feed_raw_body(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<Option<String>> {
+    let body = conn
+        .query_row(
+            "SELECT last_raw_body FROM feeds WHERE id = ?1",
+            [feed_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(Error::FeedNotFound(feed_id))?;
+
+    Ok(body)
+}
+
+/// parses the feed's stored response body again, without fetching it,
+/// for reproducing how russ parsed the feed. nothing is written to the database.
+/// fails if the feed has no stored body, see `set_feed_keeps_raw_body`.
+pub fn reparse_feed(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<(Feed, Vec<Entry>)> {
+    let body = get_feed_raw_body(conn, feed_id)?.with_context(|| {
+        format!("feed {feed_id} has no stored response body, see set_feed_keeps_raw_body")
+    })?;
+
+    let mut feed_and_entries = FeedAndEntries::from_str(&body)?;
+    if let Some(feed_link) = get_feed(conn, feed_id)?.feed_link {
+        feed_and_entries.set_feed_link(&feed_link);
+    }
+
+    Ok((feed_and_entries.feed, feed_and_entries.entries))
+}
+
+/// sets or, with `None`, removes the `Accept` header refreshing the feed sends
+/// rather than `FEED_ACCEPT`, for servers that only send the feed to requests
+/// that ask for it in a particular way
+pub fn set_feed_accept(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    accept: Option<&str>,
+) -> Result<()> {
+    let accept = accept
+        .map(|accept| accept.trim())
+        .filter(|accept| !accept.is_empty());
+
+    conn.execute(
+        "UPDATE feeds SET accept = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, accept, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+/// changes how refreshing the feed tells whether it already has an entry,
+/// for feeds whose guids or links are not stable, or not unique.
+/// entries the feed already has are matched the new way from the next refresh on.
+pub fn set_feed_dedupe_strategy(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    dedupe_strategy: DedupeStrategy,
+) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE feeds SET dedupe_strategy = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, dedupe_strategy, Utc::now()],
+    )?;
+
+    if updated == 0 {
+        return Err(Error::FeedNotFound(feed_id).into());
+    }
+
+    Ok(())
+}
+
+fn get_feed_dedupe_strategy(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<DedupeStrategy> {
+    let dedupe_strategy = conn.query_row(
+        "SELECT dedupe_strategy FROM feeds WHERE id = ?1",
+        [feed_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(dedupe_strategy)
+}
+
+/// caps how long the description and content of the feed's entries may be, in bytes,
+/// for feeds that embed whole images in their content and would bloat the database.
+/// entries stored or updated from then on are cut short, with the " […]" that marks the cut
+/// counting towards the cap, see `Entry::truncated`.
+/// `None` or 0 stores them whole.
+pub fn set_feed_max_content_bytes(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    max_content_bytes: Option<usize>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET max_content_bytes = ?2, updated_at = ?3 WHERE id = ?1",
+        params![
+            feed_id,
+            max_content_bytes.filter(|max_content_bytes| *max_content_bytes > 0),
+            Utc::now()
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn get_feed_max_content_bytes(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<Option<usize>> {
+    let max_content_bytes = conn
+        .query_row(
+            "SELECT max_content_bytes FROM feeds WHERE id = ?1",
+            [feed_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+
+    Ok(max_content_bytes)
+}
+
+/// caps how many entries the feed keeps, so busy feeds do not grow without bound.
+/// every refresh deletes the feed's oldest read entries beyond the newest `max_entries`.
+/// starred and unread entries are always kept, even if that is more than `max_entries`.
+/// `None` keeps every entry.
+pub fn set_feed_max_entries(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    max_entries: Option<usize>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE feeds SET max_entries = ?2, updated_at = ?3 WHERE id = ?1",
+        params![feed_id, max_entries, Utc::now()],
+    )?;
+
+    Ok(())
+}
+
+/// the longest interval `set_feed_refresh_interval` takes
+const MAX_USER_REFRESH_INTERVAL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// how often `feeds_due_for_refresh` says the feed is due,
+/// whether or not the feed asks to be fetched less often.
+/// `None` goes back to what the feed asks for.
+/// fails for intervals longer than a year, see `MAX_USER_REFRESH_INTERVAL`.
+pub fn set_feed_refresh_interval(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    refresh_interval: Option<Duration>,
+) -> Result<()> {
+    if refresh_interval.is_some_and(|interval| interval > MAX_USER_REFRESH_INTERVAL) {
+        return Err(anyhow::anyhow!(
+            "a feed can be refreshed at most every {} days",
+            MAX_USER_REFRESH_INTERVAL.as_secs() / (24 * 60 * 60)
+        ));
+    }
+
+    conn.execute(
+        "UPDATE feeds SET refresh_interval_seconds = ?2, updated_at = ?3 WHERE id = ?1",
+        params![
+            feed_id,
+            refresh_interval.map(|interval| interval.as_secs()),
+            Utc::now()
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// moves the feed to `position`, counting from 0, in the order feeds are listed in,
+/// see `get_feeds`. a position past the end moves it to the end.
+/// the first move numbers every feed in the order they were listed in,
+/// so the others keep their places, and feeds subscribed to after that
+/// are listed after the numbered ones, by title.
+/// every move renumbers the feeds from 0, so there are no gaps or ties.
+pub fn set_feed_position(
+    conn: &mut rusqlite::Connection,
+    feed_id: FeedId,
+    position: usize,
+) -> Result<()> {
+    in_transaction(conn, |tx| {
+        let mut feed_ids = get_feed_ids(tx)?;
+
+        let current_position = feed_ids
+            .iter()
+            .position(|id| *id == feed_id)
+            .ok_or(Error::FeedNotFound(feed_id))?;
+
+        feed_ids.remove(current_position);
+        feed_ids.insert(position.min(feed_ids.len()), feed_id);
+
+        let mut statement = tx.prepare("UPDATE feeds SET sort_position = ?2 WHERE id = ?1")?;
+        for (position, feed_id) in feed_ids.into_iter().enumerate() {
+            statement.execute(params![feed_id, position])?;
+        }
+
+        Ok(())
+    })
+}
+
+/// undoes `set_feed_position`, listing every feed by title again
+pub fn reset_feed_positions(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute("UPDATE feeds SET sort_position = NULL", [])?;
+    Ok(())
+}
+
+pub fn get_feed_ids(conn: &rusqlite::Connection) -> Result<Vec<FeedId>> {
+    let mut statement = conn.prepare(&format!("SELECT id FROM feeds ORDER BY {FEED_ORDER}"))?;
+    let mut ids = vec![];
+    for id in statement.query_map([], |row| row.get(0))? {
+        ids.push(id?)
+    }
+
+    Ok(ids)
+}
+
+/// the feeds it is time to refresh at `now`, in the same order as `get_feeds`:
+/// feeds that have never been refreshed, feeds that do not say how often
+/// to fetch them, and feeds whose `Feed::min_refresh_interval`
+/// has passed since they were last refreshed.
+/// a feed's `Feed::refresh_interval` takes the place of its `min_refresh_interval`.
+/// for schedulers that should not fetch feeds more often than they ask.
+pub fn feeds_due_for_refresh(
+    conn: &rusqlite::Connection,
+    now: DateTime<Utc>,
+) -> Result<Vec<FeedId>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT id, refreshed_at, COALESCE(refresh_interval_seconds, min_refresh_interval_seconds)
+        FROM feeds
+        ORDER BY {FEED_ORDER}"
+    ))?;
+
+    let mut feed_ids = vec![];
+
+    for feed in statement.query_map([], |row| {
+        Ok((
+            row.get::<_, FeedId>(0)?,
+            row.get::<_, Option<DateTime<Utc>>>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+        ))
+    })? {
+        let (feed_id, refreshed_at, refresh_interval_seconds) = feed?;
+
+        let is_due = match (refreshed_at, refresh_interval_seconds) {
+            // an interval too long to add to a date is never up
+            (Some(refreshed_at), Some(seconds)) => {
+                let interval = Duration::from_secs(u64::try_from(seconds).unwrap_or(0));
+                chrono::Duration::from_std(interval)
+                    .ok()
+                    .and_then(|interval| refreshed_at.checked_add_signed(interval))
+                    .is_some_and(|due_at| due_at <= now)
+            }
+            _ => true,
+        };
+
+        if is_due {
+            feed_ids.push(feed_id);
+        }
+    }
+
+    Ok(feed_ids)
+}
+
+/// the number of unread entries of every feed, in the same order as `get_feeds`.
+/// feeds with no unread entries have a count of 0.
+pub fn get_unread_counts(conn: &rusqlite::Connection) -> Result<Vec<(FeedId, i64)>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT feeds.id, COUNT(entries.id)
+        FROM feeds
+        LEFT JOIN entries
+          ON entries.feed_id = feeds.id
+          AND entries.read_at IS NULL
+        GROUP BY feeds.id
+        ORDER BY {FEED_ORDER}"
+    ))?;
+    let mut unread_counts = vec![];
+    for unread_count in statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        unread_counts.push(unread_count?)
+    }
+
+    Ok(unread_counts)
+}
+
+pub fn get_unread_count(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<i64> {
+    let unread_count = conn.query_row(
+        "SELECT COUNT(*) FROM entries WHERE feed_id=?1 AND read_at IS NULL",
+        [feed_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(unread_count)
+}
+
+/// see `get_feed_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeedStats {
+    pub total: i64,
+    pub unread: i64,
+    pub starred: i64,
+    /// of the entries with a publication date that could be parsed
+    pub oldest_published: Option<DateTime<Utc>>,
+    pub newest_published: Option<DateTime<Utc>>,
+}
+
+/// counts of the feed's entries, without loading them.
+/// a feed that does not exist has no entries.
+pub fn get_feed_stats(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<FeedStats> {
+    let feed_stats = conn.query_row(
+        "SELECT
+          COUNT(*),
+          COUNT(*) FILTER (WHERE read_at IS NULL),
+          COUNT(*) FILTER (WHERE starred = 1),
+          MIN(published_at),
+          MAX(published_at)
+        FROM entries
+        WHERE feed_id = ?1",
+        [feed_id],
+        |row| {
+            Ok(FeedStats {
+                total: row.get(0)?,
+                unread: row.get(1)?,
+                starred: row.get(2)?,
+                oldest_published: row.get(3)?,
+                newest_published: row.get(4)?,
+            })
+        },
+    )?;
+
+    Ok(feed_stats)
+}
+
+/// see `get_library_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LibraryStats {
+    pub feed_count: i64,
+    pub entry_count: i64,
+    pub unread_count: i64,
+    pub starred_count: i64,
+}
+
+/// counts of every feed and entry, like `get_feed_stats` for the whole database,
+/// without getting the stats of each feed in turn
+pub fn get_library_stats(conn: &rusqlite::Connection) -> Result<LibraryStats> {
+    let library_stats = conn.query_row(
+        "SELECT
+          (SELECT COUNT(*) FROM feeds),
+          COUNT(*),
+          COUNT(*) FILTER (WHERE read_at IS NULL),
+          COUNT(*) FILTER (WHERE starred = 1)
+        FROM entries",
+        [],
+        |row| {
+            Ok(LibraryStats {
+                feed_count: row.get(0)?,
+                entry_count: row.get(1)?,
+                unread_count: row.get(2)?,
+                starred_count: row.get(3)?,
+            })
+        },
+    )?;
+
+    Ok(library_stats)
+}
+
+pub fn get_entry_meta(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<EntryMeta> {
+    let result = conn
+        .query_row(
+            "SELECT 
+          id, 
+          feed_id, 
+          title, 
+          author, 
+          pub_date, 
+          published_at, 
+          link, 
+          read_at, 
+          inserted_at, 
+          updated_at 
+        FROM entries WHERE id=?1",
+            [entry_id],
+            |row| {
+                Ok(EntryMeta {
+                    id: row.get(0)?,
+                    feed_id: row.get(1)?,
+                    title: row.get(2)?,
+                    author: row.get(3)?,
+                    pub_date: row.get(4)?,
+                    published_at: row.get(5)?,
+                    link: row.get(6)?,
+                    read_at: row.get(7)?,
+                    inserted_at: row.get(8)?,
+                    updated_at: row.get(9)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or(Error::EntryNotFound(entry_id))?;
+
+    Ok(result)
+}
+
+/// the columns `entry_from_row` reads, in order
+const ENTRY_COLUMNS: &str = "
+          entries.id,
+          entries.feed_id,
+          entries.title,
+          entries.author,
+          entries.pub_date,
+          entries.published_at,
+          entries.description,
+          entries.content,
+          entries.link,
+          entries.guid,
+          entries.read_at,
+          entries.starred,
+          entries.enclosure_url,
+          entries.enclosure_type,
+          entries.enclosure_length,
+          entries.inserted_at,
+          entries.updated_at,
+          (SELECT json_group_array(tag)
+            FROM (SELECT tag FROM entry_tags WHERE entry_id = entries.id ORDER BY rowid)),
+          entries.thumbnail_url,
+          entries.author_email,
+          entries.author_uri,
+          entries.truncated,
+          entries.full_content,
+          entries.full_content_error,
+          entries.language";
+
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<Entry> {
+    let enclosure_url: Option<String> = row.get(12)?;
+
+    Ok(Entry {
+        id: row.get(0)?,
+        feed_id: row.get(1)?,
+        title: row.get(2)?,
+        author: row.get(3)?,
+        pub_date: row.get(4)?,
+        published_at: row.get(5)?,
+        description: row.get(6)?,
+        content: row.get(7)?,
+        link: row.get(8)?,
+        guid: row.get(9)?,
+        read_at: row.get(10)?,
+        starred: row.get(11)?,
+        enclosure: match enclosure_url {
+            Some(url) => Some(Enclosure {
+                url,
+                mime_type: row.get(13)?,
+                length: row.get(14)?,
+            }),
+            None => None,
+        },
+        tags: serde_json::from_str(&row.get::<_, String>(17)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(17, rusqlite::types::Type::Text, e.into())
+        })?,
+        thumbnail_url: row.get(18)?,
+        author_email: row.get(19)?,
+        author_uri: row.get(20)?,
+        truncated: row.get(21)?,
+        full_content: row.get(22)?,
+        full_content_error: row.get(23)?,
+        language: row.get(24)?,
+        inserted_at: row.get(15)?,
+        updated_at: row.get(16)?,
+    })
+}
+
+pub fn get_entry(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<Entry> {
+    let entry = conn
+        .query_row(
+            &format!("SELECT {ENTRY_COLUMNS} FROM entries WHERE entries.id = ?1"),
+            [entry_id],
+            entry_from_row,
+        )
+        .optional()?
+        .ok_or(Error::EntryNotFound(entry_id))?;
+
+    Ok(entry)
+}
+
+/// the entries with `entry_ids`, in the same order, in a single query.
+/// ids without an entry are left out.
+/// use it to show what `refresh_feed` inserted, see `refresh_feed_entries`.
+pub fn get_entries_by_ids(
+    conn: &rusqlite::Connection,
+    entry_ids: &[EntryId],
+) -> Result<Vec<Entry>> {
+    if entry_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let placeholders = vec!["?"; entry_ids.len()].join(", ");
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS} FROM entries WHERE entries.id IN ({placeholders})"
+    ))?;
+
+    let mut entries_by_id = statement
+        .query_map(rusqlite::params_from_iter(entry_ids), entry_from_row)?
+        .map(|entry| entry.map(|entry| (entry.id, entry)))
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+
+    Ok(entry_ids
+        .iter()
+        .filter_map(|entry_id| entries_by_id.remove(entry_id))
+        .collect())
+}
+
+/// entries across all feeds whose title, description, or content match `query`,
+/// most relevant first.
+/// `query` is an SQLite FTS5 query: every word has to match,
+/// and words in double quotes have to match as a phrase.
+/// see <https://www.sqlite.org/fts5.html#full_text_query_syntax>.
+pub fn search_entries(conn: &rusqlite::Connection, query: &str) -> Result<Vec<Entry>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries_search
+        JOIN entries ON entries.id = entries_search.rowid
+        WHERE entries_search MATCH ?1
+        ORDER BY entries_search.rank"
+    ))?;
+    let mut entries = vec![];
+    for entry in statement.query_map([query], entry_from_row)? {
+        entries.push(entry?)
+    }
+
+    Ok(entries)
+}
+
+/// like `search_entries`, for the entries of one feed
+pub fn search_feed_entries(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    query: &str,
+) -> Result<Vec<Entry>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries_search
+        JOIN entries ON entries.id = entries_search.rowid
+        WHERE entries_search MATCH ?1
+        AND entries.feed_id = ?2
+        ORDER BY entries_search.rank"
+    ))?;
+    let mut entries = vec![];
+    for entry in statement.query_map(params![query, feed_id], entry_from_row)? {
+        entries.push(entry?)
+    }
+
+    Ok(entries)
+}
+
+/// starred entries across all feeds, most recently published first
+pub fn get_starred_entries(conn: &rusqlite::Connection) -> Result<Vec<Entry>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries
+        WHERE entries.starred = 1
+        ORDER BY entries.published_at DESC NULLS LAST, entries.inserted_at DESC"
+    ))?;
+    let mut entries = vec![];
+    for entry in statement.query_map([], entry_from_row)? {
+        entries.push(entry?)
+    }
+
+    Ok(entries)
+}
+
+/// entries across all feeds tagged with `tag`, ignoring case,
+/// most recently published first
+pub fn get_entries_by_tag(conn: &rusqlite::Connection, tag: &str) -> Result<Vec<Entry>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries
+        WHERE entries.id IN (
+          SELECT entry_id FROM entry_tags WHERE tag = ?1 COLLATE NOCASE
+        )
+        ORDER BY entries.published_at DESC NULLS LAST, entries.inserted_at DESC"
+    ))?;
+    let mut entries = vec![];
+    for entry in statement.query_map([tag.trim()], entry_from_row)? {
+        entries.push(entry?)
+    }
+
+    Ok(entries)
+}
+
+/// every tag of every entry, alphabetically.
+/// tags that only differ in case are listed once.
+pub fn list_tags(conn: &rusqlite::Connection) -> Result<Vec<String>> {
+    let mut statement = conn.prepare(
+        "SELECT tag FROM entry_tags
+        GROUP BY tag COLLATE NOCASE
+        ORDER BY tag COLLATE NOCASE ASC",
+    )?;
+    let mut tags = vec![];
+    for tag in statement.query_map([], |row| row.get(0))? {
+        tags.push(tag?)
+    }
+
+    Ok(tags)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+    /// unread entries, newest first, then read entries, newest first, for triage
+    UnreadFirst,
+    /// in the order they were stored, whatever their publication dates,
+    /// for feeds whose dates are missing or misleading
+    FirstSeen,
+}
+
+/// which of a feed's entries `query_entries` returns, and in what order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryQuery {
+    pub unread_only: bool,
+    pub starred_only: bool,
+    /// by publication date, see `SortOrder` for the others.
+    /// entries without a publication date come last either way,
+    /// and entries published at the same time are ordered by when they were stored.
+    pub sort: SortOrder,
+    pub limit: Option<i64>,
+}
+
+impl EntryQuery {
+    /// the `AND ...` predicates on `entries` for the filters
+    fn predicates(&self) -> String {
+        let mut predicates = String::new();
+
+        if self.unread_only {
+            predicates.push_str("\nAND entries.read_at IS NULL");
+        }
+
+        if self.starred_only {
+            predicates.push_str("\nAND entries.starred = 1");
+        }
+
+        predicates
+    }
+
+    /// what to `ORDER BY` for the sort
+    fn ordering(&self) -> String {
+        let by_date = |direction: &str| {
+            format!(
+                "entries.published_at {direction} NULLS LAST,
+          entries.inserted_at {direction},
+          entries.id {direction}"
+            )
+        };
+
+        match self.sort {
+            SortOrder::NewestFirst => by_date("DESC"),
+            SortOrder::OldestFirst => by_date("ASC"),
+            SortOrder::UnreadFirst => format!(
+                "entries.read_at IS NULL DESC,
+          {}",
+                by_date("DESC")
+            ),
+            SortOrder::FirstSeen => "entries.inserted_at ASC,
+          entries.id ASC"
+                .to_owned(),
+        }
+    }
+}
+
+pub fn query_entries(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    query: &EntryQuery,
+) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for_each_entry(conn, feed_id, query, |entry| entries.push(entry))?;
+    Ok(entries)
+}
+
+/// calls `f` with each of the entries `query_entries` returns, in the same order,
+/// reading them from the database one at a time.
+/// only one entry is in memory at a time, rather than all of them,
+/// which matters for feeds with thousands of entries.
+/// but the query stays open until the last entry, so `f` should be quick,
+/// and entries it changes through `conn` may or may not be visited changed.
+pub fn for_each_entry(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    query: &EntryQuery,
+    mut f: impl FnMut(Entry),
+) -> Result<()> {
+    let predicates = query.predicates();
+    let ordering = query.ordering();
+
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries
+        WHERE entries.feed_id = ?1{predicates}
+        ORDER BY
+          {ordering}
+        LIMIT ?2"
+    ))?;
+    // a negative limit is no limit
+    for entry in statement.query_map(params![feed_id, query.limit.unwrap_or(-1)], entry_from_row)? {
+        f(entry?)
+    }
+
+    Ok(())
+}
+
+/// a page of `limit` of the feed's entries, newest first, for scrolling through them.
+/// the first page has no `after_id`; the next page is after the
+/// `published_at` and `id` of the last entry of the page before.
+/// unlike paging with an offset, entries stored between pages
+/// do not push entries onto the next page again, or past it.
+/// entries without a publication date come last, and entries published
+/// at the same time by id, so the order does not depend on when entries were stored.
+pub fn get_entries_after(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    after_published_at: Option<DateTime<Utc>>,
+    after_id: Option<EntryId>,
+    limit: i64,
+) -> Result<Vec<Entry>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries
+        WHERE entries.feed_id = ?1
+        AND (
+          ?2 IS NULL
+          OR (
+            ?3 IS NOT NULL
+            AND (
+              entries.published_at < ?3
+              OR (entries.published_at = ?3 AND entries.id < ?2)
+              OR entries.published_at IS NULL
+            )
+          )
+          OR (?3 IS NULL AND entries.published_at IS NULL AND entries.id < ?2)
+        )
+        ORDER BY
+          entries.published_at DESC NULLS LAST,
+          entries.id DESC
+        LIMIT ?4"
+    ))?;
+
+    let entries = statement
+        .query_map(
+            params![feed_id, after_id, after_published_at, limit],
+            entry_from_row,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
+
+/// the entry after `current_entry_id` in the entries `query_entries` returns for `query`,
+/// ignoring its limit.
+/// `None` after the last entry, or if `current_entry_id` is not one of the feed's entries.
+/// the current entry does not have to match the query,
+/// so reading an entry in an `unread_only` list still goes to the next unread entry.
+pub fn next_entry(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    current_entry_id: EntryId,
+    query: &EntryQuery,
+) -> Result<Option<Entry>> {
+    adjacent_entry(conn, feed_id, current_entry_id, query, 1)
+}
+
+/// the entry before `current_entry_id`, see `next_entry`.
+/// `None` before the first entry.
+pub fn prev_entry(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    current_entry_id: EntryId,
+    query: &EntryQuery,
+) -> Result<Option<Entry>> {
+    adjacent_entry(conn, feed_id, current_entry_id, query, -1)
+}
+
+fn adjacent_entry(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+    current_entry_id: EntryId,
+    query: &EntryQuery,
+    step: i64,
+) -> Result<Option<Entry>> {
+    let predicates = query.predicates();
+    let ordering = query.ordering();
+
+    let entry = conn
+        .query_row(
+            &format!(
+                "WITH positions AS (
+                  SELECT entries.id, ROW_NUMBER() OVER (ORDER BY {ordering}) AS position
+                  FROM entries
+                  WHERE entries.feed_id = ?1
+                  AND (entries.id = ?2 OR (1 = 1{predicates}))
+                )
+                SELECT {ENTRY_COLUMNS}
+                FROM entries
+                JOIN positions ON positions.id = entries.id
+                WHERE positions.position = (SELECT position FROM positions WHERE id = ?2) + ?3"
+            ),
+            params![feed_id, current_entry_id, step],
+            entry_from_row,
+        )
+        .optional()?;
+
+    Ok(entry)
+}
+
+/// entries across all feeds, most recently published first,
+/// each with the feed it is from.
+/// pages through the entries `limit` at a time, skipping the first `offset`.
+pub fn get_all_entries(
+    conn: &rusqlite::Connection,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<(Feed, Entry)>> {
+    let feeds = get_feeds(conn)?
+        .into_iter()
+        .map(|feed| (feed.id, feed))
+        .collect::<HashMap<_, _>>();
+
+    let mut statement = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+        FROM entries
+        ORDER BY
+          entries.published_at DESC NULLS LAST,
+          entries.inserted_at DESC,
+          entries.id DESC
+        LIMIT ?1 OFFSET ?2"
+    ))?;
+    let mut feeds_and_entries = vec![];
+    for entry in statement.query_map([limit, offset], entry_from_row)? {
+        let entry = entry?;
+        let feed = feeds
+            .get(&entry.feed_id)
+            .with_context(|| format!("entry {} has no feed {}", entry.id, entry.feed_id))?;
+        feeds_and_entries.push((feed.clone(), entry))
+    }
+
+    Ok(feeds_and_entries)
+}
+
+pub fn get_entry_content(conn: &rusqlite::Connection, entry_id: EntryId) -> Result<EntryContent> {
+    let result = conn
+        .query_row(
+            "SELECT content, description FROM entries WHERE id=?1",
+            [entry_id],
+            |row| {
+                Ok(EntryContent {
+                    content: row.get(0)?,
+                    description: row.get(1)?,
+                })
+            },
+        )
+        .optional()?
+        .ok_or(Error::EntryNotFound(entry_id))?;
+
+    Ok(result)
+}
+
+pub fn get_entries_metas(
+    conn: &rusqlite::Connection,
+    read_mode: &ReadMode,
+    feed_id: FeedId,
+) -> Result<Vec<EntryMeta>> {
+    let read_at_predicate = match read_mode {
+        ReadMode::ShowUnread => "\nAND read_at IS NULL",
+        ReadMode::ShowRead => "\nAND read_at IS NOT NULL",
+        ReadMode::All => "\n",
+    };
+
+    // entries whose pubDate could not be parsed go last,
+    // and inserted_at keeps the order stable
+    let mut query = "SELECT 
+        id, 
+        feed_id, 
+        title, 
+        author, 
+        pub_date, 
+        published_at, 
+        link, 
+        read_at, 
+        inserted_at, 
+        updated_at 
+        FROM entries 
+        WHERE feed_id=?1"
+        .to_string();
+
+    query.push_str(read_at_predicate);
+    query.push_str("\nORDER BY published_at DESC NULLS LAST, inserted_at DESC");
+
+    let mut statement = conn.prepare(&query)?;
+    let mut entries = vec![];
+    for entry in statement.query_map([feed_id], |row| {
+        Ok(EntryMeta {
+            id: row.get(0)?,
+            feed_id: row.get(1)?,
+            title: row.get(2)?,
+            author: row.get(3)?,
+            pub_date: row.get(4)?,
+            published_at: row.get(5)?,
+            link: row.get(6)?,
+            read_at: row.get(7)?,
+            inserted_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    })? {
+        entries.push(entry?)
+    }
+
+    Ok(entries)
+}
+
+/// what a refresh needs to know about an entry
+/// to match it against the entries of the remote feed
+#[derive(Clone, Debug)]
+struct EntryKeys {
+    id: EntryId,
+    guid: Option<String>,
+    /// see `normalize_entry_link`
+    link: Option<String>,
+    title: Option<String>,
+    published_at: Option<DateTime<Utc>>,
+    content_hash: Option<String>,
+}
+
+fn get_entries_keys(conn: &rusqlite::Connection, feed_id: FeedId) -> Result<Vec<EntryKeys>> {
+    let mut statement = conn.prepare(
+        "SELECT id, guid, normalized_link, title, published_at, content_hash
+        FROM entries
+        WHERE feed_id=?1",
+    )?;
+    let mut entries_keys = vec![];
+
+    for entry_keys in statement.query_map([feed_id], |row| {
+        Ok(EntryKeys {
+            id: row.get(0)?,
+            guid: row.get(1)?,
+            link: row.get(2)?,
+            title: row.get(3)?,
+            published_at: row.get(4)?,
+            content_hash: row.get(5)?,
+        })
+    })? {
+        entries_keys.push(entry_keys?);
+    }
+
+    Ok(entries_keys)
+}
+
+/// what tells apart the entries `purge_read_entries_older_than` deleted from the feed,
+/// like `EntryKeys`, with normalized links
+fn get_purged_entries_keys(
+    conn: &rusqlite::Connection,
+    feed_id: FeedId,
+) -> Result<Vec<PurgedEntryKeys>> {
+    let mut statement = conn.prepare(
+        "SELECT rowid, guid, link, title, published_at FROM purged_entries WHERE feed_id = ?1",
+    )?;
+    let keys = statement
+        .query_map([feed_id], |row| {
+            let link: Option<String> = row.get(2)?;
+            Ok(PurgedEntryKeys {
+                id: row.get(0)?,
+                guid: row.get(1)?,
+                link: link.as_deref().map(normalize_entry_link),
+                title: row.get(3)?,
+                published_at: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(keys)
+}
+
+/// see `get_purged_entries_keys`
+struct PurgedEntryKeys {
+    id: i64,
+    guid: Option<String>,
+    link: Option<String>,
+    title: Option<String>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+pub fn get_entries_links(
+    conn: &rusqlite::Connection,
+    read_mode: &ReadMode,
+    feed_id: FeedId,
+) -> Result<Vec<Option<String>>> {
+    let read_at_predicate = match read_mode {
+        ReadMode::ShowUnread => "\nAND read_at IS NULL",
+        ReadMode::ShowRead => "\nAND read_at IS NOT NULL",
+        ReadMode::All => "\n",
+    };
+
+    // entries whose pubDate could not be parsed go last,
+    // and inserted_at keeps the order stable
+    let mut query = "SELECT link FROM entries WHERE feed_id=?1".to_string();
+
+    query.push_str(read_at_predicate);
+    query.push_str("\nORDER BY published_at DESC NULLS LAST, inserted_at DESC");
+
+    let mut links = vec![];
+    let mut statement = conn.prepare(&query)?;
+
+    for link in statement.query_map([feed_id], |row| row.get(0))? {
+        links.push(link?);
+    }
+
+    Ok(links)
+}
+
+/// run `f` in a transaction, committing if `f` returns an `Ok` value,
+/// otherwise rolling back.
+fn in_transaction<F, R>(conn: &mut rusqlite::Connection, f: F) -> Result<R>
+where
+    F: Fn(&rusqlite::Transaction) -> Result<R>,
+{
+    let tx = conn.transaction()?;
+
+    let result = f(&tx)?;
+
+    tx.commit()?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const ZCT: &str = "https://zeroclarkthirty.com/feed";
+
+    fn rss_fixture(items_len: usize) -> String {
+        let items = (0..items_len)
+            .map(|i| {
+                format!(
+                    "<item>
+                      <title>Entry {i}</title>
+                      <link>https://example.com/entries/{i}</link>
+                      <description>Description {i}</description>
+                    </item>"
+                )
+            })
+            .collect::<Vec<_>>();
+
+        rss_channel(&items)
+    }
+
+    fn rss_item(title: &str, link: Option<&str>, guid: Option<&str>) -> String {
+        let link = link
+            .map(|link| format!("<link>{link}</link>"))
+            .unwrap_or_default();
+        let guid = guid
+            .map(|guid| format!(r#"<guid isPermaLink="false">{guid}</guid>"#))
+            .unwrap_or_default();
+
+        format!("<item><title>{title}</title>{link}{guid}</item>")
+    }
+
+    fn rss_channel(items: &[String]) -> String {
+        let items = items.join("\n");
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <rss version="2.0">
+              <channel>
+                <title>Example</title>
+                <link>https://example.com</link>
+                <description>An example feed</description>
+                {items}
+              </channel>
+            </rss>"#
+        )
+    }
+
+    /// serves `rss_fixture(items_len)` at every path
+    fn serve_rss_fixture(items_len: usize) -> String {
+        use crate::test_server::{serve, TestResponse};
+
+        let remote = rss_fixture(items_len);
+        serve(move |_request| TestResponse::ok(remote.clone()))
+    }
+
+    #[test]
+    fn it_fetches() {
+        let base_url = serve_rss_fixture(3);
+
+        let http_client = test_http_client();
+        let fetch_result = fetch_feed(
+            &http_client,
+            &format!("{base_url}/feed"),
+            &CacheValidators::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        let feed_and_entries = fetch_result.feed_and_entries.unwrap();
+
+        assert_eq!(feed_and_entries.feed.title.as_deref(), Some("Example"));
+        assert_eq!(
+            feed_and_entries.feed.feed_link,
+            Some(format!("{base_url}/feed"))
+        );
+        assert_eq!(
+            feed_and_entries
+                .entries
+                .iter()
+                .map(|entry| entry.link.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://example.com/entries/0",
+                "https://example.com/entries/1",
+                "https://example.com/entries/2"
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "fetches a live feed over the network"]
+    fn it_fetches_a_live_feed() {
+        let http_client = FetchOptions::default().http_client();
+        let fetch_result =
+            fetch_feed(&http_client, ZCT, &CacheValidators::default(), None, None).unwrap();
+        assert!(!fetch_result.feed_and_entries.unwrap().entries.is_empty())
+    }
+
+    #[test]
+    fn it_subscribes_to_a_feed() {
+        let base_url = serve_rss_fixture(60);
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed")).unwrap();
+
+        assert_eq!(entries_count(&conn), 60);
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().feed_link,
+            Some(format!("{base_url}/feed"))
+        );
+    }
+
+    #[test]
+    fn it_subscribes_to_a_feed_and_returns_it() {
+        let base_url = serve_rss_fixture(3);
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed =
+            subscribe_to_feed_and_get_feed(&http_client, &mut conn, &format!("{base_url}/feed"))
+                .unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Example"));
+        assert_eq!(feed.feed_link, Some(format!("{base_url}/feed")));
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![feed.id]);
+
+        // subscribing again returns the feed there already is
+        let again =
+            subscribe_to_feed_and_get_feed(&http_client, &mut conn, &format!("{base_url}/feed"))
+                .unwrap();
+        assert_eq!(again.id, feed.id);
+    }
+
+    #[test]
+    fn a_dry_run_previews_what_refreshing_would_add_without_storing_anything() {
+        let base_url = serve_rss_fixture(5);
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/feed"));
+        let feed_before = get_feed(&conn, feed_id).unwrap();
+
+        let previews = refresh_feed_dry_run(&http_client, &conn, feed_id).unwrap();
+        assert_eq!(
+            previews
+                .iter()
+                .filter_map(|preview| preview.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec!["Entry 2", "Entry 3", "Entry 4"]
+        );
+
+        assert_eq!(entries_count(&conn), 2);
+        let feed_after = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed_after.refreshed_at, feed_before.refreshed_at);
+        assert_eq!(feed_after.last_status, feed_before.last_status);
+        assert_eq!(feed_after.updated_at, feed_before.updated_at);
+
+        let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        let inserted = get_entries_by_ids(&conn, &refresh_summary.inserted)
+            .unwrap()
+            .into_iter()
+            .map(EntryPreview::from)
+            .collect::<Vec<_>>();
+        assert_eq!(previews, inserted);
+
+        assert!(refresh_feed_dry_run(&http_client, &conn, feed_id)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn refresh_feed_does_not_add_any_items_if_there_are_no_new_items() {
+        let base_url = serve_rss_fixture(60);
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed")).unwrap();
+        let old_entries = get_entries_metas(&conn, &ReadMode::ShowUnread, feed_id).unwrap();
+
+        let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert!(refresh_summary.updated.is_empty());
+
+        let e = get_entry_meta(&conn, old_entries[0].id).unwrap();
+        e.mark_as_read(&conn).unwrap();
+        let new_entries = get_entries_metas(&conn, &ReadMode::ShowUnread, feed_id).unwrap();
+
+        assert_eq!(new_entries.len(), old_entries.len() - 1);
+    }
+
+    #[test]
+    fn refresh_feed_entries_returns_the_inserted_entries() {
+        use crate::test_server::{serve, TestResponse};
+
+        let remote = rss_fixture(4).replace(
+            "<description>Description 3</description>",
+            r#"<description>Description 3</description>
+            <author>jo@example.com</author>
+            <pubDate>Sun, 01 Jan 2023 12:00:00 GMT</pubDate>
+            <enclosure url="https://example.com/3.mp3" type="audio/mpeg" length="42"/>"#,
+        );
+        let base_url = serve(move |_request| TestResponse::ok(remote.clone()));
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/feed"));
+
+        let entries = refresh_feed_entries(&http_client, &mut conn, feed_id).unwrap();
+
+        assert_eq!(
+            entries.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        let entry = &entries[1];
+        assert_eq!(entry.feed_id, feed_id);
+        assert_eq!(entry.title.as_deref(), Some("Entry 3"));
+        assert_eq!(entry.link.as_deref(), Some("https://example.com/entries/3"));
+        assert_eq!(entry.description.as_deref(), Some("Description 3"));
+        assert_eq!(entry.author, None);
+        assert_eq!(entry.author_email.as_deref(), Some("jo@example.com"));
+        assert_eq!(
+            entry.published_at,
+            Some("2023-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(
+            entry.enclosure,
+            Some(Enclosure {
+                url: "https://example.com/3.mp3".to_owned(),
+                mime_type: Some("audio/mpeg".to_owned()),
+                length: Some(42),
+            })
+        );
+        assert_eq!(entry.read_at, None);
+
+        assert!(refresh_feed_entries(&http_client, &mut conn, feed_id)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn refresh_feed_and_notify_calls_back_once_per_new_entry() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|_request| TestResponse::ok(rss_fixture(4)));
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/feed"));
+
+        let mut new_titles = vec![];
+        let refresh_summary = refresh_feed_and_notify(&http_client, &mut conn, feed_id, |entry| {
+            new_titles.push(entry.title.clone().unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(new_titles, vec!["Entry 2", "Entry 3"]);
+        assert_eq!(refresh_summary.inserted.len(), 2);
+
+        let mut notified = 0;
+        refresh_feed_and_notify(&http_client, &mut conn, feed_id, |_entry| notified += 1).unwrap();
+        assert_eq!(notified, 0);
+    }
+
+    #[test]
+    fn it_gets_entries_by_ids_in_order() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(3));
+
+        let entries = get_entries_by_ids(&conn, &[3, 99, 1]).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec![Some("Entry 2"), Some("Entry 0")]
+        );
+        assert!(get_entries_by_ids(&conn, &[]).unwrap().is_empty());
+    }
+
+    /// stores a parsed feed the same way `subscribe_to_feed` does,
+    /// without going to the network
+    fn insert_fixture(conn: &mut rusqlite::Connection, xml: &str) -> FeedId {
+        let feed_and_entries = FeedAndEntries::from_str(xml).unwrap();
+        in_transaction(conn, |tx| {
+            let feed_id = create_feed(tx, &feed_and_entries.feed)?;
+            update_feed_fingerprint(tx, feed_id, feed_and_entries.fingerprint().as_deref())?;
+            add_entries_to_feed(tx, feed_id, &feed_and_entries.entries)?;
+            Ok(feed_id)
+        })
+        .unwrap()
+    }
+
+    /// a `FetchResult` as if `xml` was fetched from a server
+    /// that does not send cache validators
+    fn fetched(xml: &str) -> FetchResult {
+        FetchResult {
+            feed_and_entries: Some(FeedAndEntries::from_str(xml).unwrap()),
+            status: Some(200),
+            cache_validators: CacheValidators::default(),
+            moved_to: None,
+            body: Some(xml.to_owned()),
+        }
+    }
+
+    fn test_db() -> rusqlite::Connection {
+        open_in_memory_database().unwrap()
+    }
+
+    const ATOM_FIXTURE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+    <feed xmlns="http://www.w3.org/2005/Atom">
+      <title>Example Atom</title>
+      <link href="https://example.com/"/>
+      <updated>2023-01-02T00:00:00Z</updated>
+      <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+      <entry>
+        <title>First</title>
+        <link href="https://example.com/first"/>
+        <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+        <published>2023-01-01T12:00:00Z</published>
+        <updated>2023-01-02T00:00:00Z</updated>
+        <author><name>Jane Doe</name></author>
+        <summary>A summary</summary>
+        <content type="html">&lt;p&gt;Some content&lt;/p&gt;</content>
+      </entry>
+      <entry>
+        <title>Second</title>
+        <link href="https://example.com/second"/>
+        <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6b</id>
+        <updated>2023-01-03T00:00:00Z</updated>
+      </entry>
+    </feed>"#;
+
+    #[test]
+    fn it_parses_rss() {
+        let feed_and_entries = FeedAndEntries::from_str(&rss_fixture(3)).unwrap();
+
+        assert!(matches!(feed_and_entries.feed.feed_kind, FeedKind::Rss));
+        assert_eq!(feed_and_entries.feed.title.as_deref(), Some("Example"));
+        assert_eq!(feed_and_entries.entries.len(), 3);
+    }
+
+    #[test]
+    fn it_splits_authors_into_their_name_email_and_uri() {
+        let author = |name: Option<&str>, email: Option<&str>, uri: Option<&str>| Author {
+            name: name.map(|name| name.to_owned()),
+            email: email.map(|email| email.to_owned()),
+            uri: uri.map(|uri| uri.to_owned()),
+        };
+
+        for (rss_author, expected) in [
+            (
+                "foo@bar.com (Foo Bar)",
+                author(Some("Foo Bar"), Some("foo@bar.com"), None),
+            ),
+            (
+                "Foo Bar <foo@bar.com>",
+                author(Some("Foo Bar"), Some("foo@bar.com"), None),
+            ),
+            (" foo@bar.com ", author(None, Some("foo@bar.com"), None)),
+            ("Foo Bar", author(Some("Foo Bar"), None, None)),
+            ("", Author::default()),
+        ] {
+            assert_eq!(Author::from_rss(rss_author), expected, "{rss_author}");
+        }
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &ATOM_FIXTURE.replace(
+                "<author><name>Jane Doe</name></author>",
+                "<author>
+                  <name>Jane Doe</name>
+                  <email>jane@example.com</email>
+                  <uri>https://example.com/jane</uri>
+                </author>",
+            ),
+        );
+        let entries = query_entries(&conn, feed_id, &EntryQuery::default()).unwrap();
+        let entry = |title: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.title.as_deref() == Some(title))
+                .unwrap()
+        };
+
+        assert_eq!(
+            entry("First").author_details(),
+            Some(author(
+                Some("Jane Doe"),
+                Some("jane@example.com"),
+                Some("https://example.com/jane")
+            ))
+        );
+        assert_eq!(entry("Second").author_details(), None);
+    }
+
+    #[test]
+    fn it_parses_atom() {
+        let feed_and_entries = FeedAndEntries::from_str(ATOM_FIXTURE).unwrap();
+
+        assert!(matches!(feed_and_entries.feed.feed_kind, FeedKind::Atom));
+        assert_eq!(feed_and_entries.feed.title.as_deref(), Some("Example Atom"));
+        assert_eq!(
+            feed_and_entries.feed.link.as_deref(),
+            Some("https://example.com/")
+        );
+
+        let first = &feed_and_entries.entries[0];
+        assert_eq!(first.title.as_deref(), Some("First"));
+        assert_eq!(first.link.as_deref(), Some("https://example.com/first"));
+        assert_eq!(first.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(first.description.as_deref(), Some("A summary"));
+        assert_eq!(first.content.as_deref(), Some("<p>Some content</p>"));
+        assert_eq!(
+            first.published_at,
+            Some("2023-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+
+        // no <published>, so fall back to <updated>
+        let second = &feed_and_entries.entries[1];
+        assert_eq!(second.author, None);
+        assert_eq!(second.description, None);
+        assert_eq!(
+            second.published_at,
+            Some("2023-01-03T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_parses_rfc_2822_dates() {
+        assert_eq!(
+            parse_datetime("Tue, 10 Jan 2023 08:30:00 -0500"),
+            Some("2023-01-10T13:30:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(
+            parse_datetime("Tue, 10 Jan 2023 13:30:00 GMT"),
+            Some("2023-01-10T13:30:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_parses_iso_8601_dates() {
+        assert_eq!(
+            parse_datetime("2023-01-10T08:30:00-05:00"),
+            Some("2023-01-10T13:30:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+    }
+
+    #[test]
+    fn it_does_not_parse_garbage_dates() {
+        assert_eq!(parse_datetime("sometime last week"), None);
+        assert_eq!(parse_datetime(""), None);
+    }
+
+    #[test]
+    fn one_malformed_entry_does_not_stop_the_others_from_being_stored() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        let remote = rss_channel(&[
+            r#"<item>
+              <title>Bad date</title>
+              <link>https://example.com/bad-date</link>
+              <pubDate>the 32nd of Nevuary</pubDate>
+            </item>"#
+                .to_owned(),
+            r#"<item>
+              <title>Bad enclosure</title>
+              <link>https://example.com/bad-enclosure</link>
+              <enclosure url="https://example.com/a.mp3" type="audio/mpeg" length="18446744073709551615"/>
+            </item>"#
+                .to_owned(),
+            rss_item("Fine", Some("https://example.com/fine"), None),
+            rss_item("Neither a guid nor a link", None, None),
+        ]);
+
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(refresh_summary.stats.inserted, 3);
+        assert_eq!(refresh_summary.stats.errors, 1);
+
+        let entries = get_entries_by_ids(&conn, &refresh_summary.inserted).unwrap();
+        assert_eq!(entries[0].pub_date.as_deref(), Some("the 32nd of Nevuary"));
+        assert_eq!(entries[0].published_at, None);
+        assert_eq!(
+            entries[1].enclosure,
+            Some(Enclosure {
+                url: "https://example.com/a.mp3".to_owned(),
+                mime_type: Some("audio/mpeg".to_owned()),
+                length: None,
+            })
+        );
+        assert_eq!(entries[2].title.as_deref(), Some("Fine"));
+    }
+
+    #[test]
+    fn it_backfills_publication_dates() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                "<item><title>Rfc 2822</title><pubDate>Mon, 09 Jan 2023 12:00:00 GMT</pubDate></item>"
+                    .to_owned(),
+                "<item><title>Rfc 3339</title><pubDate>2023-01-10T12:00:00Z</pubDate></item>"
+                    .to_owned(),
+                "<item><title>Unparseable</title><pubDate>sometime last week</pubDate></item>"
+                    .to_owned(),
+                "<item><title>Undated</title></item>".to_owned(),
+            ]),
+        );
+        conn.execute("UPDATE entries SET published_at = NULL", [])
+            .unwrap();
+
+        assert_eq!(backfill_published_at(&mut conn).unwrap(), 2);
+        // nothing left that parses
+        assert_eq!(backfill_published_at(&mut conn).unwrap(), 0);
+
+        let published_ats = query_entries(&conn, feed_id, &EntryQuery::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.title.unwrap(), entry.published_at))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            published_ats,
+            vec![
+                (
+                    "Rfc 3339".to_owned(),
+                    Some("2023-01-10T12:00:00Z".parse().unwrap())
+                ),
+                (
+                    "Rfc 2822".to_owned(),
+                    Some("2023-01-09T12:00:00Z".parse().unwrap())
+                ),
+                ("Undated".to_owned(), None),
+                ("Unparseable".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_unparseable_pub_dates_and_sorts_them_last() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                "<item><title>Unparseable</title><pubDate>sometime last week</pubDate></item>"
+                    .to_owned(),
+                "<item><title>Older</title><pubDate>Mon, 09 Jan 2023 12:00:00 GMT</pubDate></item>"
+                    .to_owned(),
+                "<item><title>Newer</title><pubDate>2023-01-10T12:00:00Z</pubDate></item>"
+                    .to_owned(),
+            ]),
+        );
+
+        let entries = get_entries_metas(&conn, &ReadMode::All, feed_id).unwrap();
+        let titles = entries
+            .iter()
+            .map(|entry| entry.title.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Newer", "Older", "Unparseable"]);
+
+        let unparseable = &entries[2];
+        assert_eq!(unparseable.pub_date.as_deref(), Some("sometime last week"));
+        assert_eq!(unparseable.published_at, None);
+
+        let older = &entries[1];
+        assert_eq!(
+            older.pub_date.as_deref(),
+            Some("Mon, 09 Jan 2023 12:00:00 GMT")
+        );
+        assert_eq!(
+            older.published_at,
+            Some("2023-01-09T12:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+
+        // full entries have both too
+        let entry = get_entry(&conn, older.id).unwrap();
+        assert_eq!(entry.pub_date, older.pub_date);
+        assert_eq!(entry.published_at, older.published_at);
+
+        let published_ats = query_entries(&conn, feed_id, &EntryQuery::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.published_at)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            published_ats,
+            entries
+                .iter()
+                .map(|entry| entry.published_at)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_does_not_parse_something_that_is_not_a_feed() {
+        let e = FeedAndEntries::from_str("<html><body>nope</body></html>")
+            .err()
+            .unwrap();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)));
+    }
+
+    #[test]
+    fn fetching_reports_http_statuses_and_responses_that_are_not_feeds() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/html" => TestResponse::ok("<!DOCTYPE html><html><body>Oops</body></html>"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+
+        let e = fetch_feed(
+            &http_client,
+            &format!("{base_url}/missing"),
+            &CacheValidators::default(),
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::HttpStatus(404))
+        ));
+
+        let mut conn = test_db();
+        let e =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/html")).unwrap_err();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)));
+        assert_eq!(
+            e.to_string(),
+            "the response is not an RSS, Atom, or JSON Feed"
+        );
+    }
+
+    #[test]
+    fn add_entries_to_feed_returns_ids_in_input_order() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        initialize_db(&mut conn).unwrap();
+        let feed_and_entries = FeedAndEntries::from_str(&rss_fixture(60)).unwrap();
+
+        let entry_ids = in_transaction(&mut conn, |tx| {
+            let feed_id = create_feed(tx, &feed_and_entries.feed)?;
+            add_entries_to_feed(tx, feed_id, &feed_and_entries.entries)
+        })
+        .unwrap();
+
+        assert_eq!(entry_ids.len(), 60);
+
+        for (i, entry_id) in entry_ids.into_iter().enumerate() {
+            let entry_meta = get_entry_meta(&conn, entry_id).unwrap();
+            assert_eq!(entry_meta.title, Some(format!("Entry {i}")));
+        }
+    }
+
+    #[test]
+    fn adding_entries_skips_the_ones_that_fail() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        initialize_db(&mut conn).unwrap();
+        let feed_and_entries = FeedAndEntries::from_str(&rss_fixture(60)).unwrap();
+
+        // fail partway through the insert loop
+        conn.execute(
+            "CREATE TRIGGER fail_midway BEFORE INSERT ON entries
+            WHEN NEW.title = 'Entry 30'
+            BEGIN
+              SELECT RAISE(ABORT, 'simulated failure');
+            END",
+            [],
+        )
+        .unwrap();
+
+        let (feed_id, entry_ids) = in_transaction(&mut conn, |tx| {
+            let feed_id = create_feed(tx, &feed_and_entries.feed)?;
+            let entry_ids = add_entries_to_feed(tx, feed_id, &feed_and_entries.entries)?;
+            Ok((feed_id, entry_ids))
+        })
+        .unwrap();
+
+        assert_eq!(entry_ids.len(), 59);
+        assert_eq!(entries_count(&conn), 59);
+
+        let titles = get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry_meta| entry_meta.title)
+            .collect::<Vec<_>>();
+        assert!(titles.contains(&"Entry 29".to_owned()));
+        assert!(!titles.contains(&"Entry 30".to_owned()));
+        assert!(titles.contains(&"Entry 31".to_owned()));
+
+        // a refresh counts the entries it could not store as errors
+        let remote = rss_channel(&[
+            rss_item("Entry 30", Some("https://example.com/entries/30"), None),
+            rss_item("Entry 60", Some("https://example.com/entries/60"), None),
+        ]);
+
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(refresh_summary.stats.inserted, 1);
+        assert_eq!(refresh_summary.stats.errors, 1);
+        assert_eq!(entries_count(&conn), 60);
+
+        // and so does subscribing
+        let base_url = crate::test_server::serve(|_request| {
+            crate::test_server::TestResponse::ok(rss_fixture(40))
+        });
+        let (_, refresh_summary) =
+            subscribe_to_feed_with_summary(&test_http_client(), &mut conn, &base_url).unwrap();
+        assert_eq!(refresh_summary.stats.fetched, 40);
+        assert_eq!(refresh_summary.stats.inserted, 39);
+        assert_eq!(refresh_summary.stats.errors, 1);
+        assert_eq!(refresh_summary.inserted.len(), 39);
+
+        // an error that is not about the entry fails them all
+        conn.execute(
+            "CREATE TRIGGER fail_elsewhere BEFORE INSERT ON entries
+            WHEN NEW.title = 'Entry 61'
+            BEGIN
+              INSERT INTO no_such_table VALUES (1);
+            END",
+            [],
+        )
+        .unwrap();
+        let remote = rss_channel(&[
+            rss_item("Entry 61", Some("https://example.com/entries/61"), None),
+            rss_item("Entry 62", Some("https://example.com/entries/62"), None),
+        ]);
+        let e = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap_err();
+        assert!(e.to_string().contains("no_such_table"), "{e}");
+        assert_eq!(
+            get_entries_metas(&conn, &ReadMode::All, feed_id)
+                .unwrap()
+                .len(),
+            60
+        );
+    }
+
+    #[test]
+    fn it_marks_entries_read_and_unread() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(2));
+
+        let before = get_entry_meta(&conn, 1).unwrap();
+        assert!(before.read_at.is_none());
+
+        mark_entry_read(&conn, 1).unwrap();
+        let read = get_entry_meta(&conn, 1).unwrap();
+        assert!(read.read_at.is_some());
+        assert!(read.updated_at >= before.updated_at);
+
+        mark_entry_unread(&conn, 1).unwrap();
+        let unread = get_entry_meta(&conn, 1).unwrap();
+        assert!(unread.read_at.is_none());
+
+        // the other entry is untouched
+        assert!(get_entry_meta(&conn, 2).unwrap().read_at.is_none());
+    }
+
+    #[test]
+    fn it_marks_a_feed_read() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(5));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+
+        mark_entry_read(&conn, 1).unwrap();
+        let already_read_at = get_entry_meta(&conn, 1).unwrap().read_at;
+
+        let updated = mark_feed_read(&conn, feed_id).unwrap();
+        assert_eq!(updated, 4);
+
+        assert!(get_entries_metas(&conn, &ReadMode::ShowUnread, feed_id)
+            .unwrap()
+            .is_empty());
+        assert_eq!(get_entry_meta(&conn, 1).unwrap().read_at, already_read_at);
+        assert_eq!(
+            get_entries_metas(&conn, &ReadMode::ShowUnread, other_feed_id)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn it_marks_a_feed_unread() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(5));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+
+        mark_all_read(&conn).unwrap();
+        mark_entry_unread(&conn, 1).unwrap();
+
+        let updated = mark_feed_unread(&conn, feed_id).unwrap();
+        assert_eq!(updated, 4);
+
+        assert!(get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .iter()
+            .all(|entry| entry.read_at.is_none()));
+        assert!(
+            get_entries_metas(&conn, &ReadMode::ShowUnread, other_feed_id)
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(mark_feed_unread(&conn, feed_id).unwrap(), 0);
+    }
+
+    #[test]
+    fn it_marks_all_feeds_read() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(5));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+
+        mark_entry_read(&conn, 1).unwrap();
+        mark_entry_read(&conn, 7).unwrap();
+        let already_read_at = get_entry_meta(&conn, 7).unwrap().read_at;
+
+        assert_eq!(mark_all_read(&conn).unwrap(), 6);
+
+        for feed_id in [feed_id, other_feed_id] {
+            assert_eq!(get_unread_count(&conn, feed_id).unwrap(), 0);
+        }
+        assert_eq!(get_entry_meta(&conn, 7).unwrap().read_at, already_read_at);
+
+        assert_eq!(mark_all_read(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn it_marks_entries_published_before_a_cutoff_read() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(5));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        let cutoff = "2023-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let days = |days: i64| cutoff + chrono::Duration::days(days);
+
+        // entry 5 has no publication date
+        for (entry_id, published_at) in [
+            (1, days(-30)),
+            (2, days(-1)),
+            (3, days(0)),
+            (4, days(1)),
+            (6, days(-10)),
+            (7, days(10)),
+        ] {
+            conn.execute(
+                "UPDATE entries SET published_at = ?2 WHERE id = ?1",
+                params![entry_id, published_at],
+            )
+            .unwrap();
+        }
+
+        star_entry(&conn, 2).unwrap();
+
+        assert_eq!(
+            mark_read_before(&conn, Some(feed_id), cutoff, true).unwrap(),
+            1
+        );
+        let read = |conn: &rusqlite::Connection| {
+            (1..=7)
+                .filter(|entry_id| get_entry_meta(conn, *entry_id).unwrap().read_at.is_some())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(read(&conn), vec![1]);
+
+        assert_eq!(mark_read_before(&conn, None, cutoff, false).unwrap(), 2);
+        assert_eq!(read(&conn), vec![1, 2, 6]);
+        assert_eq!(get_unread_count(&conn, other_feed_id).unwrap(), 1);
+
+        assert_eq!(mark_read_before(&conn, None, cutoff, false).unwrap(), 0);
+    }
+
+    #[test]
+    fn it_subscribes_to_and_refreshes_a_feed_in_a_local_file() {
+        let path =
+            std::env::temp_dir().join(format!("russ-test-file-feed-{}.xml", std::process::id()));
+        std::fs::write(&path, rss_fixture(2)).unwrap();
+        let url = url::Url::from_file_path(&path).unwrap().to_string();
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = subscribe_to_feed(&http_client, &mut conn, &url).unwrap();
+
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Example"));
+        assert_eq!(feed.feed_link.as_deref(), Some(url.as_str()));
+        assert_eq!(entries_count(&conn), 2);
+
+        std::fs::write(&path, rss_fixture(3)).unwrap();
+        let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert_eq!(refresh_summary.inserted.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+    }
+
+    #[test]
+    fn it_stores_entries_a_feed_lists_twice_once() {
+        use crate::test_server::{serve, TestResponse};
+
+        let remote = rss_channel(&[
+            rss_item("a", Some("https://example.com/a"), None),
+            rss_item("a again", Some("https://example.com/a"), None),
+            rss_item(
+                "a tracked",
+                Some("https://example.com/a?utm_source=rss"),
+                None,
+            ),
+            rss_item("b", Some("https://example.com/b"), Some("b")),
+            rss_item("b again", Some("https://example.com/b-moved"), Some("b")),
+            rss_item("c", Some("https://example.com/c"), Some("c")),
+        ]);
+        let base_url = serve(move |_request| TestResponse::ok(remote.clone()));
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed")).unwrap();
+
+        let titles = query_entries(
+            &conn,
+            feed_id,
+            &EntryQuery {
+                sort: SortOrder::OldestFirst,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.title.unwrap())
+        .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["a", "b", "c"]);
+
+        let remote = rss_channel(&[
+            rss_item("d", Some("https://example.com/d"), None),
+            rss_item("d again", Some("https://example.com/d"), None),
+            rss_item("e", None, Some("e")),
+            rss_item("e again", None, Some("e")),
+        ]);
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(refresh_summary.inserted.len(), 2);
+        assert_eq!(refresh_summary.stats.duplicates, 2);
+        assert_eq!(entries_count(&conn), 5);
+    }
+
+    #[test]
+    fn it_says_which_feed_or_entry_was_not_found() {
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        let bogus_feed_id = feed_id + 100;
+        let bogus_entry_id = 100;
+
+        let feed_not_found = |e: anyhow::Error| matches!(e.downcast_ref::<Error>(), Some(Error::FeedNotFound(id)) if *id == bogus_feed_id);
+        let entry_not_found = |e: anyhow::Error| matches!(e.downcast_ref::<Error>(), Some(Error::EntryNotFound(id)) if *id == bogus_entry_id);
+
+        assert!(feed_not_found(get_feed(&conn, bogus_feed_id).unwrap_err()));
+        assert!(feed_not_found(
+            refresh_feed(&http_client, &mut conn, bogus_feed_id).unwrap_err()
+        ));
+        assert!(entry_not_found(
+            get_entry(&conn, bogus_entry_id).unwrap_err()
+        ));
+        assert!(entry_not_found(
+            get_entry_meta(&conn, bogus_entry_id).unwrap_err()
+        ));
+        assert!(feed_not_found(
+            get_feed_url(&conn, bogus_feed_id).unwrap_err()
+        ));
+        assert!(feed_not_found(
+            get_feed_raw_body(&conn, bogus_feed_id).unwrap_err()
+        ));
+        assert!(entry_not_found(
+            get_entry_content(&conn, bogus_entry_id).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn it_unsubscribes_from_a_feed() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        assert_eq!(unsubscribe_feed(&mut conn, feed_id).unwrap(), 3);
+
+        let e = get_feed(&conn, feed_id).unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::FeedNotFound(id)) if *id == feed_id
+        ));
+        assert!(get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .is_empty());
+
+        // the other feed is untouched
+        assert_eq!(
+            get_entries_metas(&conn, &ReadMode::All, other_feed_id)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // unsubscribing again is fine
+        assert_eq!(unsubscribe_feed(&mut conn, feed_id).unwrap(), 0);
+    }
+
+    #[test]
+    fn it_counts_unread_entries_per_feed() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        let empty_feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+
+        let mut unread_counts = get_unread_counts(&conn).unwrap();
+        unread_counts.sort();
+        assert_eq!(
+            unread_counts,
+            vec![(feed_id, 3), (other_feed_id, 2), (empty_feed_id, 0)]
+        );
+
+        mark_entry_read(&conn, 1).unwrap();
+        mark_feed_read(&conn, other_feed_id).unwrap();
+
+        let mut unread_counts = get_unread_counts(&conn).unwrap();
+        unread_counts.sort();
+        assert_eq!(
+            unread_counts,
+            vec![(feed_id, 2), (other_feed_id, 0), (empty_feed_id, 0)]
+        );
+
+        assert_eq!(get_unread_count(&conn, feed_id).unwrap(), 2);
+        assert_eq!(get_unread_count(&conn, other_feed_id).unwrap(), 0);
+
+        mark_entry_unread(&conn, 1).unwrap();
+        assert_eq!(get_unread_count(&conn, feed_id).unwrap(), 3);
+    }
+
+    fn search_titles(conn: &rusqlite::Connection, query: &str) -> Vec<String> {
+        search_entries(conn, query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.title.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_searches_entries_across_feeds() {
+        let mut conn = test_db();
+        let item = |title: &str, description: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><description>{description}</description></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                item("Sourdough", "a starter needs flour and water"),
+                item("Baguettes", "flour water salt yeast, and a hot oven"),
+            ]),
+        );
+        insert_fixture(
+            &mut conn,
+            &rss_channel(&[item("Kayaking", "water, but the outdoor kind")]),
+        );
+
+        assert_eq!(search_titles(&conn, "sourdough"), vec!["Sourdough"]);
+        assert_eq!(search_titles(&conn, "oven yeast"), vec!["Baguettes"]);
+        assert_eq!(
+            search_titles(&conn, "\"flour and water\""),
+            vec!["Sourdough"]
+        );
+        assert!(search_titles(&conn, "croissant").is_empty());
+
+        let mut titles = search_titles(&conn, "water");
+        titles.sort();
+        assert_eq!(titles, vec!["Baguettes", "Kayaking", "Sourdough"]);
+
+        // both match one word, but a match in a shorter entry ranks higher
+        assert_eq!(search_titles(&conn, "kayaking OR oven")[0], "Kayaking");
+
+        // edits and deletions are reflected in the index
+        conn.execute(
+            "UPDATE entries SET description = 'now with croissants' WHERE title = 'Sourdough'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(search_titles(&conn, "croissants"), vec!["Sourdough"]);
+        assert!(search_titles(&conn, "starter").is_empty());
+
+        unsubscribe_feed(&mut conn, feed_id).unwrap();
+        assert_eq!(search_titles(&conn, "water"), vec!["Kayaking"]);
+    }
+
+    #[test]
+    fn it_searches_the_entries_of_one_feed() {
+        let mut conn = test_db();
+        let item = |title: &str, description: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><description>{description}</description></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                item("Sourdough", "a starter needs flour and water"),
+                item("Baguettes", "flour water salt yeast, and a hot oven"),
+                item("Pizza", "a very hot oven"),
+            ]),
+        );
+        let other_feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[item("Kayaking", "water, but the outdoor kind")]),
+        );
+
+        let search_titles = |feed_id: FeedId, query: &str| {
+            let mut titles = search_feed_entries(&conn, feed_id, query)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.title.unwrap())
+                .collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+
+        assert_eq!(
+            search_titles(feed_id, "water"),
+            vec!["Baguettes", "Sourdough"]
+        );
+        assert_eq!(search_titles(other_feed_id, "water"), vec!["Kayaking"]);
+        assert_eq!(search_titles(feed_id, "oven"), vec!["Baguettes", "Pizza"]);
+        assert!(search_titles(other_feed_id, "oven").is_empty());
+    }
+
+    #[test]
+    fn initialize_db_indexes_existing_entries_for_search() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(2));
+        conn.execute("DROP TABLE entries_search", []).unwrap();
+        // as if the database was from before schema versions
+        conn.pragma_update(None, "user_version", 0).unwrap();
+
+        initialize_db(&mut conn).unwrap();
+
+        assert_eq!(search_titles(&conn, "\"Description 1\""), vec!["Entry 1"]);
+    }
+
+    #[test]
+    fn it_stars_and_unstars_entries_across_feeds() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(3));
+        insert_fixture(&mut conn, &rss_fixture(2));
+
+        assert!(get_starred_entries(&conn).unwrap().is_empty());
+
+        star_entry(&conn, 1).unwrap();
+        star_entry(&conn, 4).unwrap();
+        // starring twice is the same as starring once
+        star_entry(&conn, 4).unwrap();
+
+        let mut starred_ids = get_starred_entries(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|entry| {
+                assert!(entry.starred);
+                entry.id
+            })
+            .collect::<Vec<_>>();
+        starred_ids.sort();
+        assert_eq!(starred_ids, vec![1, 4]);
+
+        unstar_entry(&conn, 1).unwrap();
+        let starred = get_starred_entries(&conn).unwrap();
+        assert_eq!(starred.len(), 1);
+        assert_eq!(starred[0].id, 4);
+        assert_eq!(starred[0].title.as_deref(), Some("Entry 0"));
+
+        // starring does not change whether an entry is read
+        assert!(get_entry_meta(&conn, 4).unwrap().read_at.is_none());
+    }
+
+    #[test]
+    fn it_toggles_whether_an_entry_is_read() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(2));
+
+        assert!(toggle_entry_read(&conn, 1).unwrap());
+        assert!(get_entry(&conn, 1).unwrap().read_at.is_some());
+
+        assert!(!toggle_entry_read(&conn, 1).unwrap());
+        assert!(get_entry(&conn, 1).unwrap().read_at.is_none());
+
+        // other entries are left alone
+        assert!(get_entry(&conn, 2).unwrap().read_at.is_none());
+
+        assert!(matches!(
+            toggle_entry_read(&conn, 99)
+                .unwrap_err()
+                .downcast_ref::<Error>(),
+            Some(Error::EntryNotFound(99))
+        ));
+    }
+
+    #[test]
+    fn it_purges_old_read_entries_except_starred_ones() {
+        let mut conn = test_db();
+        let xml = rss_fixture(5);
+        let feed_id = insert_fixture(&mut conn, &xml);
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        let now = Utc::now();
+        let read_at = |entry_id: EntryId, days_ago: i64| {
+            conn.execute(
+                "UPDATE entries SET read_at = ?2 WHERE id = ?1",
+                params![entry_id, now - chrono::Duration::days(days_ago)],
+            )
+            .unwrap();
+        };
+        // entry 1 is unread
+        read_at(2, 1);
+        read_at(3, 60);
+        read_at(4, 60);
+        read_at(5, 90);
+        read_at(6, 90);
+        star_entry(&conn, 4).unwrap();
+
+        let cutoff = now - chrono::Duration::days(30);
+
+        assert_eq!(
+            purge_feed_read_entries_older_than(&mut conn, feed_id, cutoff).unwrap(),
+            2
+        );
+
+        let remaining_ids = |conn: &rusqlite::Connection| {
+            let mut statement = conn.prepare("SELECT id FROM entries ORDER BY id").unwrap();
+            let ids = statement
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<EntryId>>>()
+                .unwrap();
+            ids
+        };
+
+        assert_eq!(remaining_ids(&conn), vec![1, 2, 4, 6]);
+
+        assert_eq!(purge_read_entries_older_than(&mut conn, cutoff).unwrap(), 1);
+        assert_eq!(remaining_ids(&conn), vec![1, 2, 4]);
+        assert!(get_entries_metas(&conn, &ReadMode::All, other_feed_id)
+            .unwrap()
+            .is_empty());
+
+        // purged entries are still in the feed, but should not come back
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&xml)).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert_eq!(remaining_ids(&conn), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn refreshing_prunes_old_read_entries_beyond_the_feeds_max_entries() {
+        let dated_fixture = |days: std::ops::RangeInclusive<u32>| {
+            rss_channel(
+                &days
+                    .map(|day| {
+                        format!(
+                            "<item>
+                              <title>Day {day}</title>
+                              <link>https://example.com/{day}</link>
+                              <pubDate>{day:02} Jan 2023 00:00:00 GMT</pubDate>
+                            </item>"
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &dated_fixture(1..=4));
+        let other_feed_id = insert_fixture(&mut conn, &dated_fixture(1..=4));
+        mark_feed_read(&conn, other_feed_id).unwrap();
+
+        set_feed_max_entries(&conn, feed_id, Some(2)).unwrap();
+        assert_eq!(get_feed(&conn, feed_id).unwrap().max_entries, Some(2));
+
+        let entry_id = |title: &str| {
+            conn.query_row(
+                "SELECT id FROM entries WHERE feed_id = ?1 AND title = ?2",
+                params![feed_id, title],
+                |row| row.get::<_, EntryId>(0),
+            )
+            .unwrap()
+        };
+        mark_feed_read(&conn, feed_id).unwrap();
+        star_entry(&conn, entry_id("Day 1")).unwrap();
+        mark_entry_unread(&conn, entry_id("Day 2")).unwrap();
+
+        let titles = |conn: &rusqlite::Connection, feed_id: FeedId| {
+            query_entries(conn, feed_id, &EntryQuery::default())
+                .unwrap()
+                .into_iter()
+                .filter_map(|entry| entry.title)
+                .collect::<Vec<_>>()
+        };
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&dated_fixture(1..=6))).unwrap();
+
+        // days 3 and 4 are beyond the newest 2, and read
+        assert_eq!(
+            titles(&conn, feed_id),
+            vec!["Day 6", "Day 5", "Day 2", "Day 1"]
+        );
+
+        // pruned entries do not come back
+        let refresh_summary =
+            merge_remote_feed(&mut conn, feed_id, fetched(&dated_fixture(1..=6))).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+
+        // and are only remembered as long as the feed lists them
+        let purged_count = |conn: &rusqlite::Connection| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM purged_entries WHERE feed_id = ?1",
+                [feed_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(purged_count(&conn), 2);
+        let refresh_summary =
+            merge_remote_feed(&mut conn, feed_id, fetched(&dated_fixture(4..=6))).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert_eq!(purged_count(&conn), 1);
+        merge_remote_feed(&mut conn, feed_id, fetched(&rss_channel(&[]))).unwrap();
+        assert_eq!(purged_count(&conn), 1);
+
+        // feeds without a limit keep everything
+        merge_remote_feed(&mut conn, other_feed_id, fetched(&dated_fixture(1..=4))).unwrap();
+        assert_eq!(titles(&conn, other_feed_id).len(), 4);
+    }
+
+    #[test]
+    fn refreshing_skips_comparing_entries_when_the_feed_has_not_changed() {
+        let built_at = |last_build_date: &str, items: &[(&str, &str)]| {
+            rss_channel(
+                &items
+                    .iter()
+                    .map(|(guid, description)| {
+                        format!(
+                            r#"<item><title>{guid}</title><guid isPermaLink="false">{guid}</guid><description>{description}</description></item>"#
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .replace(
+                "<title>Example</title>",
+                &format!("<title>Example</title><lastBuildDate>{last_build_date}</lastBuildDate>"),
+            )
+        };
+        let monday = "Mon, 02 Jan 2023 00:00:00 GMT";
+        let tuesday = "Tue, 03 Jan 2023 00:00:00 GMT";
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &built_at(monday, &[("b", "B"), ("a", "A")]));
+        merge_remote_feed(
+            &mut conn,
+            feed_id,
+            fetched(&built_at(monday, &[("b", "B"), ("a", "A")])),
+        )
+        .unwrap();
+
+        // an edit the feed did not bump its date for is not even looked at
+        let refresh_summary = merge_remote_feed(
+            &mut conn,
+            feed_id,
+            fetched(&built_at(monday, &[("b", "B edited"), ("a", "A")])),
+        )
+        .unwrap();
+        assert_eq!(refresh_summary.stats.fetched, 2);
+        assert_eq!(refresh_summary.stats.duplicates, 2);
+        assert!(refresh_summary.updated.is_empty());
+        assert!(get_feed(&conn, feed_id).unwrap().refreshed_at.is_some());
+
+        // a new date compares the entries again
+        let refresh_summary = merge_remote_feed(
+            &mut conn,
+            feed_id,
+            fetched(&built_at(tuesday, &[("b", "B edited"), ("a", "A")])),
+        )
+        .unwrap();
+        assert_eq!(refresh_summary.updated.len(), 1);
+
+        // a feed that keeps its date while adding entries lists different ones first
+        let refresh_summary = merge_remote_feed(
+            &mut conn,
+            feed_id,
+            fetched(&built_at(
+                tuesday,
+                &[("c", "C"), ("b", "B edited"), ("a", "A")],
+            )),
+        )
+        .unwrap();
+        assert_eq!(refresh_summary.inserted.len(), 1);
+        assert_eq!(entries_count(&conn), 3);
+    }
+
+    #[test]
+    fn it_reparses_the_stored_body_of_feeds_that_keep_it() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        set_feed_keeps_raw_body(&conn, feed_id, true).unwrap();
+        assert!(get_feed(&conn, feed_id).unwrap().keeps_raw_body);
+        assert!(reparse_feed(&conn, feed_id).is_err());
+
+        let xml = rss_fixture(3).replace("<title>Example</title>", "<title>Odd</title>");
+        merge_remote_feed(&mut conn, feed_id, fetched(&xml)).unwrap();
+        merge_remote_feed(&mut conn, other_feed_id, fetched(&xml)).unwrap();
+
+        assert_eq!(get_feed_raw_body(&conn, feed_id).unwrap(), Some(xml));
+        // keeping the body is opt-in
+        assert_eq!(get_feed_raw_body(&conn, other_feed_id).unwrap(), None);
+
+        let (feed, entries) = reparse_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Odd"));
+        assert_eq!(feed.feed_link, get_feed(&conn, feed_id).unwrap().feed_link);
+        assert_eq!(
+            entries
+                .iter()
+                .filter_map(|entry| entry.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec!["Entry 0", "Entry 1", "Entry 2"]
+        );
+
+        set_feed_keeps_raw_body(&conn, feed_id, false).unwrap();
+        assert_eq!(get_feed_raw_body(&conn, feed_id).unwrap(), None);
+    }
+
+    #[test]
+    fn it_stores_enclosures() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                r#"<item><title>Episode 1</title><enclosure url="https://example.com/1.mp3" type="audio/mpeg" length="12345"/></item>"#.to_owned(),
+                r#"<item><title>Episode 2</title><enclosure url="https://example.com/2.mp3" type="audio/mpeg" length="0"/></item>"#.to_owned(),
+                rss_item("Post", None, None),
+            ]),
+        );
+
+        let entries = get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .into_iter()
+            .map(|meta| get_entry(&conn, meta.id).unwrap())
+            .map(|entry| (entry.title.unwrap(), entry.enclosure))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(
+            entries["Episode 1"],
+            Some(Enclosure {
+                url: "https://example.com/1.mp3".to_owned(),
+                mime_type: Some("audio/mpeg".to_owned()),
+                length: Some(12345),
+            })
+        );
+        assert_eq!(entries["Episode 2"].as_ref().unwrap().length, None);
+        assert_eq!(entries["Post"], None);
+    }
+
+    const JSON_FEED: &str = r#"{
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": "JSON Example",
+        "home_page_url": "https://example.org/",
+        "feed_url": "https://example.org/feed.json",
+        "items": [
+            {
+                "id": "2",
+                "url": "https://example.org/second-item",
+                "title": "Second",
+                "content_html": "<p>Hello, world!</p>",
+                "content_text": "Hello, world!",
+                "summary": "A greeting",
+                "date_published": "2023-02-01T12:00:00-05:00",
+                "authors": [{ "name": "Jo" }],
+                "attachments": [
+                    { "url": "https://example.org/2.mp3", "mime_type": "audio/mpeg", "size_in_bytes": 1234 }
+                ]
+            },
+            {
+                "id": 1,
+                "external_url": "https://elsewhere.example.com/",
+                "content_text": "No title here",
+                "author": { "name": "Sam" },
+                "unknown_field": [1, 2, 3]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn it_parses_json_feeds() {
+        let FeedAndEntries { feed, entries, .. } = FeedAndEntries::from_str(JSON_FEED).unwrap();
+
+        assert!(matches!(feed.feed_kind, FeedKind::Json));
+        assert_eq!(feed.title.as_deref(), Some("JSON Example"));
+        assert_eq!(feed.link.as_deref(), Some("https://example.org/"));
+        assert_eq!(
+            feed.self_link.as_deref(),
+            Some("https://example.org/feed.json")
+        );
+        assert_eq!(entries.len(), 2);
+
+        let second = &entries[0];
+        assert_eq!(second.guid.as_deref(), Some("2"));
+        assert_eq!(second.title.as_deref(), Some("Second"));
+        assert_eq!(
+            second.link.as_deref(),
+            Some("https://example.org/second-item")
+        );
+        assert_eq!(second.content.as_deref(), Some("<p>Hello, world!</p>"));
+        assert_eq!(second.description.as_deref(), Some("A greeting"));
+        assert_eq!(second.author.as_deref(), Some("Jo"));
+        assert_eq!(
+            second.published_at,
+            Some("2023-02-01T17:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(
+            second.enclosure,
+            Some(Enclosure {
+                url: "https://example.org/2.mp3".to_owned(),
+                mime_type: Some("audio/mpeg".to_owned()),
+                length: Some(1234),
+            })
+        );
+
+        let untitled = &entries[1];
+        assert_eq!(untitled.guid.as_deref(), Some("1"));
+        assert_eq!(untitled.title, None);
+        assert_eq!(untitled.content.as_deref(), Some("No title here"));
+        assert_eq!(
+            untitled.link.as_deref(),
+            Some("https://elsewhere.example.com/")
+        );
+        assert_eq!(untitled.author.as_deref(), Some("Sam"));
+        assert_eq!(untitled.published_at, None);
+    }
+
+    #[test]
+    fn it_stores_json_feeds() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, JSON_FEED);
+
+        assert!(matches!(
+            get_feed(&conn, feed_id).unwrap().feed_kind,
+            FeedKind::Json
+        ));
+        assert_eq!(entries_count(&conn), 2);
+    }
+
+    #[test]
+    fn it_does_not_parse_json_that_is_not_a_json_feed() {
+        for json in [r#"{"version": "1.0", "items": []}"#, r#"{"title": "#] {
+            let e = FeedAndEntries::from_str(json).err().unwrap();
+            assert!(
+                matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_reads_atom_enclosure_links() {
+        let feed_and_entries = FeedAndEntries::from_str(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Example</title>
+              <id>urn:example</id>
+              <updated>2023-01-01T00:00:00Z</updated>
+              <entry>
+                <title>Episode 1</title>
+                <id>urn:example:1</id>
+                <updated>2023-01-01T00:00:00Z</updated>
+                <link rel="alternate" href="https://example.com/1"/>
+                <link rel="enclosure" type="audio/ogg" length="42" href="https://example.com/1.ogg"/>
+              </entry>
+            </feed>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            feed_and_entries.entries[0].enclosure,
+            Some(Enclosure {
+                url: "https://example.com/1.ogg".to_owned(),
+                mime_type: Some("audio/ogg".to_owned()),
+                length: Some(42),
+            })
+        );
+    }
+
+    #[test]
+    fn refreshing_updates_the_title_and_link_of_a_feed_that_changed_them() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&rss_fixture(1))).unwrap();
+        let unchanged = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(unchanged.title.as_deref(), Some("Example"));
+
+        let retitled = rss_fixture(1)
+            .replace("<title>Example</title>", "<title>Retitled</title>")
+            .replace(
+                "<link>https://example.com</link>",
+                "<link>https://example.org</link>",
+            );
+        merge_remote_feed(&mut conn, feed_id, fetched(&retitled)).unwrap();
+
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Retitled"));
+        assert_eq!(feed.link.as_deref(), Some("https://example.org"));
+        assert!(feed.updated_at > unchanged.updated_at);
+
+        // a refresh that changes nothing is not an edit
+        merge_remote_feed(&mut conn, feed_id, fetched(&retitled)).unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().updated_at,
+            feed.updated_at
+        );
+    }
+
+    #[test]
+    fn a_renamed_feed_keeps_its_title_through_refreshes() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        rename_feed(&conn, feed_id, "  My favourite feed ").unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().title.as_deref(),
+            Some("My favourite feed")
+        );
+
+        let retitled = rss_fixture(1).replace("<title>Example</title>", "<title>Retitled</title>");
+        merge_remote_feed(&mut conn, feed_id, fetched(&retitled)).unwrap();
+        merge_remote_feed(&mut conn, other_feed_id, fetched(&retitled)).unwrap();
+
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().title.as_deref(),
+            Some("My favourite feed")
+        );
+        // feeds that were not renamed follow the feed's own title
+        assert_eq!(
+            get_feed(&conn, other_feed_id).unwrap().title.as_deref(),
+            Some("Retitled")
+        );
+        // feeds are sorted by the title people see
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![feed_id, other_feed_id]);
+
+        // an empty title undoes the rename
+        rename_feed(&conn, feed_id, "").unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().title.as_deref(),
+            Some("Retitled")
+        );
+    }
+
+    #[test]
+    fn feeds_are_listed_in_the_positions_they_are_moved_to() {
+        let mut conn = test_db();
+        let mut feed = |title: &str| {
+            let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+            rename_feed(&conn, feed_id, title).unwrap();
+            feed_id
+        };
+        let (a, b, c) = (feed("a"), feed("b"), feed("c"));
+
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![a, b, c]);
+
+        set_feed_position(&mut conn, c, 0).unwrap();
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![c, a, b]);
+
+        set_feed_position(&mut conn, a, 99).unwrap();
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![c, b, a]);
+
+        let new_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        rename_feed(&conn, new_feed_id, "0").unwrap();
+        set_feed_position(&mut conn, b, 1).unwrap();
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![c, b, a, new_feed_id]);
+        assert_eq!(
+            get_feeds(&conn)
+                .unwrap()
+                .into_iter()
+                .map(|feed| feed.id)
+                .collect::<Vec<_>>(),
+            vec![c, b, a, new_feed_id]
+        );
+
+        let mut statement = conn
+            .prepare("SELECT sort_position FROM feeds WHERE sort_position IS NOT NULL ORDER BY 1")
+            .unwrap();
+        let positions = statement
+            .query_map([], |row| row.get::<_, i64>(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(positions, vec![0, 1, 2, 3]);
+        drop(statement);
+
+        assert!(matches!(
+            set_feed_position(&mut conn, 99, 0)
+                .unwrap_err()
+                .downcast_ref::<Error>(),
+            Some(Error::FeedNotFound(99))
+        ));
+
+        reset_feed_positions(&conn).unwrap();
+        assert_eq!(get_feed_ids(&conn).unwrap(), vec![new_feed_id, a, b, c]);
+    }
+
+    #[test]
+    fn it_turns_html_into_plain_text() {
+        assert_eq!(
+            html_to_plain_text(
+                "<div><p>First <b>bold <i>and italic</i></b> paragraph.</p>\n\n\n<p>Second<br>line<br/>break</p></div>"
+            )
+            .as_deref(),
+            Some("First bold and italic paragraph.\n\nSecond\nline\nbreak")
+        );
+        assert_eq!(
+            html_to_plain_text("Tom &amp; Jerry &lt;3 &quot;cheese&quot; &#8212; caf&eacute;")
+                .as_deref(),
+            Some("Tom & Jerry <3 \"cheese\" — café")
+        );
+        assert_eq!(
+            html_to_plain_text(
+                r#"<a href="https://example.com">a link</a> and <img src="x.png"> an image"#
+            )
+            .as_deref(),
+            Some("a link and an image")
+        );
+        assert_eq!(
+            html_to_plain_text("<p>unclosed <em>tags <p>everywhere").as_deref(),
+            Some("unclosed tags\n\neverywhere")
+        );
+        assert_eq!(
+            html_to_plain_text("just text").as_deref(),
+            Some("just text")
+        );
+        assert_eq!(html_to_plain_text("<p> </p><br><div></div>"), None);
+        assert_eq!(html_to_plain_text(""), None);
+    }
+
+    #[test]
+    fn plain_text_description_falls_back_to_content() {
+        let feed_and_entries = FeedAndEntries::from_str(&rss_channel(&[
+            "<item><title>a</title><description>&lt;p&gt;the &lt;b&gt;description&lt;/b&gt;&lt;/p&gt;</description></item>".to_owned(),
+            r#"<item xmlns:content="http://purl.org/rss/1.0/modules/content/"><title>b</title><description><![CDATA[<p></p>]]></description><content:encoded><![CDATA[<p>the content</p>]]></content:encoded></item>"#.to_owned(),
+        ]))
+        .unwrap();
+
+        let descriptions = feed_and_entries
+            .entries
+            .iter()
+            .map(|entry| entry.plain_text_description())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            descriptions,
+            vec![
+                Some("the description".to_owned()),
+                Some("the content".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tells_entries_with_their_content_from_ones_with_a_summary() {
+        let feed_and_entries = FeedAndEntries::from_str(&rss_channel(&[
+            r#"<item xmlns:content="http://purl.org/rss/1.0/modules/content/"><title>content only</title><content:encoded>the content</content:encoded></item>"#.to_owned(),
+            "<item><title>description only</title><description>the summary</description></item>".to_owned(),
+            r#"<item xmlns:content="http://purl.org/rss/1.0/modules/content/"><title>both</title><description>the summary</description><content:encoded>the content</content:encoded></item>"#.to_owned(),
+            r#"<item xmlns:content="http://purl.org/rss/1.0/modules/content/"><title>blank content</title><description>the summary</description><content:encoded> </content:encoded></item>"#.to_owned(),
+            "<item><title>neither</title></item>".to_owned(),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            feed_and_entries
+                .entries
+                .iter()
+                .map(|entry| (entry.has_full_content(), entry.best_body()))
+                .collect::<Vec<_>>(),
+            vec![
+                (true, Some("the content")),
+                (false, Some("the summary")),
+                (true, Some("the content")),
+                (false, Some("the summary")),
+                (false, None),
+            ]
+        );
+
+        let truncated = Entry {
+            truncated: true,
+            ..feed_and_entries.entries[0].clone()
+        };
+        assert!(!truncated.has_full_content());
+        assert_eq!(truncated.best_body(), Some("the content"));
+    }
+
+    #[test]
+    fn it_converts_entries_to_markdown() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                r#"<item>
+                  <title>A post</title>
+                  <link>https://example.com/a-post</link>
+                  <author>jane@example.com (Jane)</author>
+                  <pubDate>Mon, 02 Jan 2023 00:00:00 GMT</pubDate>
+                  <description>the summary</description>
+                  <content:encoded xmlns:content="http://purl.org/rss/1.0/modules/content/"><![CDATA[<h2>Part one</h2><p>Some <em>emphasis</em> and <a href="https://example.com/other">a link</a>.</p><ul><li>one</li><li>two</li></ul>]]></content:encoded>
+                </item>"#
+                    .to_owned(),
+                r#"<item>
+                  <title>Only a description</title>
+                  <description><![CDATA[<p>The <strong>whole</strong> thing.</p>]]></description>
+                </item>"#
+                    .to_owned(),
+            ]),
+        );
+        let entries = query_entries(&conn, feed_id, &EntryQuery::default()).unwrap();
+        let entry = |title: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.title.as_deref() == Some(title))
+                .unwrap()
+        };
+
+        assert_eq!(
+            entry("A post").to_markdown(),
+            "# A post
+
+*Jane, 2023-01-02*
+
+## Part one
+
+Some *emphasis* and [a link][1].
+
+* one
+* two
+
+[1]: https://example.com/other
+
+[Source](https://example.com/a-post)
+"
+        );
+
+        assert_eq!(
+            entry("Only a description").to_markdown(),
+            "# Only a description\n\nThe **whole** thing.\n"
+        );
+    }
+
+    #[test]
+    fn query_entries_filters_sorts_and_limits() {
+        let mut conn = test_db();
+        let dated_item = |title: &str, pub_date: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><pubDate>{pub_date}</pubDate></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                dated_item("b", "Tue, 03 Jan 2023 00:00:00 GMT"),
+                dated_item("a", "Mon, 02 Jan 2023 00:00:00 GMT"),
+                rss_item("undated", Some("https://example.com/undated"), None),
+                dated_item("c", "Wed, 04 Jan 2023 00:00:00 GMT"),
+            ]),
+        );
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        let id = |title: &str| -> EntryId {
+            conn.query_row(
+                "SELECT id FROM entries WHERE feed_id = ?1 AND title = ?2",
+                params![feed_id, title],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        mark_entry_read(&conn, id("a")).unwrap();
+        mark_entry_read(&conn, id("c")).unwrap();
+        star_entry(&conn, id("a")).unwrap();
+        star_entry(&conn, id("b")).unwrap();
+
+        let titles = |query: EntryQuery| {
+            query_entries(&conn, feed_id, &query)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.title.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        for (unread_only, starred_only, newest_first) in [
+            (false, false, vec!["c", "b", "a", "undated"]),
+            (true, false, vec!["b", "undated"]),
+            (false, true, vec!["b", "a"]),
+            (true, true, vec!["b"]),
+        ] {
+            let query = EntryQuery {
+                unread_only,
+                starred_only,
+                ..Default::default()
+            };
+
+            assert_eq!(titles(query.clone()), newest_first, "{query:?}");
+
+            let mut oldest_first = newest_first.clone();
+            oldest_first.retain(|title| *title != "undated");
+            oldest_first.reverse();
+            if newest_first.contains(&"undated") {
+                oldest_first.push("undated");
+            }
+
+            let query = EntryQuery {
+                sort: SortOrder::OldestFirst,
+                ..query
+            };
+            assert_eq!(titles(query.clone()), oldest_first, "{query:?}");
+        }
+
+        assert_eq!(
+            titles(EntryQuery {
+                limit: Some(2),
+                ..Default::default()
+            }),
+            vec!["c", "b"]
+        );
+        // whatever their dates
+        assert_eq!(
+            titles(EntryQuery {
+                sort: SortOrder::UnreadFirst,
+                ..Default::default()
+            }),
+            vec!["b", "undated", "c", "a"]
+        );
+        // the order the feed listed them in, as they were stored in one go
+        assert_eq!(
+            titles(EntryQuery {
+                sort: SortOrder::FirstSeen,
+                ..Default::default()
+            }),
+            vec!["b", "a", "undated", "c"]
+        );
+        assert_eq!(
+            query_entries(&conn, other_feed_id, &EntryQuery::default())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn refreshing_truncates_content_beyond_the_feeds_max_content_bytes() {
+        let item = |title: &str, description: &str| {
+            format!(
+                "<item><title>{title}</title><guid>{title}</guid><description>{description}</description></item>"
+            )
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[item("before", "long enough")]));
+
+        set_feed_max_content_bytes(&conn, feed_id, Some(12)).unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().max_content_bytes,
+            Some(12)
+        );
+
+        let updated = rss_channel(&[
+            item("before", "long enough, and longer"),
+            item("after", "fiancée, and more"),
+            item("short", "short"),
+        ]);
+        merge_remote_feed(&mut conn, feed_id, fetched(&updated)).unwrap();
+
+        let entries = query_entries(&conn, feed_id, &EntryQuery::default()).unwrap();
+        let entry = |title: &str| {
+            let entry_id = entries
+                .iter()
+                .find(|entry| entry.title.as_deref() == Some(title))
+                .unwrap()
+                .id;
+            get_entry(&conn, entry_id).unwrap()
+        };
+
+        // the marker counts towards the limit
+        let before = entry("before");
+        assert_eq!(before.description.as_deref(), Some("long e […]"));
+        assert!(before.description.unwrap().len() <= 12);
+        assert!(before.truncated);
+        // "é" is two bytes, so it is cut before rather than through
+        let after = entry("after");
+        assert_eq!(after.description.as_deref(), Some("fianc […]"));
+        assert!(after.description.unwrap().len() <= 12);
+        assert!(after.truncated);
+        let short = entry("short");
+        assert_eq!(short.description.as_deref(), Some("short"));
+        assert!(!short.truncated);
+
+        // comparing the whole content finds nothing new to update
+        let report = merge_remote_feed(&mut conn, feed_id, fetched(&updated)).unwrap();
+        assert!(report.updated.is_empty(), "{report:?}");
+
+        // too small a limit for the marker cuts without it
+        assert_eq!(
+            truncate_content(Some("long enough"), Some(4)),
+            (Some(Cow::from("long")), true)
+        );
+
+        set_feed_max_content_bytes(&conn, feed_id, Some(0)).unwrap();
+        assert_eq!(get_feed(&conn, feed_id).unwrap().max_content_bytes, None);
+    }
+
+    #[test]
+    fn refreshing_tells_entries_apart_by_the_feeds_dedupe_strategy() {
+        let item = |title: &str, link: &str, guid: &str| {
+            format!(
+                "<item><title>{title}</title><link>{link}</link><guid>{guid}</guid><pubDate>Mon, 02 Jan 2023 00:00:00 GMT</pubDate></item>"
+            )
+        };
+
+        // how many entries the feed has after each version of it is fetched in turn
+        let entries_len = |dedupe_strategy: DedupeStrategy, versions: &[Vec<String>]| {
+            let mut conn = test_db();
+            let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+            set_feed_dedupe_strategy(&conn, feed_id, dedupe_strategy).unwrap();
+            for version in versions {
+                merge_remote_feed(&mut conn, feed_id, fetched(&rss_channel(version))).unwrap();
+            }
+            entries_count(&conn)
+        };
+
+        // every entry links to the home page
+        let shared_links = [
+            vec![
+                item("a", "https://example.com/", "a"),
+                item("b", "https://example.com/", "b"),
+            ],
+            vec![item("c", "https://example.com/", "c")],
+        ];
+        // guids change on every fetch
+        let changing_guids = [
+            vec![
+                item("a", "https://example.com/a", "1-a"),
+                item("b", "https://example.com/b", "1-b"),
+            ],
+            vec![
+                item("a", "https://example.com/a", "2-a"),
+                item("b", "https://example.com/b", "2-b"),
+            ],
+        ];
+        // so do links
+        let changing_guids_and_links = [
+            vec![item("a", "https://example.com/1/a", "1-a")],
+            vec![item("a", "https://example.com/2/a", "2-a")],
+        ];
+
+        assert_eq!(entries_len(DedupeStrategy::Guid, &shared_links), 3);
+        assert_eq!(entries_len(DedupeStrategy::Link, &shared_links), 1);
+
+        assert_eq!(entries_len(DedupeStrategy::Link, &changing_guids), 2);
+        assert_eq!(entries_len(DedupeStrategy::Guid, &changing_guids), 4);
+        assert_eq!(
+            entries_len(DedupeStrategy::GuidThenLink, &changing_guids),
+            4
+        );
+
+        assert_eq!(
+            entries_len(DedupeStrategy::TitleDate, &changing_guids_and_links),
+            1
+        );
+        for dedupe_strategy in [
+            DedupeStrategy::GuidThenLink,
+            DedupeStrategy::Guid,
+            DedupeStrategy::Link,
+        ] {
+            assert_eq!(entries_len(dedupe_strategy, &changing_guids_and_links), 2);
+        }
+    }
+
+    #[test]
+    fn refreshing_a_feed_that_rotates_guids_keeps_one_entry_per_title() {
+        let item = |title: &str, build: usize, pub_date: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{build}/post</link><guid>{build}-{title}</guid><pubDate>{pub_date}</pubDate></item>"
+            )
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        set_feed_dedupe_strategy(&conn, feed_id, DedupeStrategy::TitleDate).unwrap();
+
+        // every build has new guids and links, and the dates move with the build time
+        let builds = [
+            vec![
+                item("A post", 1, "Mon, 02 Jan 2023 00:00:00 GMT"),
+                item("Daily digest", 1, "Mon, 02 Jan 2023 06:00:00 GMT"),
+            ],
+            vec![
+                item("A post", 2, "Mon, 02 Jan 2023 01:00:00 GMT"),
+                item("a post!", 2, "Mon, 02 Jan 2023 01:00:00 GMT"),
+                item("Daily digest", 2, "Mon, 02 Jan 2023 07:00:00 GMT"),
+            ],
+            vec![
+                item("A  Post", 3, "Mon, 02 Jan 2023 02:00:00 GMT"),
+                // the next day's digest is a new entry
+                item("Daily digest", 3, "Tue, 03 Jan 2023 06:00:00 GMT"),
+                item("Daily digest", 3, "Mon, 02 Jan 2023 08:00:00 GMT"),
+            ],
+        ];
+
+        for build in &builds {
+            merge_remote_feed(&mut conn, feed_id, fetched(&rss_channel(build))).unwrap();
+        }
+
+        let mut pub_dates = get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.pub_date.unwrap())
+            .collect::<Vec<_>>();
+        pub_dates.sort();
+
+        // one "A post", however its title is written, and a digest for each day
+        assert_eq!(
+            pub_dates,
+            vec![
+                "Mon, 02 Jan 2023 00:00:00 GMT",
+                "Mon, 02 Jan 2023 06:00:00 GMT",
+                "Tue, 03 Jan 2023 06:00:00 GMT",
+            ]
+        );
+    }
+
+    #[test]
+    fn refreshing_does_not_add_purged_entries_again_by_title_and_date() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        set_feed_dedupe_strategy(&conn, feed_id, DedupeStrategy::TitleDate).unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().dedupe_strategy,
+            DedupeStrategy::TitleDate
+        );
+
+        let version = |n: usize| {
+            rss_channel(&[format!(
+                "<item><title>a</title><guid>{n}</guid><pubDate>Mon, 02 Jan 2023 00:00:00 GMT</pubDate></item>"
+            )])
+        };
+        merge_remote_feed(&mut conn, feed_id, fetched(&version(1))).unwrap();
+        mark_feed_read(&conn, feed_id).unwrap();
+        purge_read_entries_older_than(&mut conn, Utc::now() + chrono::Duration::days(1)).unwrap();
+        assert_eq!(entries_count(&conn), 0);
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&version(2))).unwrap();
+        assert_eq!(entries_count(&conn), 0);
+
+        assert!(set_feed_dedupe_strategy(&conn, 1000, DedupeStrategy::Link).is_err());
+    }
+
+    #[test]
+    fn paging_after_an_entry_has_no_overlaps_or_gaps() {
+        let dated_item = |title: &str, pub_date: &str| {
+            format!("<item><title>{title}</title><guid>{title}</guid><pubDate>{pub_date}</pubDate></item>")
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                dated_item("a", "Wed, 04 Jan 2023 00:00:00 GMT"),
+                dated_item("b", "Tue, 03 Jan 2023 00:00:00 GMT"),
+                // published at the same time as "b"
+                dated_item("c", "Tue, 03 Jan 2023 00:00:00 GMT"),
+                rss_item("undated 1", None, Some("undated 1")),
+                dated_item("d", "Mon, 02 Jan 2023 00:00:00 GMT"),
+                rss_item("undated 2", None, Some("undated 2")),
+            ]),
+        );
+
+        let titles = |entries: &[Entry]| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.title.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut paged = vec![];
+        let mut page = get_entries_after(&conn, feed_id, None, None, 2).unwrap();
+        while !page.is_empty() {
+            paged.extend(titles(&page));
+
+            // newer than everything paged so far
+            merge_remote_feed(
+                &mut conn,
+                feed_id,
+                fetched(&rss_channel(&[dated_item(
+                    &format!("new {}", paged.len()),
+                    "Thu, 05 Jan 2023 00:00:00 GMT",
+                )])),
+            )
+            .unwrap();
+
+            let last = page.last().unwrap();
+            page = get_entries_after(&conn, feed_id, last.published_at, Some(last.id), 2).unwrap();
+        }
+
+        assert_eq!(paged, vec!["a", "c", "b", "d", "undated 2", "undated 1"]);
+    }
+
+    #[test]
+    fn it_decodes_entities_and_collapses_whitespace_in_titles_and_authors() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                "<item><title>Foo  &amp;  Bar\n</title><author>Jane\n  Doe</author><guid>a</guid></item>".to_owned(),
+                "<item><title><![CDATA[Q&A; &hellip;]]></title><guid>b</guid></item>".to_owned(),
+                "<item><title> \n </title><guid>c</guid></item>".to_owned(),
+                "<item><title>AT&amp;amp;T</title><guid>d</guid></item>".to_owned(),
+                "<item><title><![CDATA[AT&amp;T &hellip;]]></title><guid>e</guid></item>".to_owned(),
+            ]),
+        );
+
+        let entries = query_entries(
+            &conn,
+            feed_id,
+            &EntryQuery {
+                sort: SortOrder::FirstSeen,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(entries[0].title.as_deref(), Some("Foo & Bar"));
+        assert_eq!(entries[0].author.as_deref(), Some("Jane Doe"));
+        // not an entity after all, so nothing is decoded
+        assert_eq!(entries[1].title.as_deref(), Some("Q&A; &hellip;"));
+        assert_eq!(entries[2].title, None);
+        // the parser decoded it already, so it is not decoded again
+        assert_eq!(entries[3].title.as_deref(), Some("AT&amp;T"));
+        // CDATA is HTML, which still has its entities
+        assert_eq!(entries[4].title.as_deref(), Some("AT&T …"));
+
+        let atom_title = |title: &str| {
+            let feed_and_entries =
+                FeedAndEntries::from_str(&ATOM_FIXTURE.replace("<title>First</title>", title))
+                    .unwrap();
+            feed_and_entries.entries[0].title.clone()
+        };
+
+        assert_eq!(
+            atom_title(r#"<title type="html">AT&amp;amp;T</title>"#).as_deref(),
+            Some("AT&T")
+        );
+        assert_eq!(
+            atom_title("<title>AT&amp;amp;T</title>").as_deref(),
+            Some("AT&amp;T")
+        );
+    }
+
+    #[test]
+    fn it_unwraps_cdata_and_strips_html_from_titles() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                "<item><title><![CDATA[Hello]]></title><guid>a</guid></item>".to_owned(),
+                "<item><title>&lt;![CDATA[Hello &amp;amp; goodbye]]&gt;</title><guid>b</guid></item>".to_owned(),
+                "<item><title><![CDATA[<b>Bold</b> <i>move</i>]]></title><guid>c</guid></item>".to_owned(),
+                "<item><title>1 &lt; 2 &lt;3</title><guid>d</guid></item>".to_owned(),
+                "<item><title><![CDATA[<img src=\"x.png\">]]></title><guid>e</guid></item>".to_owned(),
+                "<item><title>Why Vec&lt;String&gt; is &lt;b&gt;slow&lt;/b&gt;</title><guid>f</guid></item>".to_owned(),
+                "<item><title><![CDATA[Why Vec<String> is <b>slow</b>]]></title><guid>g</guid></item>".to_owned(),
+                "<item><title><![CDATA[&lt;div&gt; or <em>span</em>]]></title><guid>h</guid></item>".to_owned(),
+            ])
+            .replace(
+                "<title>Example</title>",
+                "<title>&lt;![CDATA[&lt;em&gt;Example&lt;/em&gt;]]&gt;</title>",
+            ),
+        );
+
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().title.as_deref(),
+            Some("Example")
+        );
+
+        let entries = query_entries(
+            &conn,
+            feed_id,
+            &EntryQuery {
+                sort: SortOrder::FirstSeen,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec![
+                Some("Hello"),
+                Some("Hello & goodbye"),
+                Some("Bold move"),
+                Some("1 < 2 <3"),
+                None,
+                // plain text, which looks like tags, but is not HTML
+                Some("Why Vec<String> is <b>slow</b>"),
+                // HTML, but only `<b>` is a tag
+                Some("Why Vec<String> is slow"),
+                // an escaped tag is text, not a tag
+                Some("<div> or span"),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_seen_lists_entries_in_the_order_they_were_stored() {
+        let dated_item = |title: &str, pub_date: &str| {
+            format!("<item><title>{title}</title><guid>{title}</guid><pubDate>{pub_date}</pubDate></item>")
+        };
+
+        let before = Utc::now() - chrono::Duration::seconds(1);
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[dated_item("first", "Wed, 04 Jan 2023 00:00:00 GMT")]),
+        );
+        // backdated, and undated
+        for item in [
+            dated_item("second", "Mon, 02 Jan 2023 00:00:00 GMT"),
+            rss_item("third", None, Some("third")),
+            dated_item("fourth", "Sun, 01 Jan 2023 00:00:00 GMT"),
+        ] {
+            merge_remote_feed(&mut conn, feed_id, fetched(&rss_channel(&[item]))).unwrap();
+        }
+
+        let entries = query_entries(
+            &conn,
+            feed_id,
+            &EntryQuery {
+                sort: SortOrder::FirstSeen,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .filter_map(|entry| entry.title.as_deref())
+                .collect::<Vec<_>>(),
+            vec!["first", "second", "third", "fourth"]
+        );
+
+        // `CURRENT_TIMESTAMP` is UTC, and reads back as such
+        for entry in &entries {
+            assert!(entry.inserted_at >= before, "{:?}", entry.inserted_at);
+            assert!(entry.inserted_at <= Utc::now(), "{:?}", entry.inserted_at);
+        }
+    }
+
+    #[test]
+    fn it_counts_a_feeds_entries() {
+        let mut conn = test_db();
+        let dated_item = |title: &str, pub_date: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><pubDate>{pub_date}</pubDate></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                dated_item("b", "Tue, 03 Jan 2023 00:00:00 GMT"),
+                dated_item("a", "Mon, 02 Jan 2023 00:00:00 GMT"),
+                rss_item("undated", Some("https://example.com/undated"), None),
+                dated_item("c", "Wed, 04 Jan 2023 00:00:00 GMT"),
+            ]),
+        );
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        let id = |title: &str| -> EntryId {
+            conn.query_row(
+                "SELECT id FROM entries WHERE feed_id = ?1 AND title = ?2",
+                params![feed_id, title],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        mark_entry_read(&conn, id("a")).unwrap();
+        mark_entry_read(&conn, id("c")).unwrap();
+        star_entry(&conn, id("a")).unwrap();
+        star_entry(&conn, id("b")).unwrap();
+        star_entry(&conn, id("undated")).unwrap();
+
+        assert_eq!(
+            get_feed_stats(&conn, feed_id).unwrap(),
+            FeedStats {
+                total: 4,
+                unread: 2,
+                starred: 3,
+                oldest_published: Some("2023-01-02T00:00:00Z".parse().unwrap()),
+                newest_published: Some("2023-01-04T00:00:00Z".parse().unwrap()),
+            }
+        );
+
+        assert_eq!(
+            get_feed_stats(&conn, other_feed_id).unwrap(),
+            FeedStats {
+                total: 2,
+                unread: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(get_feed_stats(&conn, 1000).unwrap(), FeedStats::default());
+    }
+
+    #[test]
+    fn it_counts_every_feed_and_entry() {
+        let mut conn = test_db();
+        assert_eq!(get_library_stats(&conn).unwrap(), LibraryStats::default());
+
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+        insert_fixture(&mut conn, &rss_fixture(2));
+        insert_fixture(&mut conn, &rss_channel(&[]));
+
+        let entry_ids = get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .into_iter()
+            .map(|entry_meta| entry_meta.id)
+            .collect::<Vec<_>>();
+        mark_entry_read(&conn, entry_ids[0]).unwrap();
+        star_entry(&conn, entry_ids[0]).unwrap();
+        star_entry(&conn, entry_ids[1]).unwrap();
+
+        assert_eq!(
+            get_library_stats(&conn).unwrap(),
+            LibraryStats {
+                feed_count: 3,
+                entry_count: 5,
+                unread_count: 4,
+                starred_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn next_and_prev_entry_step_through_the_feed_and_stop_at_the_ends() {
+        let mut conn = test_db();
+        let dated_item = |title: &str, pub_date: &str| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><pubDate>{pub_date}</pubDate></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                dated_item("b", "Tue, 03 Jan 2023 00:00:00 GMT"),
+                dated_item("a", "Mon, 02 Jan 2023 00:00:00 GMT"),
+                rss_item("undated", Some("https://example.com/undated"), None),
+                dated_item("c", "Wed, 04 Jan 2023 00:00:00 GMT"),
+            ]),
+        );
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        let id = |title: &str| -> EntryId {
+            conn.query_row(
+                "SELECT id FROM entries WHERE feed_id = ?1 AND title = ?2",
+                params![feed_id, title],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let title = |entry: Option<Entry>| entry.map(|entry| entry.title.unwrap());
+
+        let query = EntryQuery::default();
+        let next =
+            |title_of: &str| title(next_entry(&conn, feed_id, id(title_of), &query).unwrap());
+        let prev =
+            |title_of: &str| title(prev_entry(&conn, feed_id, id(title_of), &query).unwrap());
+
+        assert_eq!(next("c").as_deref(), Some("b"));
+        assert_eq!(next("b").as_deref(), Some("a"));
+        assert_eq!(next("a").as_deref(), Some("undated"));
+        assert_eq!(next("undated"), None);
+
+        assert_eq!(prev("undated").as_deref(), Some("a"));
+        assert_eq!(prev("b").as_deref(), Some("c"));
+        assert_eq!(prev("c"), None);
+
+        mark_entry_read(&conn, id("b")).unwrap();
+        mark_entry_read(&conn, id("a")).unwrap();
+        let unread_only = EntryQuery {
+            unread_only: true,
+            ..Default::default()
+        };
+
+        // from a read entry, too
+        assert_eq!(
+            title(next_entry(&conn, feed_id, id("b"), &unread_only).unwrap()).as_deref(),
+            Some("undated")
+        );
+        assert_eq!(
+            title(next_entry(&conn, feed_id, id("c"), &unread_only).unwrap()).as_deref(),
+            Some("undated")
+        );
+        assert_eq!(
+            title(prev_entry(&conn, feed_id, id("undated"), &unread_only).unwrap()).as_deref(),
+            Some("c")
+        );
+
+        let oldest_first = EntryQuery {
+            sort: SortOrder::OldestFirst,
+            ..Default::default()
+        };
+        assert_eq!(
+            title(next_entry(&conn, feed_id, id("a"), &oldest_first).unwrap()).as_deref(),
+            Some("b")
+        );
+
+        // an entry of another feed is not in this feed's list
+        let other_entry_id =
+            query_entries(&conn, other_feed_id, &EntryQuery::default()).unwrap()[0].id;
+        assert!(next_entry(&conn, feed_id, other_entry_id, &query)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn for_each_entry_visits_entries_in_order_without_collecting_them() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(50));
+        insert_fixture(&mut conn, &rss_fixture(3));
+
+        let mut count = 0;
+        let mut last_id = None;
+        for_each_entry(&conn, feed_id, &EntryQuery::default(), |entry| {
+            assert_eq!(entry.feed_id, feed_id);
+            // newest first, and they were all stored at once
+            if let Some(last_id) = last_id {
+                assert!(entry.id < last_id);
+            }
+            last_id = Some(entry.id);
+            count += 1;
+        })
+        .unwrap();
+        assert_eq!(count, 50);
+
+        let mut count = 0;
+        let query = EntryQuery {
+            limit: Some(10),
+            ..Default::default()
+        };
+        for_each_entry(&conn, feed_id, &query, |_| count += 1).unwrap();
+        assert_eq!(count, 10);
+    }
+
+    #[test]
+    fn get_all_entries_interleaves_feeds_by_publication_date() {
+        let mut conn = test_db();
+        let dated_item = |title: &str, day: u32| {
+            format!(
+                "<item><title>{title}</title><link>https://example.com/{title}</link><pubDate>{day:02} Jan 2023 00:00:00 GMT</pubDate></item>"
+            )
+        };
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[dated_item("a1", 1), dated_item("a3", 3)]),
+        );
+        let other_feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                dated_item("b4", 4),
+                rss_item("b-undated", Some("https://example.com/b-undated"), None),
+                dated_item("b2", 2),
+            ]),
+        );
+        rename_feed(&conn, other_feed_id, "B").unwrap();
+
+        let timeline = |limit: i64, offset: i64| {
+            get_all_entries(&conn, limit, offset)
+                .unwrap()
+                .into_iter()
+                .map(|(feed, entry)| {
+                    assert_eq!(feed.id, entry.feed_id);
+                    (feed.title.unwrap(), entry.title.unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+        let labelled = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(feed_title, title)| (feed_title.to_string(), title.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            timeline(10, 0),
+            labelled(&[
+                ("B", "b4"),
+                ("Example", "a3"),
+                ("B", "b2"),
+                ("Example", "a1"),
+                ("B", "b-undated"),
+            ])
+        );
+        assert_eq!(timeline(2, 1), labelled(&[("Example", "a3"), ("B", "b2")]));
+        assert!(timeline(10, 5).is_empty());
+        assert_eq!(get_feed(&conn, feed_id).unwrap().title.unwrap(), "Example");
+    }
+
+    #[test]
+    fn corrupt_rows_are_errors_not_panics() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        conn.execute("UPDATE feeds SET feed_kind = 'XML'", [])
+            .unwrap();
+        assert!(get_feeds(&conn).is_err());
+        assert!(get_feed(&conn, feed_id).is_err());
+
+        conn.execute("UPDATE entries SET inserted_at = NULL WHERE id = 2", [])
+            .unwrap();
+        assert!(get_entries_metas(&conn, &ReadMode::All, feed_id).is_err());
+        assert!(get_entry(&conn, 2).is_err());
+        assert!(query_entries(&conn, feed_id, &EntryQuery::default()).is_err());
+
+        // a blob where a link should be text
+        conn.execute("UPDATE entries SET link = X'FF' WHERE id = 1", [])
+            .unwrap();
+        assert!(get_entries_links(&conn, &ReadMode::All, feed_id).is_err());
+    }
+
+    fn rss_fixture_with_hints(hints: &str) -> String {
+        rss_fixture(1)
+            .replace(
+                r#"<rss version="2.0">"#,
+                r#"<rss version="2.0" xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">"#,
+            )
+            .replace(
+                "<title>Example</title>",
+                &format!("<title>Example</title>{hints}"),
+            )
+    }
+
+    #[test]
+    fn it_reads_how_often_feeds_ask_to_be_fetched() {
+        let min_refresh_interval = |hints: &str| {
+            FeedAndEntries::from_str(&rss_fixture_with_hints(hints))
+                .unwrap()
+                .feed
+                .min_refresh_interval
+        };
+        let minutes = |minutes: u64| Some(Duration::from_secs(minutes * 60));
+
+        assert_eq!(min_refresh_interval(""), None);
+        assert_eq!(min_refresh_interval("<ttl>90</ttl>"), minutes(90));
+        assert_eq!(min_refresh_interval("<ttl>soon</ttl>"), None);
+        assert_eq!(
+            min_refresh_interval(
+                "<sy:updatePeriod>hourly</sy:updatePeriod><sy:updateFrequency>2</sy:updateFrequency>"
+            ),
+            minutes(30)
+        );
+        assert_eq!(
+            min_refresh_interval("<ttl>10</ttl><sy:updatePeriod>daily</sy:updatePeriod>"),
+            minutes(24 * 60)
+        );
+        assert_eq!(
+            min_refresh_interval("<sy:updatePeriod>yearly</sy:updatePeriod>"),
+            Some(MAX_REFRESH_INTERVAL)
+        );
+        assert_eq!(
+            min_refresh_interval("<ttl>307445734561825861</ttl>"),
+            Some(MAX_REFRESH_INTERVAL)
+        );
+    }
+
+    #[test]
+    fn refresh_intervals_the_user_sets_take_the_place_of_the_feeds() {
+        let mut conn = test_db();
+        let now = "2023-06-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        let every_15_minutes = insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>60</ttl>"));
+        let daily = insert_fixture(&mut conn, &rss_fixture_with_hints(""));
+        let as_the_feed_asks = insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>60</ttl>"));
+
+        set_feed_refresh_interval(&conn, every_15_minutes, Some(Duration::from_secs(15 * 60)))
+            .unwrap();
+        set_feed_refresh_interval(&conn, daily, Some(Duration::from_secs(24 * 60 * 60))).unwrap();
+        set_feed_refresh_interval(&conn, as_the_feed_asks, Some(Duration::from_secs(60))).unwrap();
+        set_feed_refresh_interval(&conn, as_the_feed_asks, None).unwrap();
+
+        assert_eq!(
+            get_feed(&conn, every_15_minutes).unwrap().refresh_interval,
+            Some(Duration::from_secs(15 * 60))
+        );
+        assert_eq!(
+            get_feed(&conn, as_the_feed_asks).unwrap().refresh_interval,
+            None
+        );
+
+        conn.execute("UPDATE feeds SET refreshed_at = ?1", [now])
+            .unwrap();
+
+        let due_after = |minutes: i64| {
+            let mut due =
+                feeds_due_for_refresh(&conn, now + chrono::Duration::minutes(minutes)).unwrap();
+            due.sort();
+            due
+        };
+
+        assert!(due_after(10).is_empty());
+        assert_eq!(due_after(20), vec![every_15_minutes]);
+        assert_eq!(due_after(90), vec![every_15_minutes, as_the_feed_asks]);
+        assert_eq!(
+            due_after(25 * 60),
+            vec![every_15_minutes, daily, as_the_feed_asks]
+        );
+
+        assert!(set_feed_refresh_interval(
+            &conn,
+            daily,
+            Some(Duration::from_secs(10_000_000_000_000))
+        )
+        .is_err());
+
+        // an interval too long to add to a date, from before they were limited
+        conn.execute(
+            "UPDATE feeds SET refresh_interval_seconds = ?2 WHERE id = ?1",
+            params![daily, i64::MAX],
+        )
+        .unwrap();
+        assert_eq!(due_after(25 * 60), vec![every_15_minutes, as_the_feed_asks]);
+    }
+
+    #[test]
+    fn it_finds_feeds_due_for_refresh() {
+        let mut conn = test_db();
+        let now = "2023-06-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let minutes_ago = |minutes: i64| now - chrono::Duration::minutes(minutes);
+
+        let never_refreshed = insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>60</ttl>"));
+        let without_hints = insert_fixture(&mut conn, &rss_fixture_with_hints(""));
+        let hourly_refreshed_recently =
+            insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>60</ttl>"));
+        let hourly_refreshed_long_ago =
+            insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>60</ttl>"));
+        let half_hourly_refreshed_recently =
+            insert_fixture(&mut conn, &rss_fixture_with_hints("<ttl>30</ttl>"));
+
+        for (feed_id, refreshed_at) in [
+            (without_hints, minutes_ago(1)),
+            (hourly_refreshed_recently, minutes_ago(45)),
+            (hourly_refreshed_long_ago, minutes_ago(61)),
+            (half_hourly_refreshed_recently, minutes_ago(45)),
+        ] {
+            conn.execute(
+                "UPDATE feeds SET refreshed_at = ?2 WHERE id = ?1",
+                params![feed_id, refreshed_at],
+            )
+            .unwrap();
+        }
+
+        let mut due = feeds_due_for_refresh(&conn, now).unwrap();
+        due.sort();
+
+        assert_eq!(
+            due,
+            vec![
+                never_refreshed,
+                without_hints,
+                hourly_refreshed_long_ago,
+                half_hourly_refreshed_recently
+            ]
+        );
+        assert_eq!(
+            get_feed(&conn, hourly_refreshed_recently)
+                .unwrap()
+                .min_refresh_interval,
+            Some(Duration::from_secs(60 * 60))
+        );
+    }
+
+    #[test]
+    fn it_reads_media_rss_thumbnails() {
+        let feed = rss_channel(&[
+            r#"<item>
+              <title>Thumbnail</title>
+              <link>https://example.com/1</link>
+              <m:content url="https://example.com/1.mp4" type="video/mp4"/>
+              <m:thumbnail url="https://example.com/1.jpg" width="75" height="50"/>
+            </item>"#
+                .to_owned(),
+            r#"<item>
+              <title>Thumbnail of content in a group</title>
+              <link>https://example.com/2</link>
+              <m:group>
+                <m:content url="https://example.com/2.mp4" type="video/mp4">
+                  <m:thumbnail url="https://example.com/2.jpg"/>
+                </m:content>
+              </m:group>
+            </item>"#
+                .to_owned(),
+            r#"<item>
+              <title>Image content</title>
+              <link>https://example.com/3</link>
+              <m:content url="https://example.com/3.mp3" type="audio/mpeg"/>
+              <m:content url="https://example.com/3.png" medium="image"/>
+            </item>"#
+                .to_owned(),
+            rss_item("Nothing", Some("https://example.com/4"), None),
+        ])
+        .replace(
+            r#"<rss version="2.0">"#,
+            r#"<rss version="2.0" xmlns:m="http://search.yahoo.com/mrss/">"#,
+        );
+
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &feed);
+
+        let thumbnail_urls = (1..=4)
+            .map(|entry_id| get_entry(&conn, entry_id).unwrap().thumbnail_url)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            thumbnail_urls,
+            vec![
+                Some("https://example.com/1.jpg".to_owned()),
+                Some("https://example.com/2.jpg".to_owned()),
+                Some("https://example.com/3.png".to_owned()),
+                None,
+            ]
+        );
+
+        let atom = ATOM_FIXTURE
+            .replace(
+                r#"<feed xmlns="http://www.w3.org/2005/Atom">"#,
+                r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">"#,
+            )
+            .replace(
+                "<title>First</title>",
+                r#"<title>First</title>
+                <media:group><media:thumbnail url="https://example.com/first.jpg"/></media:group>"#,
+            );
+        let feed_and_entries = FeedAndEntries::from_str(&atom).unwrap();
+        assert_eq!(
+            feed_and_entries.entries[0].thumbnail_url.as_deref(),
+            Some("https://example.com/first.jpg")
+        );
+    }
+
+    #[test]
+    fn it_stores_entry_tags() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                r#"<item>
+                  <title>Tagged</title>
+                  <link>https://example.com/tagged</link>
+                  <category>Rust</category>
+                  <category domain="https://example.com/tags"> databases </category>
+                  <category>rust</category>
+                  <category>Rust</category>
+                  <category></category>
+                </item>"#
+                    .to_owned(),
+                rss_item("Untagged", Some("https://example.com/untagged"), None),
+            ]),
+        );
+        let other_feed_id = insert_fixture(
+            &mut conn,
+            &ATOM_FIXTURE.replace(
+                "<title>First</title>",
+                r#"<title>First</title><category term="RUST" label="The Rust language"/>"#,
+            ),
+        );
+
+        let tagged = get_entry(&conn, 1).unwrap();
+        assert_eq!(tagged.tags, vec!["Rust", "databases", "rust"]);
+        assert!(get_entry(&conn, 2).unwrap().tags.is_empty());
+
+        let rust_entries = get_entries_by_tag(&conn, "rust").unwrap();
+        assert_eq!(
+            rust_entries
+                .iter()
+                .map(|entry| (entry.feed_id, entry.title.as_deref()))
+                .collect::<Vec<_>>(),
+            // the RSS entry has no publication date, so it goes last
+            vec![(other_feed_id, Some("First")), (feed_id, Some("Tagged"))]
+        );
+        assert_eq!(get_entries_by_tag(&conn, "Databases").unwrap().len(), 1);
+        assert!(get_entries_by_tag(&conn, "go").unwrap().is_empty());
+
+        assert_eq!(list_tags(&conn).unwrap().len(), 2);
+        assert_eq!(list_tags(&conn).unwrap()[0], "databases");
+
+        unsubscribe_feed(&mut conn, feed_id).unwrap();
+        assert_eq!(list_tags(&conn).unwrap(), vec!["RUST"]);
+    }
+
+    #[test]
+    fn it_lists_feeds_without_titles() {
+        let mut conn = test_db();
+        insert_fixture(&mut conn, &rss_fixture(1));
+        conn.execute(
+            "INSERT INTO feeds (title, feed_link, feed_kind)
+            VALUES (NULL, 'https://example.com/untitled', 'RSS'), (NULL, NULL, 'RSS')",
+            [],
+        )
+        .unwrap();
+
+        let feeds = get_feeds(&conn).unwrap();
+        let mut display_titles = feeds
+            .iter()
+            .map(|feed| feed.display_title())
+            .collect::<Vec<_>>();
+        display_titles.sort();
+
+        assert_eq!(
+            display_titles,
+            vec!["(untitled)", "Example", "https://example.com/untitled"]
+        );
+    }
+
+    #[test]
+    fn it_groups_feeds_into_categories() {
+        let mut conn = test_db();
+        let news_feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        let dev_feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        let uncategorized_feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+
+        set_feed_category(&conn, news_feed_id, Some("News")).unwrap();
+        set_feed_category(&conn, dev_feed_id, Some("News")).unwrap();
+        assert_eq!(list_categories(&conn).unwrap(), vec!["News"]);
+
+        // reassign
+        set_feed_category(&conn, dev_feed_id, Some("Dev")).unwrap();
+        assert_eq!(list_categories(&conn).unwrap(), vec!["Dev", "News"]);
+        assert_eq!(
+            get_feed(&conn, dev_feed_id).unwrap().category.as_deref(),
+            Some("Dev")
+        );
+
+        let feed_ids = |category| {
+            let mut feed_ids = get_feeds_by_category(&conn, category)
+                .unwrap()
+                .into_iter()
+                .map(|feed| feed.id)
+                .collect::<Vec<_>>();
+            feed_ids.sort();
+            feed_ids
+        };
+
+        assert_eq!(feed_ids(Some("News")), vec![news_feed_id]);
+        assert_eq!(feed_ids(Some("Dev")), vec![dev_feed_id]);
+        assert_eq!(feed_ids(None), vec![uncategorized_feed_id]);
+
+        // a blank category is the same as none
+        set_feed_category(&conn, news_feed_id, Some("  ")).unwrap();
+        assert_eq!(feed_ids(None), vec![news_feed_id, uncategorized_feed_id]);
+        assert_eq!(list_categories(&conn).unwrap(), vec!["Dev"]);
+    }
+
+    #[test]
+    fn it_normalizes_feed_links() {
+        assert_eq!(
+            normalize_feed_link("  HTTPS://Example.COM:443/feed/  "),
+            "https://example.com/feed"
+        );
+        assert_eq!(
+            normalize_feed_link("http://example.com:8080/a/feed.xml?b=1"),
+            "http://example.com:8080/a/feed.xml?b=1"
+        );
+        assert_eq!(
+            normalize_feed_link("http://example.com"),
+            "http://example.com/"
+        );
+        assert_eq!(normalize_feed_link(" not a url "), "not a url");
+    }
+
+    #[test]
+    fn it_sends_credentials_when_subscribing_and_refreshing() {
+        use crate::test_server::{serve, TestResponse};
+
+        // "user:pass"
+        const AUTHORIZATION: &str = "Basic dXNlcjpwYXNz";
+
+        let elsewhere_url = serve(|request| match request.header("Authorization") {
+            Some(_) => TestResponse::status(400),
+            None => TestResponse::ok(rss_fixture(1)),
+        });
+
+        let base_url = serve(move |request| {
+            if request.header("Authorization") != Some(AUTHORIZATION) {
+                return TestResponse::status(401);
+            }
+
+            match request.path.as_str() {
+                "/feed" => TestResponse::ok(rss_fixture(2)),
+                "/elsewhere" => TestResponse::status(302)
+                    .with_header("Location", &format!("{elsewhere_url}/feed")),
+                _ => TestResponse::status(404),
+            }
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_url = format!("{base_url}/feed");
+        let credentials = Credentials {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+
+        let e = subscribe_to_feed(&http_client, &mut conn, &feed_url).unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::HttpStatus(401))
+        ));
+
+        let feed_id =
+            subscribe_to_protected_feed(&http_client, &mut conn, &feed_url, &credentials).unwrap();
+        assert_eq!(entries_count(&conn), 2);
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+
+        set_feed_credentials(&conn, feed_id, None).unwrap();
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+
+        // subscribing again with the credentials stores them again
+        assert_eq!(
+            subscribe_to_protected_feed(&http_client, &mut conn, &feed_url, &credentials).unwrap(),
+            feed_id
+        );
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+
+        // credentials are not passed on to other sites
+        subscribe_to_protected_feed(
+            &http_client,
+            &mut conn,
+            &format!("{base_url}/elsewhere"),
+            &credentials,
+        )
+        .unwrap();
+
+        assert!(!format!("{credentials:?}").contains("\"pass\""));
+    }
+
+    #[test]
+    fn it_decodes_feeds_in_the_encoding_they_declare() {
+        use crate::test_server::{serve, TestResponse};
+
+        let feed = |encoding: &str, title: &[u8]| {
+            let mut feed = format!(
+                r#"<?xml version="1.0" encoding="{encoding}"?>
+                <rss version="2.0"><channel><title>"#
+            )
+            .into_bytes();
+            feed.extend_from_slice(title);
+            feed.extend_from_slice(
+                b"</title><link>https://example.com</link><description>d</description></channel></rss>",
+            );
+            feed
+        };
+
+        // "Café Noël" in Latin-1
+        let latin_1 = feed("ISO-8859-1", b"Caf\xE9 No\xEBl");
+        // curly quotes are only in Windows-1252
+        let windows_1252 = feed("windows-1252", b"\x93Caf\xE9\x94");
+        let utf_8 = feed("UTF-8", "Café".as_bytes());
+        let mut utf_8_with_bom = b"\xEF\xBB\xBF".to_vec();
+        utf_8_with_bom.extend_from_slice(&feed("ISO-8859-1", "Café".as_bytes()));
+
+        let base_url = serve(move |request| match request.path.as_str() {
+            "/latin-1" => TestResponse::ok(latin_1.clone()),
+            "/windows-1252" => TestResponse::ok(windows_1252.clone()),
+            "/utf-8" => TestResponse::ok(utf_8.clone()),
+            "/bom" => TestResponse::ok(utf_8_with_bom.clone()),
+            // the header wins over the declaration
+            "/header" => TestResponse::ok(feed("UTF-8", b"Caf\xE9")).with_header(
+                "Content-Type",
+                "application/rss+xml; charset=\"ISO-8859-1\"",
+            ),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+
+        for (path, title) in [
+            ("/latin-1", "Café Noël"),
+            ("/windows-1252", "“Café”"),
+            ("/utf-8", "Café"),
+            ("/bom", "Café"),
+            ("/header", "Café"),
+        ] {
+            let fetch_result = fetch_feed(
+                &http_client,
+                &format!("{base_url}{path}"),
+                &CacheValidators::default(),
+                None,
+                None,
+            )
+            .unwrap();
+
+            assert_eq!(
+                fetch_result.feed_and_entries.unwrap().feed.title.as_deref(),
+                Some(title),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_decompresses_responses() {
+        use crate::test_server::{serve, TestResponse};
+        use flate2::write::{GzEncoder, ZlibEncoder};
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut gzipped = GzEncoder::new(vec![], Compression::default());
+        gzipped.write_all(rss_fixture(2).as_bytes()).unwrap();
+        let gzipped = gzipped.finish().unwrap();
+
+        let mut deflated = ZlibEncoder::new(vec![], Compression::default());
+        deflated.write_all(rss_fixture(3).as_bytes()).unwrap();
+        let deflated = deflated.finish().unwrap();
+
+        let base_url = serve(move |request| {
+            assert_eq!(request.header("Accept-Encoding"), Some("gzip, deflate"));
+
+            match request.path.as_str() {
+                "/gzip" => {
+                    TestResponse::ok(gzipped.clone()).with_header("Content-Encoding", "gzip")
+                }
+                "/deflate" => {
+                    TestResponse::ok(deflated.clone()).with_header("Content-Encoding", "deflate")
+                }
+                "/mislabeled" => {
+                    TestResponse::ok(rss_fixture(1)).with_header("Content-Encoding", "gzip")
+                }
+                _ => TestResponse::status(404),
+            }
+        });
+
+        let http_client = test_http_client();
+
+        for (path, entries_len) in [("/gzip", 2), ("/deflate", 3), ("/mislabeled", 1)] {
+            let fetch_result = fetch_feed(
+                &http_client,
+                &format!("{base_url}{path}"),
+                &CacheValidators::default(),
+                None,
+                None,
+            )
+            .unwrap();
+
+            assert_eq!(
+                fetch_result.feed_and_entries.unwrap().entries.len(),
+                entries_len,
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn subscribing_again_refreshes_the_feed() {
+        use crate::test_server::{serve, TestResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = AtomicUsize::new(0);
+        // a new entry every time
+        let base_url = serve(move |_request| {
+            TestResponse::ok(rss_fixture(2 + requests.fetch_add(1, Ordering::SeqCst)))
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed")).unwrap();
+        assert_eq!(entries_count(&conn), 2);
+
+        assert_eq!(
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed")).unwrap(),
+            feed_id
+        );
+        assert_eq!(get_feeds(&conn).unwrap().len(), 1);
+        assert_eq!(entries_count(&conn), 3);
+    }
+
+    #[test]
+    fn subscribing_to_a_feed_that_moved_recognizes_it_by_its_fingerprint() {
+        use crate::test_server::{serve, TestResponse};
+
+        let items = |guids: &[&str]| {
+            rss_channel(
+                &guids
+                    .iter()
+                    .map(|guid| rss_item(guid, None, Some(guid)))
+                    .collect::<Vec<_>>(),
+            )
+            .replace(
+                r#"<rss version="2.0">"#,
+                r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#,
+            )
+            .replace(
+                "<title>Example</title>",
+                r#"<title>Example</title>
+                <atom:link href="https://example.com/feed.xml" rel="self"/>"#,
+            )
+        };
+        let moved = items(&["c", "b", "a"]);
+        let other = items(&["x", "y"]);
+
+        let base_url = serve(move |request| match request.path.as_str() {
+            "/old" | "/new" => TestResponse::ok(moved.clone()),
+            _ => TestResponse::ok(other.clone()),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/old")).unwrap();
+        let other_feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/other")).unwrap();
+        assert_ne!(feed_id, other_feed_id);
+
+        let fingerprint = get_feed_fingerprint(&conn, feed_id).unwrap().unwrap();
+        assert_eq!(
+            FeedAndEntries::from_str(&items(&["c", "b", "a"]))
+                .unwrap()
+                .fingerprint(),
+            Some(fingerprint.clone())
+        );
+        assert_ne!(
+            get_feed_fingerprint(&conn, other_feed_id).unwrap(),
+            Some(fingerprint.clone())
+        );
+        assert_eq!(
+            find_feed_by_fingerprint(&conn, &fingerprint).unwrap(),
+            Some(feed_id)
+        );
+
+        // the same feed at a different url, with the credentials it was subscribed with
+        let credentials = Credentials {
+            username: "user".to_owned(),
+            password: "pass".to_owned(),
+        };
+        assert_eq!(
+            subscribe_to_protected_feed(
+                &http_client,
+                &mut conn,
+                &format!("{base_url}/new"),
+                &credentials
+            )
+            .unwrap(),
+            feed_id
+        );
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().feed_link,
+            Some(format!("{base_url}/new"))
+        );
+        assert_eq!(
+            get_feed_request(&conn, feed_id).unwrap().credentials,
+            Some(credentials)
+        );
+        assert_eq!(get_feeds(&conn).unwrap().len(), 2);
+        assert_eq!(entries_count(&conn), 5);
+
+        // feeds without guids have no fingerprint
+        assert_eq!(
+            FeedAndEntries::from_str(&rss_fixture(3))
+                .unwrap()
+                .fingerprint(),
+            None
+        );
+    }
+
+    #[test]
+    fn subscribing_to_feeds_that_list_the_same_entries_keeps_them_apart() {
+        use crate::test_server::{serve, TestResponse};
+
+        let items = rss_channel(&[
+            rss_item("a", None, Some("a")),
+            rss_item("b", None, Some("b")),
+        ]);
+        let self_linked = |self_link: &str| {
+            items
+                .replace(
+                    r#"<rss version="2.0">"#,
+                    r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#,
+                )
+                .replace(
+                    "<title>Example</title>",
+                    &format!(
+                        r#"<title>Example</title>
+                        <atom:link href="{self_link}" rel="self"/>"#
+                    ),
+                )
+        };
+        let rss = self_linked("https://example.com/feed.rss");
+        let atom_variant = self_linked("https://example.com/feed.atom");
+
+        let base_url = serve(move |request| match request.path.as_str() {
+            "/rss" => TestResponse::ok(rss.clone()),
+            "/atom" => TestResponse::ok(atom_variant.clone()),
+            // like the categories of a site without self links
+            _ => TestResponse::ok(items.clone()),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        for (first, second) in [("/rss", "/atom"), ("/news", "/sports")] {
+            let feed_id =
+                subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}{first}")).unwrap();
+            let other_feed_id =
+                subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}{second}")).unwrap();
+            assert_ne!(feed_id, other_feed_id);
+            assert_eq!(
+                get_feed(&conn, feed_id).unwrap().feed_link,
+                Some(format!("{base_url}{first}"))
+            );
+            assert_eq!(
+                get_feed(&conn, other_feed_id).unwrap().feed_link,
+                Some(format!("{base_url}{second}"))
+            );
+        }
+        assert_eq!(get_feeds(&conn).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn it_subscribes_to_many_feeds_at_once() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/a" | "/b" | "/c" => TestResponse::ok(rss_channel(&[rss_item(
+                &request.path,
+                Some(&format!("https://example.com{}", request.path)),
+                None,
+            )])),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let existing_feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/a")).unwrap();
+
+        let urls = ["/a", "/b", "/missing", "/c"]
+            .iter()
+            .map(|path| format!("{base_url}{path}"))
+            .chain(["not a url".to_owned()])
+            .collect::<Vec<_>>();
+
+        let mut progress = vec![];
+        let results = subscribe_many(&http_client, &mut conn, &urls, 2, |done, total| {
+            progress.push((done, total))
+        });
+
+        assert_eq!(progress, (1..=5).map(|done| (done, 5)).collect::<Vec<_>>());
+        assert_eq!(
+            results.iter().map(|(url, _)| url).collect::<Vec<_>>(),
+            urls.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(*results[0].1.as_ref().unwrap(), existing_feed_id);
+        assert!(results[1].1.is_ok());
+        assert!(matches!(
+            results[2].1.as_ref().unwrap_err().downcast_ref::<Error>(),
+            Some(Error::HttpStatus(404))
+        ));
+        assert!(results[3].1.is_ok());
+        assert!(matches!(
+            results[4].1.as_ref().unwrap_err().downcast_ref::<Error>(),
+            Some(Error::InvalidUrl(_))
+        ));
+
+        assert_eq!(get_feeds(&conn).unwrap().len(), 3);
+        assert_eq!(entries_count(&conn), 3);
+    }
+
+    #[test]
+    fn it_does_not_subscribe_to_invalid_urls() {
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        for url in [
+            "not a url",
+            "example.com/feed",
+            "htps://example.com/feed",
+            "gemini://example.com/feed",
+            "mailto:feeds@example.com",
+        ] {
+            let e = subscribe_to_feed(&http_client, &mut conn, url).unwrap_err();
+            assert!(
+                matches!(e.downcast_ref::<Error>(), Some(Error::InvalidUrl(invalid_url)) if invalid_url == url),
+                "{url}: {e:?}"
+            );
+            assert!(matches!(
+                validate_feed(&http_client, url)
+                    .unwrap_err()
+                    .downcast_ref::<Error>(),
+                Some(Error::InvalidUrl(_))
+            ));
+        }
+
+        assert!(get_feeds(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_trims_urls_before_subscribing() {
+        let base_url = serve_rss_fixture(2);
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!(" \t{base_url}/feed\n ")).unwrap();
+
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().feed_link,
+            Some(format!("{base_url}/feed"))
+        );
+    }
+
+    #[test]
+    fn it_does_not_subscribe_to_the_same_feed_twice() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/feed" => TestResponse::ok(rss_fixture(2)),
+            "/moved" => TestResponse::status(301).with_header("Location", "/feed"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/feed/")).unwrap();
+
+        for url in [
+            format!("{base_url}/feed"),
+            format!("  {}/feed/ ", base_url.to_uppercase()),
+            format!("{base_url}/moved"),
+        ] {
+            assert_eq!(
+                subscribe_to_feed(&http_client, &mut conn, &url).unwrap(),
+                feed_id,
+                "{url}"
+            );
+        }
+
+        assert_eq!(get_feeds(&conn).unwrap().len(), 1);
+        assert_eq!(entries_count(&conn), 2);
+
+        // the index backs this up
+        assert!(conn
+            .execute(
+                "INSERT INTO feeds (feed_link) VALUES (?1)",
+                [format!("{base_url}/feed")],
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn it_previews_feeds() {
+        let preview = FeedPreview::from_str(&rss_fixture(3)).unwrap();
+        assert!(matches!(preview.detected_format, FeedKind::Rss));
+        assert_eq!(preview.title.as_deref(), Some("Example"));
+        assert_eq!(preview.link.as_deref(), Some("https://example.com"));
+        assert_eq!(preview.item_count, 3);
+
+        let preview = FeedPreview::from_str(ATOM_FIXTURE).unwrap();
+        assert!(matches!(preview.detected_format, FeedKind::Atom));
+        assert_eq!(preview.title.as_deref(), Some("Example Atom"));
+        assert_eq!(preview.link.as_deref(), Some("https://example.com/"));
+        assert_eq!(preview.item_count, 2);
+
+        let e = FeedPreview::from_str("<html><body>hi</body></html>")
+            .err()
+            .unwrap();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)));
+    }
+
+    #[test]
+    fn it_validates_a_feed_url() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/feed" => TestResponse::ok(rss_fixture(2)),
+            _ => TestResponse::ok("<html></html>"),
+        });
+
+        let http_client = test_http_client();
+        let preview = validate_feed(&http_client, &format!("{base_url}/feed")).unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Example"));
+        assert_eq!(preview.item_count, 2);
+
+        let e = validate_feed(&http_client, &format!("{base_url}/page"))
+            .err()
+            .unwrap();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)));
+    }
+
+    #[test]
+    fn it_subscribes_to_the_feed_a_page_links_to() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/" => TestResponse::ok(
+                r#"<html><head><link rel="alternate" type="application/rss+xml" href="/feed"></head></html>"#,
+            ),
+            "/feed" => TestResponse::ok(rss_fixture(2)),
+            "/nothing" => TestResponse::ok("<html></html>"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id = subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/")).unwrap();
+
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().feed_link,
+            Some(format!("{base_url}/feed"))
+        );
+        assert_eq!(entries_count(&conn), 2);
+
+        let e =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/nothing")).unwrap_err();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::NotAFeed)));
+    }
+
+    #[test]
+    fn initialize_db_merges_duplicate_feeds() {
+        let mut conn = test_db();
+        conn.execute("DROP INDEX feeds_feed_link_index", [])
+            .unwrap();
+        // as if the database was from before schema versions
+        conn.pragma_update(None, "user_version", 0).unwrap();
+
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        let duplicate_feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        set_feed_link(&conn, feed_id, "https://example.com/feed");
+        set_feed_link(&conn, duplicate_feed_id, "https://EXAMPLE.com/feed/");
+        set_feed_link(&conn, other_feed_id, "https://example.com/other");
+        star_entry(&conn, 4).unwrap();
+
+        initialize_db(&mut conn).unwrap();
+
+        let feeds = get_feeds(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|feed| (feed.id, feed.feed_link.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(feeds.len(), 2);
+        assert!(feeds.contains(&(feed_id, "https://example.com/feed".to_owned())));
+        assert!(feeds.contains(&(other_feed_id, "https://example.com/other".to_owned())));
+
+        // the starred entry moved to the remaining feed, the other duplicate went away
+        assert_eq!(get_entry_meta(&conn, 4).unwrap().feed_id, feed_id);
+        assert!(get_entry_meta(&conn, 3).is_err());
+        assert_eq!(entries_count(&conn), 4);
+    }
+
+    fn entries_count(conn: &rusqlite::Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn refresh_dedupes_by_guid_even_when_links_change() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                rss_item("One", Some("https://example.com/1"), Some("guid-1")),
+                rss_item("Two", Some("https://example.com/2"), Some("guid-2")),
+            ]),
+        );
+
+        let remote = rss_channel(&[
+            rss_item("One", Some("https://example.com/1?moved"), Some("guid-1")),
+            rss_item("Two", Some("https://example.com/2?moved"), Some("guid-2")),
+            rss_item("Three", Some("https://example.com/3"), Some("guid-3")),
+        ]);
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(entries_count(&conn), 3);
+
+        // a second identical refresh adds nothing
+        merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(entries_count(&conn), 3);
+    }
+
+    #[test]
+    fn it_normalizes_entry_links() {
+        assert_eq!(
+            normalize_entry_link(
+                " https://example.com/a?utm_source=rss&b=2&fbclid=x&a=1&utm_medium=feed#comments "
+            ),
+            "https://example.com/a?a=1&b=2"
+        );
+        assert_eq!(
+            normalize_entry_link("https://example.com/a?utm_campaign=x"),
+            "https://example.com/a"
+        );
+        assert_eq!(
+            normalize_entry_link("https://example.com/a?id=2&id=1"),
+            "https://example.com/a?id=2&id=1"
+        );
+        assert_eq!(normalize_entry_link(" not a url "), "not a url");
+    }
+
+    #[test]
+    fn refresh_ignores_tracking_params_in_links() {
+        let with_utm = |utm_source: &str| {
+            rss_channel(&[rss_item(
+                "Article",
+                Some(&format!(
+                    "https://example.com/article?utm_source={utm_source}&amp;utm_medium=rss"
+                )),
+                None,
+            )])
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &with_utm("monday"));
+
+        let refresh_summary =
+            merge_remote_feed(&mut conn, feed_id, fetched(&with_utm("tuesday"))).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert_eq!(entries_count(&conn), 1);
+
+        // the original link is kept
+        assert_eq!(
+            get_entry(&conn, 1).unwrap().link.as_deref(),
+            Some("https://example.com/article?utm_source=monday&utm_medium=rss")
+        );
+
+        // entries stored before links were normalized get normalized links when migrating
+        conn.execute("UPDATE entries SET normalized_link = NULL", [])
+            .unwrap();
+        conn.pragma_update(None, "user_version", 0).unwrap();
+        initialize_db(&mut conn).unwrap();
+
+        let refresh_summary =
+            merge_remote_feed(&mut conn, feed_id, fetched(&with_utm("wednesday"))).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert_eq!(entries_count(&conn), 1);
+    }
+
+    #[test]
+    fn refresh_keeps_entries_that_only_have_a_guid() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+
+        let remote = rss_channel(&[
+            rss_item("No link", None, Some("guid-1")),
+            rss_item("Nothing to key on", None, None),
+        ]);
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+
+        let entries = get_entries_metas(&conn, &ReadMode::All, feed_id).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title.as_deref(), Some("No link"));
+    }
+
+    #[test]
+    fn refresh_matches_entries_stored_before_guids_by_link() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[rss_item("One", Some("https://example.com/1"), None)]),
+        );
+
+        let remote = rss_channel(&[rss_item(
+            "One",
+            Some("https://example.com/1"),
+            Some("guid-1"),
+        )]);
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(entries_count(&conn), 1);
+    }
+
+    #[test]
+    fn refresh_updates_entries_whose_content_changed_in_place() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+        let before = get_entry_meta(&conn, 2).unwrap();
+
+        let remote = rss_fixture(4).replace(
+            "<description>Description 1</description>",
+            "<description>Description 1, now with a typo fixed</description>",
+        );
+
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+
+        assert_eq!(refresh_summary.inserted, vec![4]);
+        assert_eq!(refresh_summary.updated, vec![2]);
+        assert_eq!(entries_count(&conn), 4);
+
+        let after = get_entry_meta(&conn, 2).unwrap();
+        assert_eq!(after.inserted_at, before.inserted_at);
+        assert!(after.updated_at > before.updated_at);
+        assert_eq!(
+            get_entry_content(&conn, 2).unwrap().description.as_deref(),
+            Some("Description 1, now with a typo fixed")
+        );
+
+        // nothing changed since the last refresh
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert!(refresh_summary.updated.is_empty());
+        assert_eq!(refresh_summary.stats.duplicates, 4);
+    }
+
+    #[test]
+    fn refresh_counts_what_it_did_with_each_fetched_entry() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+
+        let remote = rss_fixture(4)
+            .replace(
+                "<description>Description 1</description>",
+                "<description>Description 1, edited</description>",
+            )
+            .replace(
+                "</channel>",
+                "<item><title>Neither a guid nor a link</title></item></channel>",
+            );
+
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(
+            refresh_summary.stats,
+            RefreshStats {
+                fetched: 5,
+                inserted: 2,
+                duplicates: 1,
+                updated: 1,
+                errors: 1,
+            }
+        );
+
+        let refresh_summary = merge_remote_feed(&mut conn, feed_id, fetched(&remote)).unwrap();
+        assert_eq!(
+            refresh_summary.stats,
+            RefreshStats {
+                fetched: 5,
+                inserted: 0,
+                duplicates: 4,
+                updated: 0,
+                errors: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn refresh_backfills_content_hashes_without_reporting_updates() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        conn.execute("UPDATE entries SET content_hash = NULL", [])
+            .unwrap();
+
+        let refresh_summary =
+            merge_remote_feed(&mut conn, feed_id, fetched(&rss_fixture(2))).unwrap();
+        assert!(refresh_summary.inserted.is_empty());
+        assert!(refresh_summary.updated.is_empty());
+        assert_eq!(refresh_summary.stats.duplicates, 2);
+
+        let missing_hashes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM entries WHERE content_hash IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(missing_hashes, 0);
+    }
+
+    #[test]
+    fn initialize_db_adds_missing_columns_to_an_existing_database() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE feeds (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              title TEXT,
+              feed_link TEXT,
+              link TEXT,
+              feed_kind TEXT,
+              refreshed_at TIMESTAMP,
+              inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+              updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO feeds (title, feed_link, feed_kind)
+            VALUES ('old', 'https://example.com/feed.xml', 'RSS')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "CREATE TABLE entries (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              feed_id INTEGER,
+              title TEXT,
+              author TEXT,
+              pub_date TIMESTAMP,
+              description TEXT,
+              content TEXT,
+              link TEXT,
+              read_at TIMESTAMP,
+              inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+              updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )
+        .unwrap();
+        let old_pub_date = "2023-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        conn.execute(
+            "INSERT INTO entries (feed_id, title, link, pub_date)
+            VALUES (1, 'old', 'https://example.com', ?1)",
+            [old_pub_date],
+        )
+        .unwrap();
+        // left behind by unsubscribing before entries had a foreign key
+        conn.execute(
+            "INSERT INTO entries (feed_id, title, link) VALUES (2, 'orphan', 'https://example.com')",
+            [],
+        )
+        .unwrap();
+
+        initialize_db(&mut conn).unwrap();
+        // running it again is a no-op
+        initialize_db(&mut conn).unwrap();
+
+        let guid: Option<String> = conn
+            .query_row("SELECT guid FROM entries WHERE title = 'old'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(guid, None);
+
+        // old entries only stored parsed dates, so they carry over
+        let old_entry = get_entry_meta(&conn, 1).unwrap();
+        assert_eq!(old_entry.published_at, Some(old_pub_date));
+
+        assert_eq!(entries_count(&conn), 1);
+    }
+
+    #[test]
+    fn deleting_a_feed_deletes_its_entries() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(3));
+        conn.execute(
+            "INSERT INTO entry_tags (entry_id, tag)
+            SELECT id, 'tag' FROM entries WHERE feed_id = ?1",
+            [feed_id],
+        )
+        .unwrap();
+
+        conn.execute("DELETE FROM feeds WHERE id = ?1", [feed_id])
+            .unwrap();
+
+        let feed_entries: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM entries WHERE feed_id = ?1",
+                [feed_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(feed_entries, 0);
+        assert!(list_tags(&conn).unwrap().is_empty());
+        assert_eq!(
+            get_entries_metas(&conn, &ReadMode::All, other_feed_id)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        assert_eq!(unsubscribe_feed(&mut conn, other_feed_id).unwrap(), 3);
+        assert_eq!(entries_count(&conn), 0);
+    }
+
+    #[test]
+    fn run_migrations_migrates_a_version_0_database_forward() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        in_transaction(&mut conn, create_feeds_and_entries).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+
+        let columns = |conn: &rusqlite::Connection, table: &str| {
+            let mut statement = conn
+                .prepare("SELECT name FROM pragma_table_info(?1)")
+                .unwrap();
+            let columns = statement
+                .query_map([table], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<String>>>()
+                .unwrap();
+            columns
+        };
+        assert!(!columns(&conn, "entries").contains(&"enclosure_url".to_owned()));
+
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_SCHEMA_VERSION);
+
+        let entries_columns = columns(&conn, "entries");
+        for column in [
+            "guid",
+            "content_hash",
+            "starred",
+            "published_at",
+            "enclosure_url",
+        ] {
+            assert!(entries_columns.contains(&column.to_owned()), "{column}");
+        }
+        let feeds_columns = columns(&conn, "feeds");
+        for column in ["etag", "last_modified", "last_error", "category"] {
+            assert!(feeds_columns.contains(&column.to_owned()), "{column}");
+        }
+
+        // running them again is a no-op
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn initializing_adds_the_columns_an_old_table_is_missing() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE feeds (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT)",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO feeds (title) VALUES ('Old')", [])
+            .unwrap();
+
+        initialize_db(&mut conn).unwrap();
+
+        let columns = conn
+            .prepare("SELECT name FROM pragma_table_info('feeds')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<String>>>()
+            .unwrap();
+        for column in [
+            "feed_link",
+            "link",
+            "feed_kind",
+            "refreshed_at",
+            "inserted_at",
+            "updated_at",
+            "etag",
+            "category",
+            "accept",
+        ] {
+            assert!(columns.contains(&column.to_owned()), "{column}");
+        }
+
+        let old_inserted_at: Option<DateTime<Utc>> = conn
+            .query_row(
+                "SELECT inserted_at FROM feeds WHERE title = 'Old'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(old_inserted_at.is_some());
+
+        // new feeds get the timestamps the table would have defaulted to
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Example"));
+        assert!(feed.inserted_at <= Utc::now());
+    }
+
+    #[test]
+    fn run_migrations_refuses_a_database_from_a_newer_version() {
+        let mut conn = test_db();
+        conn.pragma_update(None, "user_version", LATEST_SCHEMA_VERSION + 1)
+            .unwrap();
+
+        assert!(run_migrations(&mut conn).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_SCHEMA_VERSION + 1);
+    }
+
+    /// retries without making tests wait
+    fn test_http_client() -> HttpClient {
+        FetchOptions {
+            retry_base_delay: Duration::from_millis(1),
+            ..Default::default()
+        }
+        .http_client()
+    }
+
+    fn set_feed_link(conn: &rusqlite::Connection, feed_id: FeedId, feed_link: &str) {
+        conn.execute(
+            "UPDATE feeds SET feed_link = ?2 WHERE id = ?1",
+            params![feed_id, feed_link],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn refreshing_records_the_status_and_how_long_the_fetch_took() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/feed" if request.header("If-None-Match") == Some("\"v1\"") => {
+                TestResponse::status(304)
+            }
+            "/feed" => TestResponse::ok(rss_fixture(2)).with_header("ETag", "\"v1\""),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(2));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/feed"));
+
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.last_status, None);
+        assert_eq!(feed.last_fetch_duration, None);
+
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.last_status, Some(200));
+        assert!(feed.last_fetch_duration.is_some());
+
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert_eq!(get_feed(&conn, feed_id).unwrap().last_status, Some(304));
+
+        set_feed_link(&conn, feed_id, &format!("{base_url}/gone"));
+        let results = refresh_feeds(&http_client, &mut conn, &[feed_id], 1);
+        assert!(results[0].1.is_err());
+        assert_eq!(get_feed(&conn, feed_id).unwrap().last_status, Some(404));
+    }
+
+    #[test]
+    fn refreshing_feeds_fetches_them_at_the_same_time() {
+        use crate::test_server::{serve, TestResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let server_max_in_flight = max_in_flight.clone();
+
+        let base_url = serve(move |request| {
+            let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            server_max_in_flight.fetch_max(now_in_flight, Ordering::SeqCst);
+
+            // hold the request until the other one comes in, or until it
+            // would have, had the feeds been fetched at the same time
+            let started_at = std::time::Instant::now();
+            while server_max_in_flight.load(Ordering::SeqCst) < 2
+                && started_at.elapsed() < Duration::from_secs(2)
+            {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            match request.path.as_str() {
+                "/a" => TestResponse::ok(rss_fixture(3)),
+                "/b" => TestResponse::ok(rss_fixture(5)),
+                _ => TestResponse::status(404),
+            }
+        });
+
+        let mut conn = test_db();
+        let mut feed_ids = vec![];
+        for path in ["/a", "/b"] {
+            let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+            set_feed_link(&conn, feed_id, &format!("{base_url}{path}"));
+            feed_ids.push(feed_id);
+        }
+
+        let results = refresh_feeds(&test_http_client(), &mut conn, &feed_ids, 2);
+
+        assert!(
+            results.iter().all(|(_, result)| result.is_ok()),
+            "{results:?}"
+        );
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn subscribing_again_is_in_the_refresh_history() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|_request| TestResponse::ok(rss_fixture(2)));
+        let feed_url = format!("{base_url}/feed");
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id = subscribe_to_feed(&http_client, &mut conn, &feed_url).unwrap();
+        assert!(get_refresh_history(&conn, feed_id, 10).unwrap().is_empty());
+
+        assert_eq!(
+            subscribe_to_feed(&http_client, &mut conn, &feed_url).unwrap(),
+            feed_id
+        );
+        assert_eq!(get_refresh_history(&conn, feed_id, 10).unwrap().len(), 1);
+
+        subscribe_many(&http_client, &mut conn, &[feed_url], 1, |_, _| ());
+        let history = get_refresh_history(&conn, feed_id, 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|refresh| refresh.status == Some(200)));
+    }
+
+    #[test]
+    fn refreshing_keeps_a_history_of_each_feeds_refreshes() {
+        use crate::test_server::{serve, TestResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = AtomicUsize::new(0);
+        let base_url = serve(
+            move |_request| match requests.fetch_add(1, Ordering::SeqCst) {
+                0 => TestResponse::ok(rss_fixture(2)),
+                1 => TestResponse::ok(rss_fixture(4)),
+                _ => TestResponse::status(503),
+            },
+        );
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/feed"));
+
+        assert!(get_refresh_history(&conn, feed_id, 10).unwrap().is_empty());
+
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        refresh_feeds(&http_client, &mut conn, &[feed_id], 1);
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap_err();
+
+        let history = get_refresh_history(&conn, feed_id, 10).unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|refresh| (refresh.status, refresh.inserted, refresh.error.is_some()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(503), 0, true),
+                (Some(200), 2, false),
+                (Some(200), 1, false)
+            ]
+        );
+        assert!(history[0].refreshed_at >= history[1].refreshed_at);
+        assert_eq!(get_refresh_history(&conn, feed_id, 1).unwrap().len(), 1);
+        assert!(get_refresh_history(&conn, other_feed_id, 10)
+            .unwrap()
+            .is_empty());
+
+        for _ in 0..REFRESH_HISTORY_LEN {
+            log_refresh(
+                &mut conn,
+                feed_id,
+                Some(304),
+                Duration::from_millis(1),
+                &Ok(RefreshSummary::default()),
+            );
+        }
+        let history = get_refresh_history(&conn, feed_id, REFRESH_HISTORY_LEN * 2).unwrap();
+        assert_eq!(history.len(), REFRESH_HISTORY_LEN);
+        assert!(history.iter().all(|refresh| refresh.status == Some(304)));
+
+        unsubscribe_feed(&mut conn, feed_id).unwrap();
+        let refresh_log_len: i64 = conn
+            .query_row("SELECT COUNT(*) FROM refresh_log", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(refresh_log_len, 0);
+    }
+
+    #[test]
+    fn it_asks_for_a_feed_with_the_accept_header() {
+        use crate::test_server::{serve, TestResponse};
+
+        const HTML: &str = "<html><head><title>Not a feed</title></head></html>";
+
+        let base_url = serve(|request| {
+            let accept = request.header("Accept").unwrap_or_default();
+            match request.path.as_str() {
+                "/negotiated" if accept.contains("application/rss+xml") => {
+                    TestResponse::ok(rss_fixture(2))
+                }
+                "/custom" if accept == "application/x-custom-feed" => {
+                    TestResponse::ok(rss_fixture(3))
+                }
+                _ => TestResponse::ok(HTML).with_header("Content-Type", "text/html"),
+            }
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/negotiated")).unwrap();
+        assert_eq!(entries_count(&conn), 2);
+        assert_eq!(get_feed(&conn, feed_id).unwrap().accept, None);
+
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/custom"));
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+
+        set_feed_accept(&conn, feed_id, Some("application/x-custom-feed")).unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().accept.as_deref(),
+            Some("application/x-custom-feed")
+        );
+        let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert_eq!(refresh_summary.inserted.len(), 3);
+
+        set_feed_accept(&conn, feed_id, Some(" ")).unwrap();
+        assert_eq!(get_feed(&conn, feed_id).unwrap().accept, None);
+    }
+
+    #[test]
+    fn it_stores_the_article_an_entry_links_to() {
+        use crate::test_server::{serve, TestResponse};
+
+        const PAGE: &str = r#"<html>
+          <head><title>A post</title></head>
+          <body>
+            <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+            <article>
+              <h2>A post</h2>
+              <p>The whole article, not just the summary.</p>
+            </article>
+            <footer>Copyright</footer>
+          </body>
+        </html>"#;
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/post"
+                if request
+                    .header("Accept")
+                    .unwrap_or_default()
+                    .contains("text/html") =>
+            {
+                TestResponse::ok(PAGE).with_header("Content-Type", "text/html")
+            }
+            "/empty" => TestResponse::ok("<html><body><nav>Home</nav></body></html>"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[
+                rss_item("post", Some(&format!("{base_url}/post")), None),
+                rss_item("empty", Some(&format!("{base_url}/empty")), None),
+                rss_item("gone", Some(&format!("{base_url}/gone")), None),
+            ]),
+        );
+        let entry_ids = get_entries_metas(&conn, &ReadMode::All, feed_id)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.title.unwrap(), entry.id))
+            .collect::<HashMap<_, _>>();
+
+        fetch_full_content(&http_client, &conn, entry_ids["post"]).unwrap();
+        let post = get_entry(&conn, entry_ids["post"]).unwrap();
+        let text = html_to_plain_text(post.full_content.as_deref().unwrap()).unwrap();
+        assert!(text.contains("The whole article, not just the summary."));
+        assert!(!text.contains("About"));
+        assert!(!text.contains("Copyright"));
+        assert_eq!(post.full_content_error, None);
+        assert_eq!(post.best_body(), post.full_content.as_deref());
+
+        for title in ["empty", "gone"] {
+            assert!(fetch_full_content(&http_client, &conn, entry_ids[title]).is_err());
+            let entry = get_entry(&conn, entry_ids[title]).unwrap();
+            assert_eq!(entry.full_content, None);
+            assert!(entry.full_content_error.is_some());
+        }
+
+        assert!(matches!(
+            fetch_full_content(&http_client, &conn, 99)
+                .unwrap_err()
+                .downcast_ref::<Error>(),
+            Some(Error::EntryNotFound(99))
+        ));
+    }
 
-    let mut statement = conn.prepare(&query)?;
-    let mut entries = vec![];
-    for entry in statement.query_map([feed_id], |row| {
-        Ok(EntryMeta {
-            id: row.get(0)?,
-            feed_id: row.get(1)?,
-            title: row.get(2)?,
-            author: row.get(3)?,
-            pub_date: row.get(4)?,
-            link: row.get(5)?,
-            read_at: row.get(6)?,
-            inserted_at: row.get(7)?,
-            updated_at: row.get(8)?,
-        })
-    })? {
-        entries.push(entry?)
+    #[test]
+    fn it_refreshes_all_feeds_and_reports_each_result() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/a" => TestResponse::ok(rss_fixture(3)),
+            "/b" => TestResponse::ok(rss_fixture(5)),
+            "/c" => TestResponse::ok(rss_fixture(1)),
+            _ => TestResponse::status(404),
+        });
+
+        let mut conn = test_db();
+        let mut feed_ids = vec![];
+        for path in ["/a", "/b", "/c", "/missing"] {
+            let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+            set_feed_link(&conn, feed_id, &format!("{base_url}{path}"));
+            feed_ids.push(feed_id);
+        }
+
+        let mut results = refresh_all_feeds(&test_http_client(), &mut conn, 2).unwrap();
+        results.sort_by_key(|(feed_id, _)| *feed_id);
+
+        let inserted = results
+            .iter()
+            .map(|(feed_id, result)| {
+                (
+                    *feed_id,
+                    result
+                        .as_ref()
+                        .map(|refresh_summary| refresh_summary.inserted.len())
+                        .ok(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            inserted,
+            vec![
+                (feed_ids[0], Some(3)),
+                (feed_ids[1], Some(5)),
+                (feed_ids[2], Some(1)),
+                (feed_ids[3], None),
+            ]
+        );
+
+        // the failing feed did not keep the others from being stored
+        assert_eq!(entries_count(&conn), 9);
     }
 
-    Ok(entries)
-}
+    #[test]
+    fn refresh_sends_cache_validators_and_skips_not_modified_feeds() {
+        use crate::test_server::{serve, TestResponse};
 
-pub fn get_entries_links(
-    conn: &rusqlite::Connection,
-    read_mode: &ReadMode,
-    feed_id: FeedId,
-) -> Result<Vec<Option<String>>> {
-    let read_at_predicate = match read_mode {
-        ReadMode::ShowUnread => "\nAND read_at IS NULL",
-        ReadMode::ShowRead => "\nAND read_at IS NOT NULL",
-        ReadMode::All => "\n",
-    };
+        let base_url = serve(|request| {
+            if request.header("If-None-Match") == Some("\"v1\"")
+                && request.header("If-Modified-Since") == Some("Mon, 02 Jan 2023 00:00:00 GMT")
+            {
+                TestResponse::status(304)
+            } else {
+                TestResponse::ok(rss_fixture(3))
+                    .with_header("ETag", "\"v1\"")
+                    .with_header("Last-Modified", "Mon, 02 Jan 2023 00:00:00 GMT")
+            }
+        });
 
-    // we get weird pubDate formats from feeds,
-    // so sort by inserted at as this as a stable order at least
-    let mut query = "SELECT link FROM entries WHERE feed_id=?1".to_string();
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = subscribe_to_feed(&http_client, &mut conn, &base_url).unwrap();
 
-    query.push_str(read_at_predicate);
-    query.push_str("\nORDER BY pub_date DESC, inserted_at DESC");
+        let cache_validators = get_feed_request(&conn, feed_id).unwrap().cache_validators;
+        assert_eq!(
+            cache_validators,
+            CacheValidators {
+                etag: Some("\"v1\"".to_owned()),
+                last_modified: Some("Mon, 02 Jan 2023 00:00:00 GMT".to_owned()),
+            }
+        );
 
-    let mut links = vec![];
-    let mut statement = conn.prepare(&query)?;
+        // nothing in the entries table may change on a 304
+        conn.execute(
+            "CREATE TRIGGER entries_are_untouched BEFORE INSERT ON entries
+            BEGIN
+              SELECT RAISE(ABORT, 'entries were touched');
+            END",
+            [],
+        )
+        .unwrap();
 
-    for link in statement.query_map([feed_id], |row| row.get(0))? {
-        links.push(link?);
+        let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert_eq!(refresh_summary, RefreshSummary::default());
+        assert_eq!(entries_count(&conn), 3);
+
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert!(feed.refreshed_at.is_some());
+        // the 304 did not send validators, so the old ones are kept
+        let cache_validators_after = get_feed_request(&conn, feed_id).unwrap().cache_validators;
+        assert_eq!(cache_validators_after, cache_validators);
     }
 
-    Ok(links)
-}
+    #[test]
+    fn refresh_records_errors_and_clears_them_on_success() {
+        use crate::test_server::{serve, TestResponse};
 
-/// run `f` in a transaction, committing if `f` returns an `Ok` value,
-/// otherwise rolling back.
-fn in_transaction<F, R>(conn: &mut rusqlite::Connection, f: F) -> Result<R>
-where
-    F: Fn(&rusqlite::Transaction) -> Result<R>,
-{
-    let tx = conn.transaction()?;
+        let base_url = serve(|request| match request.path.as_str() {
+            "/feed" | "/fixed" => TestResponse::ok(rss_fixture(2)),
+            _ => TestResponse::status(404),
+        });
 
-    let result = f(&tx)?;
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        let healthy_feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/missing"));
+        set_feed_link(&conn, healthy_feed_id, &format!("{base_url}/feed"));
 
-    tx.commit()?;
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+        refresh_feed(&http_client, &mut conn, healthy_feed_id).unwrap();
 
-    Ok(result)
-}
+        let feeds_with_errors = get_feeds_with_errors(&conn).unwrap();
+        assert_eq!(feeds_with_errors.len(), 1);
+        assert_eq!(feeds_with_errors[0].id, feed_id);
+        let last_error = feeds_with_errors[0].last_error.as_deref().unwrap();
+        assert!(last_error.contains("/missing"));
+        assert!(last_error.contains("404"));
+        assert!(feeds_with_errors[0].last_error_at.is_some());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    const ZCT: &str = "https://zeroclarkthirty.com/feed";
+        set_feed_link(&conn, feed_id, &format!("{base_url}/fixed"));
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.last_error, None);
+        assert_eq!(feed.last_error_at, None);
+        assert!(get_feeds_with_errors(&conn).unwrap().is_empty());
+    }
 
     #[test]
-    fn it_fetches() {
-        let http_client = ureq::AgentBuilder::new()
-            .timeout_read(std::time::Duration::from_secs(5))
-            .build();
-        let feed_and_entries = fetch_feed(&http_client, ZCT).unwrap();
-        assert!(!feed_and_entries.entries.is_empty())
+    fn fetching_sends_the_user_agent_and_times_out_on_slow_servers() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/slow" => {
+                std::thread::sleep(Duration::from_secs(2));
+                TestResponse::ok(rss_fixture(1))
+            }
+            _ => match request.header("User-Agent") {
+                Some("russ-test/1.0") => TestResponse::ok(rss_fixture(1)),
+                _ => TestResponse::status(403),
+            },
+        });
+
+        let http_client = FetchOptions {
+            timeout: Duration::from_millis(200),
+            user_agent: "russ-test/1.0".to_owned(),
+            ..Default::default()
+        }
+        .http_client();
+
+        let fetch_result = fetch_feed(
+            &http_client,
+            &format!("{base_url}/feed"),
+            &CacheValidators::default(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(fetch_result.feed_and_entries.is_some());
+
+        let now = std::time::Instant::now();
+        let e = fetch_feed(
+            &http_client,
+            &format!("{base_url}/slow"),
+            &CacheValidators::default(),
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(e.downcast_ref::<Error>(), Some(Error::Timeout)));
+        assert!(now.elapsed() < Duration::from_secs(2));
     }
 
     #[test]
-    fn it_subscribes_to_a_feed() {
-        let http_client = ureq::AgentBuilder::new()
-            .timeout_read(std::time::Duration::from_secs(5))
-            .build();
-        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
-        initialize_db(&mut conn).unwrap();
-        subscribe_to_feed(&http_client, &mut conn, ZCT).unwrap();
-        let count: i64 = conn
-            .query_row("SELECT COUNT(*) FROM entries", [], |row| row.get(0))
-            .unwrap();
+    fn fetching_retries_transient_failures() {
+        use crate::test_server::{serve, TestResponse};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let server_requests = requests.clone();
+
+        let base_url = serve(move |request| {
+            let n = server_requests.fetch_add(1, Ordering::SeqCst);
+
+            match request.path.as_str() {
+                "/flaky" if n < 2 => TestResponse::status(503),
+                "/flaky" => TestResponse::ok(rss_fixture(1)),
+                "/busy" if n == 0 => TestResponse::status(429).with_header("Retry-After", "1"),
+                "/busy" => TestResponse::ok(rss_fixture(1)),
+                "/closed" => TestResponse::status(503).with_header("Retry-After", "3600"),
+                "/down" => TestResponse::status(500),
+                _ => TestResponse::status(404),
+            }
+        });
+
+        let http_client = test_http_client();
+        let fetch = |path: &str| {
+            requests.store(0, Ordering::SeqCst);
+            let result = fetch_feed(
+                &http_client,
+                &format!("{base_url}{path}"),
+                &CacheValidators::default(),
+                None,
+                None,
+            );
+            (result, requests.load(Ordering::SeqCst))
+        };
+
+        let (result, requests_len) = fetch("/flaky");
+        assert!(result.unwrap().feed_and_entries.is_some());
+        assert_eq!(requests_len, 3);
 
-        assert!(count > 50)
+        let now = std::time::Instant::now();
+        let (result, requests_len) = fetch("/busy");
+        assert!(result.is_ok());
+        assert_eq!(requests_len, 2);
+        assert!(now.elapsed() >= Duration::from_secs(1));
+
+        // too long to wait
+        let (result, requests_len) = fetch("/closed");
+        assert!(matches!(
+            result.err().unwrap().downcast_ref::<Error>(),
+            Some(Error::HttpStatus(503))
+        ));
+        assert_eq!(requests_len, 1);
+
+        // gives up after `retries`
+        let (result, requests_len) = fetch("/down");
+        assert!(matches!(
+            result.err().unwrap().downcast_ref::<Error>(),
+            Some(Error::HttpStatus(500))
+        ));
+        assert_eq!(requests_len, 3);
+
+        // not worth retrying
+        let (result, requests_len) = fetch("/missing");
+        assert!(matches!(
+            result.err().unwrap().downcast_ref::<Error>(),
+            Some(Error::HttpStatus(404))
+        ));
+        assert_eq!(requests_len, 1);
     }
 
     #[test]
-    fn refresh_feed_does_not_add_any_items_if_there_are_no_new_items() {
-        let http_client = ureq::AgentBuilder::new()
-            .timeout_read(std::time::Duration::from_secs(5))
-            .build();
-        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
-        initialize_db(&mut conn).unwrap();
-        subscribe_to_feed(&http_client, &mut conn, ZCT).unwrap();
-        let feed_id = 1;
-        let old_entries = get_entries_metas(&conn, &ReadMode::ShowUnread, feed_id).unwrap();
-        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
-        let e = get_entry_meta(&conn, 1).unwrap();
-        e.mark_as_read(&conn).unwrap();
-        let new_entries = get_entries_metas(&conn, &ReadMode::ShowUnread, feed_id).unwrap();
+    fn it_parses_retry_after() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(61)).to_rfc2822();
+        let delay = parse_retry_after(&in_a_minute).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(61));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
 
-        assert_eq!(new_entries.len(), old_entries.len() - 1);
+    #[test]
+    fn backoff_doubles_with_jitter() {
+        for retries in 0..4 {
+            let delay = backoff(Duration::from_secs(1), retries);
+            let max = Duration::from_secs(2u64.pow(retries));
+            assert!(delay <= max && delay >= max / 2, "{retries}: {delay:?}");
+        }
     }
 
     #[test]
-    fn build_bulk_insert_query() {
-        let entries = vec!["entry1", "entry2"];
-        let query = super::build_bulk_insert_query(
-            "entries",
-            &[
-                "feed_id",
-                "title",
-                "author",
-                "pub_date",
-                "description",
-                "content",
-                "link",
-                "updated_at",
-            ],
-            &entries,
+    fn refresh_follows_redirects_and_only_persists_permanent_ones() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/feed" => TestResponse::ok(rss_fixture(2)),
+            "/moved" => TestResponse::status(301).with_header("Location", "/also-moved"),
+            "/also-moved" => TestResponse::status(308).with_header("Location", "/feed"),
+            "/temporary" => TestResponse::status(302).with_header("Location", "/feed"),
+            "/moved-then-temporary" => {
+                TestResponse::status(301).with_header("Location", "/temporary")
+            }
+            "/loop" => TestResponse::status(301).with_header("Location", "/loop"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        for (path, expected_path) in [
+            ("/moved", "/feed"),
+            ("/temporary", "/temporary"),
+            ("/moved-then-temporary", "/temporary"),
+        ] {
+            let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+            set_feed_link(&conn, feed_id, &format!("{base_url}{path}"));
+
+            let refresh_summary = refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+            assert_eq!(refresh_summary.inserted.len(), 2);
+            assert_eq!(
+                get_feed_url(&conn, feed_id).unwrap(),
+                format!("{base_url}{expected_path}")
+            );
+
+            unsubscribe_feed(&mut conn, feed_id).unwrap();
+        }
+
+        // subscribing stores where the feed moved to
+        let feed_id =
+            subscribe_to_feed(&http_client, &mut conn, &format!("{base_url}/moved")).unwrap();
+        assert_eq!(
+            get_feed_url(&conn, feed_id).unwrap(),
+            format!("{base_url}/feed")
+        );
+
+        let feed_id = insert_fixture(&mut conn, &rss_channel(&[]));
+        set_feed_link(&conn, feed_id, &format!("{base_url}/loop"));
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+    }
+
+    fn self_linked_rss_fixture(self_link: &str) -> String {
+        rss_fixture(2)
+            .replace(
+                r#"<rss version="2.0">"#,
+                r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#,
+            )
+            .replace(
+                "<title>Example</title>",
+                &format!(
+                    r#"<title>Example</title>
+                    <atom:link href="{self_link}" rel="self" type="application/rss+xml"/>"#
+                ),
+            )
+    }
+
+    #[test]
+    fn it_captures_self_links() {
+        use crate::test_server::{serve, TestResponse};
+
+        let feed_and_entries =
+            FeedAndEntries::from_str(&self_linked_rss_fixture("https://example.com/rss")).unwrap();
+        assert_eq!(
+            feed_and_entries.feed.self_link.as_deref(),
+            Some("https://example.com/rss")
+        );
+
+        let atom = ATOM_FIXTURE.replace(
+            r#"<link href="https://example.com/"/>"#,
+            r#"<link href="https://example.com/"/>
+            <link rel="self" href="https://example.com/atom"/>"#,
+        );
+        let feed_and_entries = FeedAndEntries::from_str(&atom).unwrap();
+        assert_eq!(
+            feed_and_entries.feed.self_link.as_deref(),
+            Some("https://example.com/atom")
+        );
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/subscribe-here" | "/canonical" => {
+                TestResponse::ok(self_linked_rss_fixture("https://example.com/canonical"))
+            }
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let feed_id = subscribe_to_feed(
+            &http_client,
+            &mut conn,
+            &format!("{base_url}/subscribe-here"),
+        )
+        .unwrap();
+        let feed = get_feed(&conn, feed_id).unwrap();
+        assert_eq!(feed.feed_link, Some(format!("{base_url}/subscribe-here")));
+        assert_eq!(
+            feed.self_link.as_deref(),
+            Some("https://example.com/canonical")
+        );
+    }
+
+    #[test]
+    fn checking_feeds_health_tells_dead_feeds_from_live_ones() {
+        use crate::test_server::{serve, TestResponse};
+
+        let base_url = serve(|request| match request.path.as_str() {
+            "/ok" => TestResponse::ok(rss_fixture(2)),
+            "/unchanged" if request.header("If-None-Match") == Some("\"v1\"") => {
+                TestResponse::status(304)
+            }
+            "/not-a-feed" => TestResponse::ok("<html><body>Hello</body></html>")
+                .with_header("Content-Type", "text/html"),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+
+        let mut feed_ids = vec![];
+        for (title, url) in [
+            ("a", format!("{base_url}/ok")),
+            ("b", format!("{base_url}/unchanged")),
+            ("c", format!("{base_url}/gone")),
+            ("d", format!("{base_url}/not-a-feed")),
+            // nothing listens on port 1
+            ("e", "http://127.0.0.1:1/feed".to_owned()),
+            // gone, though where it says it lives is not
+            ("f", format!("{base_url}/moved-away")),
+            // without a url
+            ("g", format!("{base_url}/unlisted")),
+        ] {
+            let feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+            rename_feed(&conn, feed_id, title).unwrap();
+            set_feed_link(&conn, feed_id, &url);
+            feed_ids.push(feed_id);
+        }
+        conn.execute(
+            "UPDATE feeds SET etag = '\"v1\"' WHERE id = ?1",
+            [feed_ids[1]],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE feeds SET self_link = ?2 WHERE id = ?1",
+            params![feed_ids[5], format!("{base_url}/ok")],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE feeds SET feed_link = NULL WHERE id = ?1",
+            [feed_ids[6]],
+        )
+        .unwrap();
+
+        let health = check_feeds_health(&http_client, &conn, 2).unwrap();
+
+        assert_eq!(
+            health,
+            vec![
+                (feed_ids[0], FeedHealth::Ok),
+                (feed_ids[1], FeedHealth::NotModified),
+                (feed_ids[2], FeedHealth::HttpError(404)),
+                (feed_ids[3], FeedHealth::ParseError),
+                (feed_ids[4], FeedHealth::Unreachable),
+                (feed_ids[5], FeedHealth::HttpError(404)),
+                (feed_ids[6], FeedHealth::Unreachable),
+            ]
+        );
+
+        // nothing was written
+        assert_eq!(entries_count(&conn), 7);
+        for feed_id in feed_ids {
+            let feed = get_feed(&conn, feed_id).unwrap();
+            assert_eq!(feed.last_status, None);
+            assert_eq!(feed.refreshed_at, None);
+        }
+    }
+
+    #[test]
+    fn getting_every_feed_gets_the_same_feeds_as_getting_each_one() {
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &rss_channel(&[]).replace(
+                "<description>",
+                "<language>en</language><ttl>60</ttl><image><url>https://example.com/logo.png</url><title>Example</title><link>https://example.com</link></image><description>",
+            ),
+        );
+        set_feed_link(&conn, feed_id, "https://example.com/feed");
+        rename_feed(&conn, feed_id, "Renamed").unwrap();
+        set_feed_category(&conn, feed_id, Some("News")).unwrap();
+        set_feed_keeps_raw_body(&conn, feed_id, true).unwrap();
+        set_feed_accept(&conn, feed_id, Some("application/rss+xml")).unwrap();
+        set_feed_dedupe_strategy(&conn, feed_id, DedupeStrategy::Link).unwrap();
+        set_feed_max_content_bytes(&conn, feed_id, Some(1000)).unwrap();
+        set_feed_max_entries(&conn, feed_id, Some(10)).unwrap();
+        set_feed_refresh_interval(&conn, feed_id, Some(Duration::from_secs(3600))).unwrap();
+        let other_feed_id = insert_fixture(&mut conn, &rss_fixture(1));
+
+        let feeds = get_feeds(&conn).unwrap();
+
+        assert_eq!(
+            feeds.iter().map(|feed| feed.id).collect::<Vec<_>>(),
+            vec![other_feed_id, feed_id]
+        );
+        for feed in &feeds {
+            // `Feed` is not `PartialEq`, but its debug output has every field
+            assert_eq!(
+                format!("{feed:?}"),
+                format!("{:?}", get_feed(&conn, feed.id).unwrap())
+            );
+        }
+
+        let feed = &feeds[1];
+        assert_eq!(feed.title.as_deref(), Some("Renamed"));
+        assert_eq!(feed.feed_link.as_deref(), Some("https://example.com/feed"));
+        assert_eq!(feed.category.as_deref(), Some("News"));
+        assert!(feed.keeps_raw_body);
+        assert_eq!(feed.accept.as_deref(), Some("application/rss+xml"));
+        assert_eq!(feed.dedupe_strategy, DedupeStrategy::Link);
+        assert_eq!(feed.max_content_bytes, Some(1000));
+        assert_eq!(feed.max_entries, Some(10));
+        assert_eq!(feed.refresh_interval, Some(Duration::from_secs(3600)));
+        assert_eq!(feed.min_refresh_interval, Some(Duration::from_secs(3600)));
+        assert_eq!(
+            feed.image_url.as_deref(),
+            Some("https://example.com/logo.png")
+        );
+        assert_eq!(feed.language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn it_finds_feeds_by_language() {
+        let mut conn = test_db();
+
+        let rss = rss_channel(&[
+            r#"<item xml:lang="fr"><title>Bonjour</title><guid>1</guid></item>"#.to_owned(),
+            "<item><title>Hello</title><guid>2</guid></item>".to_owned(),
+        ])
+        .replace("<description>", "<language>en-US</language><description>");
+        let english_feed_id = insert_fixture(&mut conn, &rss);
+
+        let atom = ATOM_FIXTURE.replace(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom">"#,
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="de">"#,
+        );
+        let german_feed_id = insert_fixture(&mut conn, &atom);
+
+        let json = r#"{
+          "version": "https://jsonfeed.org/version/1.1",
+          "title": "Example",
+          "language": "en_GB",
+          "items": [{ "id": "1", "title": "Colour", "url": "https://example.com/1" }]
+        }"#;
+        let british_feed_id = insert_fixture(&mut conn, json);
+
+        insert_fixture(&mut conn, &rss_fixture(1));
+
+        let feed_ids = |language: &str| {
+            let mut feed_ids = get_feeds_by_language(&conn, language)
+                .unwrap()
+                .into_iter()
+                .map(|feed| feed.id)
+                .collect::<Vec<_>>();
+            feed_ids.sort();
+            feed_ids
+        };
+
+        assert_eq!(feed_ids("en"), vec![english_feed_id, british_feed_id]);
+        assert_eq!(feed_ids("EN-us"), vec![english_feed_id]);
+        assert_eq!(feed_ids("en-gb"), vec![british_feed_id]);
+        assert_eq!(feed_ids("de"), vec![german_feed_id]);
+        assert!(feed_ids("e").is_empty());
+        assert!(feed_ids("fr").is_empty());
+        assert!(feed_ids(" ").is_empty());
+
+        assert_eq!(
+            get_feed(&conn, british_feed_id)
+                .unwrap()
+                .language
+                .as_deref(),
+            Some("en-gb")
+        );
+
+        let entry_languages = |feed_id: FeedId| {
+            let mut entry_languages = get_entries_metas(&conn, &ReadMode::All, feed_id)
+                .unwrap()
+                .into_iter()
+                .map(|entry| {
+                    let entry = get_entry(&conn, entry.id).unwrap();
+                    (entry.title.unwrap(), entry.language)
+                })
+                .collect::<Vec<_>>();
+            entry_languages.sort();
+            entry_languages
+        };
+
+        assert_eq!(
+            entry_languages(english_feed_id),
+            vec![
+                ("Bonjour".to_owned(), Some("fr".to_owned())),
+                ("Hello".to_owned(), Some("en-us".to_owned())),
+            ]
+        );
+        assert_eq!(entry_languages(german_feed_id)[0].1.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn it_stores_the_feeds_image() {
+        let with_image = |url: &str| {
+            rss_channel(&[]).replace(
+                "<description>",
+                &format!(
+                    "<image><url>{url}</url><title>Example</title><link>https://example.com</link></image><description>"
+                ),
+            )
+        };
+
+        let mut conn = test_db();
+        let feed_id = insert_fixture(&mut conn, &with_image("https://example.com/logo.png"));
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().image_url.as_deref(),
+            Some("https://example.com/logo.png")
+        );
+
+        merge_remote_feed(
+            &mut conn,
+            feed_id,
+            fetched(&with_image("https://example.com/new-logo.png")),
+        )
+        .unwrap();
+        assert_eq!(
+            get_feed(&conn, feed_id).unwrap().image_url.as_deref(),
+            Some("https://example.com/new-logo.png")
+        );
+
+        merge_remote_feed(&mut conn, feed_id, fetched(&rss_channel(&[]))).unwrap();
+        assert_eq!(get_feed(&conn, feed_id).unwrap().image_url, None);
+
+        let atom = ATOM_FIXTURE.replace(
+            "<updated>2023-01-02T00:00:00Z</updated>\n      <id>",
+            "<updated>2023-01-02T00:00:00Z</updated>\n      <icon>https://example.com/favicon.ico</icon>\n      <logo>https://example.com/logo.svg</logo>\n      <id>",
+        );
+        assert_eq!(
+            FeedAndEntries::from_str(&atom)
+                .unwrap()
+                .feed
+                .image_url
+                .as_deref(),
+            Some("https://example.com/logo.svg")
+        );
+
+        let json = r#"{
+          "version": "https://jsonfeed.org/version/1.1",
+          "title": "Example",
+          "favicon": "https://example.com/favicon.png",
+          "items": []
+        }"#;
+        assert_eq!(
+            FeedAndEntries::from_str(json)
+                .unwrap()
+                .feed
+                .image_url
+                .as_deref(),
+            Some("https://example.com/favicon.png")
         );
+    }
+
+    #[test]
+    fn refresh_falls_back_to_the_self_link_when_the_feed_link_breaks() {
+        use crate::test_server::{serve, TestResponse};
+
+        let url = serve(|request| match request.path.as_str() {
+            "/canonical" => TestResponse::ok(rss_fixture(2)),
+            "/down" => TestResponse::status(500),
+            _ => TestResponse::status(404),
+        });
+
+        let http_client = test_http_client();
+        let mut conn = test_db();
+        let feed_id = insert_fixture(
+            &mut conn,
+            &self_linked_rss_fixture(&format!("{url}/canonical")),
+        );
+        set_feed_link(&conn, feed_id, &format!("{url}/gone"));
+
+        // a url that is only down for now is kept
+        set_feed_link(&conn, feed_id, &format!("{url}/down"));
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
+        assert_eq!(entries_count(&conn), 2);
+        assert_eq!(get_feed_url(&conn, feed_id).unwrap(), format!("{url}/down"));
+
+        // a url that is gone moves the feed to its self link.
+        // the feed at `/canonical` has no self link, so the refresh cleared it
+        conn.execute(
+            "UPDATE feeds SET self_link = ?1",
+            [format!("{url}/canonical")],
+        )
+        .unwrap();
+        set_feed_link(&conn, feed_id, &format!("{url}/gone"));
+        refresh_feed(&http_client, &mut conn, feed_id).unwrap();
         assert_eq!(
-            query,
-            "INSERT INTO entries(feed_id, title, author, pub_date, description, content, link, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8), (?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)"
+            get_feed_url(&conn, feed_id).unwrap(),
+            format!("{url}/canonical")
         );
+
+        // without a self link to fall back to, the refresh fails as before
+        conn.execute("UPDATE feeds SET self_link = NULL", [])
+            .unwrap();
+        set_feed_link(&conn, feed_id, &format!("{url}/gone"));
+        assert!(refresh_feed(&http_client, &mut conn, feed_id).is_err());
+    }
+
+    /// a database file that is deleted when the test is done with it,
+    /// for what in-memory databases can't do
+    struct TempDatabase {
+        path: std::path::PathBuf,
+    }
+
+    impl TempDatabase {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("russ-test-{name}-{}.db", std::process::id()));
+            Self { path }
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    #[test]
+    fn separate_databases_do_not_share_feeds() {
+        let mut work = open_in_memory_database().unwrap();
+        let mut personal = open_in_memory_database().unwrap();
+
+        let work_feed_id = insert_fixture(&mut work, &rss_fixture(2));
+        assert!(get_feeds(&personal).unwrap().is_empty());
+
+        insert_fixture(&mut personal, &rss_fixture(1));
+        insert_fixture(&mut personal, &rss_fixture(1));
+        assert_eq!(get_feed_ids(&work).unwrap(), vec![work_feed_id]);
+        assert_eq!(get_feeds(&personal).unwrap().len(), 2);
+
+        // a file keeps its feeds between connections
+        let database = TempDatabase::new("profile");
+        let feed_id = insert_fixture(&mut open_database(&database.path).unwrap(), &rss_fixture(1));
+        let reopened = open_database(&database.path).unwrap();
+        assert_eq!(get_feed_ids(&reopened).unwrap(), vec![feed_id]);
+        assert_eq!(schema_version(&reopened).unwrap(), LATEST_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn initialize_db_configures_the_connection() {
+        let database = TempDatabase::new("configure");
+        let mut conn = rusqlite::Connection::open(&database.path).unwrap();
+        initialize_db(&mut conn).unwrap();
+
+        let journal_mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let foreign_keys: bool = conn
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+
+        // 1 is NORMAL
+        let synchronous: i64 = conn
+            .pragma_query_value(None, "synchronous", |row| row.get(0))
+            .unwrap();
+        assert_eq!(synchronous, 1);
+    }
+
+    #[test]
+    fn connection_pools_read_from_several_threads_at_once() {
+        let database = TempDatabase::new("connection-pool");
+
+        let pool = connection_pool(&database.path).unwrap();
+        let feed_id = insert_fixture(&mut pool.get().unwrap(), &rss_fixture(5));
+
+        let journal_mode: String = pool
+            .get()
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let readers = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    let conn = pool.get().unwrap();
+                    query_entries(&conn, feed_id, &EntryQuery::default())
+                        .unwrap()
+                        .len()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 5);
+        }
     }
 
     #[test]
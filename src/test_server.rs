@@ -0,0 +1,142 @@
+//! A tiny HTTP/1.1 server for tests, so they don't have to go to the network.
+//! Every connection is handled on its own thread by `handler`,
+//! and every response closes its connection.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Debug)]
+pub struct TestRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl TestRequest {
+    /// the value of the first header named `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        TestResponse {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        TestResponse {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+/// a small RSS feed with two entries, see `serve_feed`
+pub const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <title>Example</title>
+    <link>https://example.com</link>
+    <description>An example feed</description>
+    <item><title>One</title><link>https://example.com/1</link></item>
+    <item><title>Two</title><link>https://example.com/2</link></item>
+  </channel>
+</rss>"#;
+
+/// like `serve`, with `FEED` at each of `paths` and a 404 everywhere else
+pub fn serve_feed(paths: &[&str]) -> String {
+    let paths = paths
+        .iter()
+        .map(|path| (*path).to_owned())
+        .collect::<Vec<_>>();
+
+    serve(move |request| {
+        if paths.contains(&request.path) {
+            TestResponse::ok(FEED)
+        } else {
+            TestResponse::status(404)
+        }
+    })
+}
+
+/// starts a server on a random local port, returning its base url,
+/// like `http://127.0.0.1:12345`
+pub fn serve<F>(handler: F) -> String
+where
+    F: Fn(&TestRequest) -> TestResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let handler = Arc::clone(&handler);
+
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("/")
+                    .to_owned();
+
+                let mut headers = vec![];
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+                    }
+                }
+
+                let response = handler(&TestRequest { path, headers });
+
+                let mut head = format!("HTTP/1.1 {} Test\r\n", response.status);
+                for (name, value) in &response.headers {
+                    head.push_str(&format!("{name}: {value}\r\n"));
+                }
+                head.push_str(&format!(
+                    "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.body.len()
+                ));
+
+                // the client may hang up early (e.g. on a timeout), which is fine
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&response.body);
+            });
+        }
+    });
+
+    format!("http://{addr}")
+}
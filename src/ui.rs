@@ -127,9 +127,12 @@ where
         text.push('\n');
     }
 
-    if let Some(pub_date) = &entry_meta.pub_date {
+    if let Some(published_at) = &entry_meta.published_at {
         text.push_str("Pub. date: ");
-        text.push_str(pub_date.to_string().as_str());
+        text.push_str(published_at.to_string().as_str());
+    } else if let Some(pub_date) = &entry_meta.pub_date {
+        text.push_str("Pub. date: ");
+        text.push_str(pub_date);
     } else {
         // TODO this should probably pull the <updated> tag
         // and use that
@@ -167,7 +170,8 @@ where
         .feeds
         .items
         .iter()
-        .flat_map(|feed| feed.title.as_ref())
+        // every feed needs a row, or selecting a row would select the wrong feed
+        .map(|feed| feed.display_title())
         .map(Span::raw)
         .map(ListItem::new)
         .collect::<Vec<ListItem>>();
@@ -229,10 +233,10 @@ where
         text.push('\n');
     }
 
-    if let Some(item) = app.entries.items.get(0) {
-        if let Some(pub_date) = &item.pub_date {
+    if let Some(item) = app.entries.items.first() {
+        if let Some(published_at) = &item.published_at {
             text.push_str("Most recent entry at: ");
-            text.push_str(pub_date.to_string().as_str());
+            text.push_str(published_at.to_string().as_str());
             text.push('\n');
         }
     }
@@ -285,6 +289,7 @@ where
     match app.selected {
         Selected::Feeds => {
             text.push_str("r - refresh selected feed; x - refresh all feeds\n");
+            text.push_str("m - mark all entries in selected feed read\n");
             text.push_str("c - copy link; o - open link in browser\n")
         }
         _ => {